@@ -0,0 +1,205 @@
+use crate::targets::get_optional_string;
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = ".gdscript-formatter-mcp.toml";
+const ROOT_MARKERS: [&str; 2] = [".git", "project.godot"];
+
+fn load_config(path: &Path) -> Result<Map<String, Value>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+    let parsed = text
+        .parse::<toml::Table>()
+        .map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))?;
+    match serde_json::to_value(parsed) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(format!("Config {} must be a table", path.display())),
+        Err(e) => Err(format!(
+            "Failed to convert config {}: {}",
+            path.display(),
+            e
+        )),
+    }
+}
+
+fn find_profile(config: &Map<String, Value>, name: &str) -> Result<Map<String, Value>, String> {
+    let profile = config
+        .get("profiles")
+        .and_then(|profiles| profiles.get(name))
+        .ok_or_else(|| format!("Profile '{name}' not found in {CONFIG_FILE_NAME}"))?;
+    profile
+        .as_object()
+        .cloned()
+        .ok_or_else(|| format!("Profile '{name}' in {CONFIG_FILE_NAME} must be a table"))
+}
+
+fn is_marker_root(dir: &Path) -> bool {
+    ROOT_MARKERS.iter().any(|marker| dir.join(marker).exists())
+}
+
+pub fn discover_config_files(start: &Path) -> Vec<PathBuf> {
+    let mut configs = Vec::new();
+    for dir in start.ancestors() {
+        let candidate = dir.join(CONFIG_FILE_NAME);
+        if candidate.is_file() {
+            configs.push(candidate);
+        }
+        if is_marker_root(dir) {
+            break;
+        }
+    }
+    configs
+}
+
+fn merge_tables(base: &mut Map<String, Value>, overlay: Map<String, Value>) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Object(base_table)), Value::Object(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn load_merged_config(start: &Path) -> Result<Option<Map<String, Value>>, String> {
+    let configs = discover_config_files(start);
+    if configs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut merged = Map::new();
+    for path in configs.iter().rev() {
+        merge_tables(&mut merged, load_config(path)?);
+    }
+    Ok(Some(merged))
+}
+
+fn config_start_dir(arguments: &Map<String, Value>) -> Result<PathBuf, String> {
+    let cwd =
+        env::current_dir().map_err(|e| format!("Failed to determine current directory: {e}"))?;
+    let start = if let Some(dir) = get_optional_string(arguments, "dir")? {
+        cwd.join(dir)
+    } else if let Some(file) = arguments
+        .get("files")
+        .and_then(Value::as_array)
+        .and_then(|files| files.first())
+        .and_then(Value::as_str)
+    {
+        let file = cwd.join(file);
+        file.parent().map(Path::to_path_buf).unwrap_or(file)
+    } else {
+        cwd
+    };
+    Ok(fs::canonicalize(&start).unwrap_or(start))
+}
+
+fn apply_profile_from(
+    arguments: &Map<String, Value>,
+    start: &Path,
+) -> Result<Map<String, Value>, String> {
+    let Some(name) = get_optional_string(arguments, "profile")? else {
+        return Ok(arguments.clone());
+    };
+    let Some(config) = load_merged_config(start)? else {
+        return Err(format!(
+            "Profile '{name}' requested but no {CONFIG_FILE_NAME} found from {}",
+            start.display()
+        ));
+    };
+
+    let mut merged = find_profile(&config, &name)?;
+    for (key, value) in arguments {
+        if key != "profile" {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(merged)
+}
+
+pub fn apply_profile(arguments: &Map<String, Value>) -> Result<Map<String, Value>, String> {
+    if !arguments.contains_key("profile") {
+        return Ok(arguments.clone());
+    }
+    apply_profile_from(arguments, &config_start_dir(arguments)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map_from_json(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn apply_profile_uses_defaults_and_explicit_args_win() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let config_path = temp.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            "[profiles.ci]\ncheck = true\nreorder_code = true\n",
+        )
+        .expect("write config");
+
+        let args = map_from_json(json!({
+            "profile": "ci",
+            "reorder_code": false
+        }));
+        let merged = apply_profile_from(&args, temp.path()).expect("apply profile");
+
+        assert_eq!(merged.get("check"), Some(&json!(true)));
+        assert_eq!(merged.get("reorder_code"), Some(&json!(false)));
+        assert!(!merged.contains_key("profile"));
+    }
+
+    #[test]
+    fn apply_profile_rejects_unknown_profile() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let config_path = temp.path().join(CONFIG_FILE_NAME);
+        fs::write(&config_path, "[profiles.ci]\ncheck = true\n").expect("write config");
+
+        let args = map_from_json(json!({"profile": "strict"}));
+        let err = apply_profile_from(&args, temp.path()).expect_err("should fail");
+        assert_eq!(
+            err,
+            "Profile 'strict' not found in .gdscript-formatter-mcp.toml"
+        );
+    }
+
+    #[test]
+    fn nested_configs_merge_with_closest_winning() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let root = temp.path();
+        let package = root.join("packages").join("player");
+        fs::create_dir_all(&package).expect("create package dir");
+        fs::create_dir(root.join(".git")).expect("create .git marker");
+        fs::write(
+            root.join(CONFIG_FILE_NAME),
+            "[profiles.ci]\ncheck = true\nreorder_code = true\n",
+        )
+        .expect("write root config");
+        fs::write(
+            package.join(CONFIG_FILE_NAME),
+            "[profiles.ci]\nreorder_code = false\nuse_spaces = true\n",
+        )
+        .expect("write package config");
+
+        let configs = discover_config_files(&package);
+        assert_eq!(configs.len(), 2);
+
+        let args = map_from_json(json!({
+            "profile": "ci",
+            "dir": package.to_string_lossy().to_string()
+        }));
+        let merged = apply_profile(&args).expect("apply profile");
+        assert_eq!(merged.get("check"), Some(&json!(true)));
+        assert_eq!(merged.get("reorder_code"), Some(&json!(false)));
+        assert_eq!(merged.get("use_spaces"), Some(&json!(true)));
+    }
+}
@@ -1,17 +1,132 @@
-use reqwest::blocking::Client;
-use reqwest::header::{ACCEPT, USER_AGENT};
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{ACCEPT, ETAG, IF_NONE_MATCH, USER_AGENT};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs::{self, File};
-use std::io;
+use std::io::{self, Read, Seek, Write};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tempfile::tempdir_in;
 use zip::ZipArchive;
 
+use crate::tools::lint::DEFAULT_MAX_DIAGNOSTICS;
+
 pub const SERVER_NAME: &str = "gdscript-formatter-mcp";
-const LATEST_RELEASE_API_URL: &str =
-    "https://api.github.com/repos/GDQuest/GDScript-formatter/releases/latest";
+const MAX_NESTED_ZIP_BYTES: u64 = 256 * 1024 * 1024;
+const INSTALL_RETRIES_ENV: &str = "GDSCRIPT_FORMATTER_MCP_INSTALL_RETRIES";
+const INSTALL_BACKOFF_MS_ENV: &str = "GDSCRIPT_FORMATTER_MCP_INSTALL_BACKOFF_MS";
+pub const FORMATTER_VERSION_ENV: &str = "GDSCRIPT_FORMATTER_VERSION";
+const OFFLINE_ENV: &str = "GDSCRIPT_FORMATTER_MCP_OFFLINE";
+const DEFAULT_INSTALL_BACKOFF_MS: u64 = 1000;
+const INSTALL_LOCK_TIMEOUT_MS_ENV: &str = "GDSCRIPT_FORMATTER_MCP_INSTALL_LOCK_TIMEOUT_MS";
+const DEFAULT_INSTALL_LOCK_TIMEOUT_MS: u64 = 120_000;
+const MAX_DIAGNOSTICS_ENV: &str = "GDSCRIPT_FORMATTER_MCP_MAX_DIAGNOSTICS";
+const INSTALL_LOCK_FILE_NAME: &str = ".lock";
+const PINNED_TAG_FILE_NAME: &str = "PINNED";
+const INSTALL_LOCK_POLL: Duration = Duration::from_millis(50);
+/// A lock older than this is assumed to belong to a crashed process.
+const STALE_INSTALL_LOCK_AGE: Duration = Duration::from_secs(15 * 60);
+const REPO_ENV: &str = "GDSCRIPT_FORMATTER_MCP_REPO";
+const API_BASE_ENV: &str = "GDSCRIPT_FORMATTER_MCP_API_BASE";
+const DEFAULT_REPO: &str = "GDQuest/GDScript-formatter";
+const DEFAULT_API_BASE: &str = "https://api.github.com";
+const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+const MCP_GITHUB_TOKEN_ENV: &str = "GDSCRIPT_FORMATTER_MCP_GITHUB_TOKEN";
+
+#[derive(Debug)]
+pub enum BinaryError {
+    UnsupportedPlatform(String),
+    NetworkUnavailable(String),
+    CacheUnwritable(String),
+    BinaryInvalid(String),
+    EnvPathMissing(String),
+    ReleaseNotFound(String),
+    Offline(String),
+    RateLimited(String),
+}
+
+impl BinaryError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnsupportedPlatform(_) => "unsupported_platform",
+            Self::NetworkUnavailable(_) => "network_unavailable",
+            Self::CacheUnwritable(_) => "cache_unwritable",
+            Self::BinaryInvalid(_) => "binary_invalid",
+            Self::EnvPathMissing(_) => "env_path_missing",
+            Self::ReleaseNotFound(_) => "release_not_found",
+            Self::Offline(_) => "offline",
+            Self::RateLimited(_) => "rate_limited",
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            Self::UnsupportedPlatform(message)
+            | Self::NetworkUnavailable(message)
+            | Self::CacheUnwritable(message)
+            | Self::BinaryInvalid(message)
+            | Self::EnvPathMissing(message)
+            | Self::ReleaseNotFound(message)
+            | Self::Offline(message)
+            | Self::RateLimited(message) => message,
+        }
+    }
+
+    fn context(self, prefix: &str) -> Self {
+        let message = format!("{prefix}: {}", self.message());
+        match self {
+            Self::UnsupportedPlatform(_) => Self::UnsupportedPlatform(message),
+            Self::NetworkUnavailable(_) => Self::NetworkUnavailable(message),
+            Self::CacheUnwritable(_) => Self::CacheUnwritable(message),
+            Self::BinaryInvalid(_) => Self::BinaryInvalid(message),
+            Self::EnvPathMissing(_) => Self::EnvPathMissing(message),
+            Self::ReleaseNotFound(_) => Self::ReleaseNotFound(message),
+            Self::Offline(_) => Self::Offline(message),
+            Self::RateLimited(_) => Self::RateLimited(message),
+        }
+    }
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<BinaryError> for String {
+    fn from(err: BinaryError) -> Self {
+        err.to_string()
+    }
+}
+
+enum AssetError {
+    NotFound(String),
+    Failed(BinaryError),
+}
+
+impl From<BinaryError> for AssetError {
+    fn from(err: BinaryError) -> Self {
+        Self::Failed(err)
+    }
+}
+
+impl From<AssetError> for BinaryError {
+    fn from(err: AssetError) -> Self {
+        match err {
+            AssetError::NotFound(message) => Self::NetworkUnavailable(message),
+            AssetError::Failed(err) => err,
+        }
+    }
+}
 
 #[derive(Debug, Deserialize)]
 struct ReleaseInfo {
@@ -25,23 +140,144 @@ struct ReleaseAsset {
     browser_download_url: String,
 }
 
+enum ReleaseFetch {
+    Modified {
+        release: ReleaseInfo,
+        etag: Option<String>,
+    },
+    NotModified,
+}
+
+pub struct BinaryStatus {
+    pub path: PathBuf,
+    pub version: Option<String>,
+    pub downloaded: bool,
+}
+
+pub const UNKNOWN_VERSION: &str = "unknown";
+
+/// What `force_update` installed, the tag recorded in `PINNED` afterwards,
+/// and the `GDSCRIPT_FORMATTER_VERSION` pin that overrides both when set.
+pub struct UpdateOutcome {
+    pub status: BinaryStatus,
+    pub pinned: Option<String>,
+    pub env_pin: Option<String>,
+}
+
+/// The formatter that would be used right now, without downloading anything.
+pub struct InstalledFormatter {
+    pub path: Option<PathBuf>,
+    pub version: String,
+}
+
+fn describe_installed_formatter(
+    binary_path: &Path,
+    version_file_path: Option<&Path>,
+    probe_version: impl FnOnce(&Path) -> Option<String>,
+) -> InstalledFormatter {
+    if !binary_path.exists() {
+        return InstalledFormatter {
+            path: None,
+            version: UNKNOWN_VERSION.to_owned(),
+        };
+    }
+    let version = version_file_path
+        .and_then(read_installed_tag)
+        .filter(|tag| !tag.is_empty())
+        .or_else(|| probe_version(binary_path))
+        .unwrap_or_else(|| UNKNOWN_VERSION.to_owned());
+    InstalledFormatter {
+        path: Some(binary_path.to_path_buf()),
+        version,
+    }
+}
+
+#[derive(Clone)]
+pub struct CacheRootSelection {
+    pub path: PathBuf,
+    pub source: &'static str,
+    pub rejected: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 struct PlatformInfo {
     os: String,
     arch: String,
+    /// `musl` or `gnu` on Linux, `None` elsewhere.
+    libc: Option<String>,
     binary_name: String,
 }
 
+impl PlatformInfo {
+    /// Cache subdirectory for this platform, e.g. `linux-x86_64`. musl hosts
+    /// get their own `linux-x86_64-musl` so a cache shared with a glibc host
+    /// never hands either of them the other's binary.
+    fn cache_dir_name(&self) -> String {
+        match self.libc.as_deref() {
+            Some("musl") => format!("{}-{}-musl", self.os, self.arch),
+            _ => format!("{}-{}", self.os, self.arch),
+        }
+    }
+}
+
+const PREVIEW_TTL: Duration = Duration::from_secs(600);
+
+/// A file a format preview would rewrite: the hash of the content the preview
+/// saw and the formatted bytes to write on apply.
+pub struct PendingFile {
+    pub path: PathBuf,
+    pub original_hash: String,
+    pub formatted: Vec<u8>,
+}
+
+struct PendingPreview {
+    created: Instant,
+    files: Vec<PendingFile>,
+}
+
+/// Binary path, its modification time and size, and the arguments. The stamp
+/// makes a binary replaced in place (e.g. by `gdscript_update`) miss the cache.
+type OutputCacheKey = (PathBuf, Option<(SystemTime, u64)>, Vec<String>);
+
 pub struct FormatterManager {
-    cache_root: PathBuf,
+    cache_root: Mutex<CacheRootSelection>,
     platform: Option<PlatformInfo>,
     client: Client,
+    releases_api_url: String,
+    github_token: Option<String>,
+    offline: bool,
+    output_cache: Mutex<HashMap<OutputCacheKey, String>>,
+    shutdown_requested: AtomicBool,
+    pending_previews: Mutex<HashMap<String, PendingPreview>>,
+    preview_counter: AtomicU64,
+    install_retries: u64,
+    install_backoff: Duration,
+    install_lock_timeout: Duration,
+    max_diagnostics: usize,
 }
 
 impl FormatterManager {
     pub fn new() -> Result<Self, String> {
         let platform = detect_platform();
         let cache_root = resolve_cache_root()?;
+        let releases_api_url = releases_api_url(env::var_os(REPO_ENV), env::var_os(API_BASE_ENV))?;
+        let install_retries = env_u64(INSTALL_RETRIES_ENV, env::var_os(INSTALL_RETRIES_ENV), 0)?;
+        let install_backoff = Duration::from_millis(env_u64(
+            INSTALL_BACKOFF_MS_ENV,
+            env::var_os(INSTALL_BACKOFF_MS_ENV),
+            DEFAULT_INSTALL_BACKOFF_MS,
+        )?);
+        let install_lock_timeout = Duration::from_millis(env_u64(
+            INSTALL_LOCK_TIMEOUT_MS_ENV,
+            env::var_os(INSTALL_LOCK_TIMEOUT_MS_ENV),
+            DEFAULT_INSTALL_LOCK_TIMEOUT_MS,
+        )?);
+        let max_diagnostics = env_u64(
+            MAX_DIAGNOSTICS_ENV,
+            env::var_os(MAX_DIAGNOSTICS_ENV),
+            DEFAULT_MAX_DIAGNOSTICS as u64,
+        )?;
+        let max_diagnostics = usize::try_from(max_diagnostics).unwrap_or(usize::MAX);
 
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
@@ -49,223 +285,1310 @@ impl FormatterManager {
             .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
 
         Ok(Self {
-            cache_root,
+            cache_root: Mutex::new(cache_root),
             platform,
             client,
+            releases_api_url,
+            github_token: github_token(
+                env::var_os(MCP_GITHUB_TOKEN_ENV),
+                env::var_os(GITHUB_TOKEN_ENV),
+            ),
+            offline: offline_from_env(env::var_os(OFFLINE_ENV)),
+            output_cache: Mutex::new(HashMap::new()),
+            shutdown_requested: AtomicBool::new(false),
+            pending_previews: Mutex::new(HashMap::new()),
+            preview_counter: AtomicU64::new(0),
+            install_retries,
+            install_backoff,
+            install_lock_timeout,
+            max_diagnostics,
         })
     }
 
-    pub fn ensure_binary(&self) -> Result<PathBuf, String> {
+    /// `max_diagnostics` when a lint call does not pass one.
+    pub fn max_diagnostics(&self) -> usize {
+        self.max_diagnostics
+    }
+
+    pub fn preview_ttl(&self) -> Duration {
+        PREVIEW_TTL
+    }
+
+    /// Stores a preview and returns the token that `take_preview` accepts
+    /// until the preview expires.
+    pub fn store_preview(&self, files: Vec<PendingFile>) -> String {
+        let sequence = self.preview_counter.fetch_add(1, Ordering::SeqCst);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let token = format!("{:x}{:x}{sequence:x}", std::process::id(), nanos);
+        let mut previews = self
+            .pending_previews
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        previews.retain(|_, preview| preview.created.elapsed() < PREVIEW_TTL);
+        previews.insert(
+            token.clone(),
+            PendingPreview {
+                created: Instant::now(),
+                files,
+            },
+        );
+        token
+    }
+
+    /// Removes and returns the preview for `token`, or `None` when it is
+    /// unknown or has expired.
+    pub fn take_preview(&self, token: &str) -> Option<Vec<PendingFile>> {
+        let preview = self
+            .pending_previews
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(token)?;
+        (preview.created.elapsed() < PREVIEW_TTL).then_some(preview.files)
+    }
+
+    pub fn request_shutdown(&self) {
+        self.shutdown_requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown_requested.load(Ordering::SeqCst)
+    }
+
+    pub fn cache_root(&self) -> PathBuf {
+        self.cache_root_selection().path
+    }
+
+    pub fn cache_root_selection(&self) -> CacheRootSelection {
+        self.cache_root
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn cached_output(&self, binary: &Path, args: &[&str]) -> Result<String, String> {
+        let stamp = fs::metadata(binary)
+            .ok()
+            .and_then(|metadata| Some((metadata.modified().ok()?, metadata.len())));
+        let key = (
+            binary.to_path_buf(),
+            stamp,
+            args.iter().map(|arg| (*arg).to_owned()).collect::<Vec<_>>(),
+        );
+        if let Some(text) = self
+            .output_cache
+            .lock()
+            .map_err(|_| "Formatter output cache is poisoned".to_owned())?
+            .get(&key)
+        {
+            return Ok(text.clone());
+        }
+
+        let output = Command::new(binary)
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to execute formatter: {e}"))?;
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let text = if stdout.trim().is_empty() {
+            String::from_utf8_lossy(&output.stderr).to_string()
+        } else {
+            stdout
+        };
+        if !output.status.success() {
+            return Err(format!(
+                "`{}` exited with {}: {}",
+                args.join(" "),
+                output.status,
+                text.trim()
+            ));
+        }
+
+        self.output_cache
+            .lock()
+            .map_err(|_| "Formatter output cache is poisoned".to_owned())?
+            .insert(key, text.clone());
+        Ok(text)
+    }
+
+    /// Reports the formatter in use from the cache (`VERSION` file, falling
+    /// back to `--version`). Never downloads; unknown when nothing is cached.
+    pub fn installed_formatter(&self) -> InstalledFormatter {
+        self.describe_current_formatter(|binary| {
+            self.cached_output(binary, &["--version"])
+                .ok()
+                .map(|text| text.trim().to_owned())
+                .filter(|text| !text.is_empty())
+        })
+    }
+
+    /// Like [`Self::installed_formatter`], but only reads the cached
+    /// `VERSION` file and never runs the binary, for paths such as
+    /// `initialize` that must answer immediately.
+    pub fn installed_formatter_from_cache(&self) -> InstalledFormatter {
+        self.describe_current_formatter(|_| None)
+    }
+
+    fn describe_current_formatter(
+        &self,
+        probe: impl FnOnce(&Path) -> Option<String>,
+    ) -> InstalledFormatter {
+        if let Some(path) = env::var_os("GDSCRIPT_FORMATTER_PATH") {
+            return describe_installed_formatter(Path::new(&path), None, probe);
+        }
+        let Some(platform) = self.platform.as_ref() else {
+            return describe_installed_formatter(Path::new(""), None, probe);
+        };
+        if let Some((tag, _)) = self.pinned(platform) {
+            let version_dir = tag_install_dir(&self.cache_root(), platform, &tag);
+            return describe_installed_formatter(
+                &version_dir.join(&platform.binary_name),
+                Some(&version_dir.join("VERSION")),
+                probe,
+            );
+        }
+        let platform_dir = self.cache_root().join(platform.cache_dir_name());
+        describe_installed_formatter(
+            &platform_dir.join(&platform.binary_name),
+            Some(&platform_dir.join("VERSION")),
+            probe,
+        )
+    }
+
+    pub fn ensure_binary(&self) -> Result<PathBuf, BinaryError> {
+        self.ensure_binary_status().map(|status| status.path)
+    }
+
+    pub fn ensure_binary_status(&self) -> Result<BinaryStatus, BinaryError> {
         if let Some(path) = env::var_os("GDSCRIPT_FORMATTER_PATH") {
             let path = PathBuf::from(path);
             if path.exists() {
-                return Ok(path);
+                return Ok(BinaryStatus {
+                    path,
+                    version: None,
+                    downloaded: false,
+                });
             }
-            return Err(format!(
+            return Err(BinaryError::EnvPathMissing(format!(
                 "GDSCRIPT_FORMATTER_PATH points to a missing file: {}",
                 path.display()
-            ));
+            )));
         }
 
-        let platform = self.platform.as_ref().ok_or_else(|| {
-            format!(
+        let platform = self.require_platform()?;
+        if self.offline {
+            return self.cached_binary_offline(platform);
+        }
+
+        let (retries, backoff) = (self.install_retries, self.install_backoff);
+        if let Some((tag, origin)) = self.pinned(platform) {
+            validate_release_tag(&tag)?;
+            return retry_install(retries, backoff, || self.install_tag(platform, &tag))
+                .map_err(|err| err.context(&origin));
+        }
+        retry_install(retries, backoff, || {
+            let current = self.cache_root_selection();
+            let (result, switched) =
+                with_cache_root_fallback(&current, cache_root_candidates(), |root| {
+                    self.ensure_cached_binary(platform, root)
+                });
+            if let Some(selection) = switched {
+                *self
+                    .cache_root
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = selection;
+            }
+            result
+        })
+    }
+
+    pub fn ensure_binary_for_tag(&self, tag: &str) -> Result<PathBuf, BinaryError> {
+        validate_release_tag(tag)?;
+        let platform = self.require_platform()?;
+        self.install_tag(platform, tag).map(|status| status.path)
+    }
+
+    /// Resolves `tag` to its own `versions/<tag>` cache slot, downloading it
+    /// only when that slot is empty, so pinned versions never clobber each
+    /// other or the latest-tracking binary.
+    fn install_tag(&self, platform: &PlatformInfo, tag: &str) -> Result<BinaryStatus, BinaryError> {
+        let cache_root = self.cache_root();
+        let version_dir = tag_install_dir(&cache_root, platform, tag);
+        let binary_path = version_dir.join(&platform.binary_name);
+        if binary_path.exists() {
+            return Ok(BinaryStatus {
+                path: binary_path,
+                version: Some(tag.to_owned()),
+                downloaded: false,
+            });
+        }
+        fs::create_dir_all(&version_dir).map_err(|e| {
+            BinaryError::CacheUnwritable(format!(
+                "Failed to create version cache dir {}: {}",
+                version_dir.display(),
+                e
+            ))
+        })?;
+
+        with_install_lock(
+            &version_dir,
+            self.install_lock_timeout,
+            || None,
+            || {
+                if binary_path.exists() {
+                    return Ok(BinaryStatus {
+                        path: binary_path.clone(),
+                        version: Some(tag.to_owned()),
+                        downloaded: false,
+                    });
+                }
+                install_release(
+                    &self.fetch_release_by_tag(tag)?,
+                    platform,
+                    &binary_path,
+                    &version_dir.join("VERSION"),
+                    |release, asset| {
+                        self.download_release_asset(release, asset, &cache_root, &binary_path)
+                    },
+                    || self.fetch_release_by_tag(tag),
+                    true,
+                )
+            },
+        )
+    }
+
+    /// The tag the formatter is pinned to, with where the pin came from:
+    /// `GDSCRIPT_FORMATTER_VERSION`, else the pin `force_update` recorded.
+    fn pinned(&self, platform: &PlatformInfo) -> Option<(String, String)> {
+        if let Some(tag) = pinned_tag(env::var_os(FORMATTER_VERSION_ENV)) {
+            let origin = format!("{FORMATTER_VERSION_ENV}={tag}");
+            return Some((tag, origin));
+        }
+        let tag = self.recorded_pin(platform)?;
+        let origin = format!("{} ({tag})", self.pin_file_path(platform).display());
+        Some((tag, origin))
+    }
+
+    fn pin_file_path(&self, platform: &PlatformInfo) -> PathBuf {
+        self.cache_root()
+            .join(platform.cache_dir_name())
+            .join(PINNED_TAG_FILE_NAME)
+    }
+
+    /// The tag `gdscript_update` recorded with `pin: true`, if any.
+    fn recorded_pin(&self, platform: &PlatformInfo) -> Option<String> {
+        read_installed_tag(&self.pin_file_path(platform))
+            .filter(|tag| validate_release_tag(tag).is_ok())
+    }
+
+    /// Re-downloads the formatter even when the cached `VERSION` matches.
+    /// Without `tag` this replaces the default cached binary with the latest
+    /// release; with `tag` it refreshes that version's `versions/<tag>` slot.
+    /// The recorded pin only changes when asked: `pin: Some(true)` pins
+    /// later calls to `tag`, `Some(false)` returns them to the latest.
+    pub fn force_update(
+        &self,
+        tag: Option<&str>,
+        pin: Option<bool>,
+    ) -> Result<UpdateOutcome, BinaryError> {
+        if let Some(tag) = tag {
+            validate_release_tag(tag)?;
+        }
+        let platform = self.require_platform()?;
+
+        let cache_root = self.cache_root();
+        let install_dir = match tag {
+            Some(tag) => tag_install_dir(&cache_root, platform, tag),
+            None => cache_root.join(platform.cache_dir_name()),
+        };
+        fs::create_dir_all(&install_dir).map_err(|e| {
+            BinaryError::CacheUnwritable(format!(
+                "Failed to create formatter cache dir {}: {}",
+                install_dir.display(),
+                e
+            ))
+        })?;
+
+        let fetch = || match tag {
+            Some(tag) => self.fetch_release_by_tag(tag),
+            None => self.fetch_latest_release(),
+        };
+        let binary_path = install_dir.join(&platform.binary_name);
+        let status = with_install_lock(
+            &install_dir,
+            self.install_lock_timeout,
+            || None,
+            || {
+                install_release(
+                    &fetch()?,
+                    platform,
+                    &binary_path,
+                    &install_dir.join("VERSION"),
+                    |release, asset| {
+                        self.download_release_asset(release, asset, &cache_root, &binary_path)
+                    },
+                    fetch,
+                    true,
+                )
+            },
+        )?;
+        if let Some(pin) = pin {
+            record_pin(
+                &cache_root.join(platform.cache_dir_name()),
+                tag.filter(|_| pin),
+            )?;
+        }
+        Ok(UpdateOutcome {
+            status,
+            pinned: self.recorded_pin(platform),
+            env_pin: pinned_tag(env::var_os(FORMATTER_VERSION_ENV)),
+        })
+    }
+
+    /// Resolves the binary the online path would use, from cache only.
+    fn cached_binary_offline(&self, platform: &PlatformInfo) -> Result<BinaryStatus, BinaryError> {
+        let pinned = self.pinned(platform).map(|(tag, _)| tag);
+        let cache_root = self.cache_root();
+        let install_dir = match &pinned {
+            Some(tag) => tag_install_dir(&cache_root, platform, tag),
+            None => cache_root.join(platform.cache_dir_name()),
+        };
+        let binary_path = install_dir.join(&platform.binary_name);
+        if !binary_path.exists() {
+            return Err(BinaryError::Offline(format!(
+                "Offline mode ({OFFLINE_ENV}) and no cached formatter found at {}",
+                binary_path.display()
+            )));
+        }
+        Ok(BinaryStatus {
+            path: binary_path,
+            version: read_installed_tag(&install_dir.join("VERSION")).or(pinned),
+            downloaded: false,
+        })
+    }
+
+    /// Every GitHub request goes through here so offline mode can refuse
+    /// them before a connection (and its timeout) is attempted.
+    fn http_get(&self, url: &str) -> Result<RequestBuilder, BinaryError> {
+        if self.offline {
+            return Err(BinaryError::Offline(format!(
+                "Offline mode ({OFFLINE_ENV}) does not allow network access: {url}"
+            )));
+        }
+        let request = self.client.get(url).header(
+            USER_AGENT,
+            format!("{}/{}", SERVER_NAME, env!("CARGO_PKG_VERSION")),
+        );
+        Ok(match &self.github_token {
+            Some(token) if token_allowed_for(url, &self.releases_api_url) => {
+                request.bearer_auth(token)
+            }
+            _ => request,
+        })
+    }
+
+    /// Turns a 403/429 rate-limit response into a `RateLimited` error that
+    /// says how to raise the limit, instead of the bare status error.
+    fn reject_rate_limited(&self, response: Response) -> Result<Response, BinaryError> {
+        if !matches!(
+            response.status(),
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        ) {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        Err(
+            rate_limit_error(&body, self.github_token.is_some()).unwrap_or_else(|| {
+                BinaryError::NetworkUnavailable(format!(
+                    "GitHub returned {status}: {}",
+                    body.trim()
+                ))
+            }),
+        )
+    }
+
+    fn require_platform(&self) -> Result<&PlatformInfo, BinaryError> {
+        self.platform.as_ref().ok_or_else(|| {
+            BinaryError::UnsupportedPlatform(format!(
                 "Unsupported platform for gdscript-formatter: os={} arch={}",
                 env::consts::OS,
                 env::consts::ARCH
-            )
-        })?;
+            ))
+        })
+    }
 
-        let platform_dir = self
-            .cache_root
-            .join(format!("{}-{}", platform.os, platform.arch));
+    fn ensure_cached_binary(
+        &self,
+        platform: &PlatformInfo,
+        cache_root: &Path,
+    ) -> Result<BinaryStatus, BinaryError> {
+        let platform_dir = cache_root.join(platform.cache_dir_name());
         fs::create_dir_all(&platform_dir).map_err(|e| {
-            format!(
+            BinaryError::CacheUnwritable(format!(
                 "Failed to create platform cache dir {}: {}",
                 platform_dir.display(),
                 e
-            )
+            ))
         })?;
 
-        let binary_path = platform_dir.join(&platform.binary_name);
-        let version_file_path = platform_dir.join("VERSION");
+        let binary_path = platform_dir.join(&platform.binary_name);
+        let version_file_path = platform_dir.join("VERSION");
+        let etag_file_path = platform_dir.join("ETAG");
+        let cached_status = || BinaryStatus {
+            path: binary_path.clone(),
+            version: read_installed_tag(&version_file_path),
+            downloaded: false,
+        };
+        // Another server instance may be installing into the same directory;
+        // once it is done, the checks below see its binary and VERSION.
+        with_install_lock(
+            &platform_dir,
+            self.install_lock_timeout,
+            || binary_path.exists().then(|| Ok(cached_status())),
+            || {
+                let cached_etag =
+                    if binary_path.exists() && read_installed_tag(&version_file_path).is_some() {
+                        read_installed_tag(&etag_file_path).filter(|etag| !etag.is_empty())
+                    } else {
+                        None
+                    };
+
+                match fetch_latest_if_changed(cached_etag.as_deref(), &binary_path, |etag| {
+                    self.fetch_release_if_changed(
+                        &format!("{}/latest", self.releases_api_url),
+                        etag,
+                    )
+                }) {
+                    Ok(ReleaseFetch::NotModified) => Ok(cached_status()),
+                    Ok(ReleaseFetch::Modified { release, etag }) => {
+                        let update_result = install_release(
+                            &release,
+                            platform,
+                            &binary_path,
+                            &version_file_path,
+                            |release, asset| {
+                                self.download_release_asset(
+                                    release,
+                                    asset,
+                                    cache_root,
+                                    &binary_path,
+                                )
+                            },
+                            || self.fetch_latest_release(),
+                            false,
+                        );
+
+                        match update_result {
+                            Ok(status) => {
+                                store_etag(&etag_file_path, etag.as_deref());
+                                Ok(status)
+                            }
+                            Err(update_err) => {
+                                if binary_path.exists() {
+                                    eprintln!(
+                                        "Warning: could not update formatter, using cached binary: {update_err}"
+                                    );
+                                    Ok(cached_status())
+                                } else {
+                                    Err(update_err.context(
+                                        "Failed to update formatter and no cached formatter found",
+                                    ))
+                                }
+                            }
+                        }
+                    }
+                    Err(fetch_err) => {
+                        if binary_path.exists() {
+                            eprintln!(
+                                "Warning: could not fetch latest release, using cached formatter: {fetch_err}"
+                            );
+                            Ok(cached_status())
+                        } else {
+                            Err(fetch_err.context(
+                                "Failed to fetch latest release and no cached formatter found",
+                            ))
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    fn fetch_latest_release(&self) -> Result<ReleaseInfo, BinaryError> {
+        self.fetch_release(&format!("{}/latest", self.releases_api_url))
+    }
+
+    fn fetch_release_by_tag(&self, tag: &str) -> Result<ReleaseInfo, BinaryError> {
+        self.fetch_release(&format!("{}/tags/{tag}", self.releases_api_url))
+            .map_err(|err| match err {
+                BinaryError::ReleaseNotFound(_) => BinaryError::ReleaseNotFound(format!(
+                    "Formatter release tag '{tag}' does not exist"
+                )),
+                err => err,
+            })
+    }
+
+    fn fetch_release(&self, url: &str) -> Result<ReleaseInfo, BinaryError> {
+        match self.fetch_release_if_changed(url, None)? {
+            ReleaseFetch::Modified { release, .. } => Ok(release),
+            ReleaseFetch::NotModified => Err(unexpected_not_modified(url)),
+        }
+    }
+
+    /// Fetches a release, sending `If-None-Match: <etag>` when given so an
+    /// unchanged release costs a `304` instead of a full JSON body.
+    fn fetch_release_if_changed(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+    ) -> Result<ReleaseFetch, BinaryError> {
+        let mut request = self
+            .http_get(url)?
+            .header(ACCEPT, "application/vnd.github+json");
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let response = request.send().map_err(|e| {
+            BinaryError::NetworkUnavailable(format!("HTTP request to GitHub failed: {e}"))
+        })?;
+        let response = self.reject_rate_limited(response)?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(ReleaseFetch::NotModified);
+        }
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(BinaryError::ReleaseNotFound(format!(
+                "GitHub release not found: {url}"
+            )));
+        }
+        let response = response.error_for_status().map_err(|e| {
+            BinaryError::NetworkUnavailable(format!("GitHub release request failed: {e}"))
+        })?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let release = response.json::<ReleaseInfo>().map_err(|e| {
+            BinaryError::NetworkUnavailable(format!("Failed to parse GitHub release JSON: {e}"))
+        })?;
+        Ok(ReleaseFetch::Modified { release, etag })
+    }
+
+    /// Looks up the published SHA-256 of `asset`. Releases without a checksums
+    /// asset (or without an entry for this asset) are installed unverified.
+    fn asset_checksum(
+        &self,
+        release: &ReleaseInfo,
+        asset: &ReleaseAsset,
+    ) -> Result<Option<String>, BinaryError> {
+        let Some(checksums) = select_checksums_asset(release, asset) else {
+            eprintln!(
+                "Warning: release {} publishes no checksums; installing {} without SHA-256 verification",
+                release.tag_name, asset.name
+            );
+            return Ok(None);
+        };
+        let text = self
+            .http_get(&checksums.browser_download_url)?
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|e| {
+                BinaryError::NetworkUnavailable(format!(
+                    "Failed to download checksums {}: {}",
+                    checksums.name, e
+                ))
+            })?;
+        let expected = parse_checksum(&text, &asset.name);
+        if expected.is_none() {
+            eprintln!(
+                "Warning: {} has no SHA-256 entry for {}; installing without verification",
+                checksums.name, asset.name
+            );
+        }
+        Ok(expected)
+    }
+
+    fn download_release_asset(
+        &self,
+        release: &ReleaseInfo,
+        asset: &ReleaseAsset,
+        cache_root: &Path,
+        target_binary_path: &Path,
+    ) -> Result<(), AssetError> {
+        let expected_sha256 = self.asset_checksum(release, asset)?;
+        self.download_and_extract_asset(
+            &asset.browser_download_url,
+            cache_root,
+            target_binary_path,
+            expected_sha256.as_deref(),
+        )
+    }
+
+    fn download_and_extract_asset(
+        &self,
+        url: &str,
+        cache_root: &Path,
+        target_binary_path: &Path,
+        expected_sha256: Option<&str>,
+    ) -> Result<(), AssetError> {
+        let response = self.http_get(url)?.send().map_err(|e| {
+            BinaryError::NetworkUnavailable(format!("Failed to download asset from {url}: {e}"))
+        })?;
+        let response = self.reject_rate_limited(response)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(AssetError::NotFound(format!(
+                "Asset download returned 404 Not Found: {url}"
+            )));
+        }
+        let response = response
+            .error_for_status()
+            .map_err(|e| BinaryError::NetworkUnavailable(format!("Asset download failed: {e}")))?;
+
+        let bytes = response.bytes().map_err(|e| {
+            BinaryError::NetworkUnavailable(format!("Failed to read asset bytes: {e}"))
+        })?;
+        if let Some(expected) = expected_sha256 {
+            verify_sha256(&bytes, expected, url)?;
+        }
+
+        let temp_dir = tempdir_in(cache_root).map_err(|e| {
+            BinaryError::CacheUnwritable(format!("Failed to create temp dir in cache: {e}"))
+        })?;
+        let zip_path = temp_dir.path().join("asset.zip");
+        fs::write(&zip_path, &bytes).map_err(|e| {
+            BinaryError::CacheUnwritable(format!(
+                "Failed to write downloaded zip to {}: {}",
+                zip_path.display(),
+                e
+            ))
+        })?;
+
+        let file = File::open(&zip_path).map_err(|e| {
+            BinaryError::CacheUnwritable(format!(
+                "Failed to open downloaded zip {}: {}",
+                zip_path.display(),
+                e
+            ))
+        })?;
+        let mut archive = ZipArchive::new(file)
+            .map_err(|e| BinaryError::BinaryInvalid(format!("Failed to read zip archive: {e}")))?;
+
+        Ok(extract_binary_from_zip(&mut archive, target_binary_path)?)
+    }
+}
+
+fn env_u64(key: &str, value: Option<OsString>, default: u64) -> Result<u64, String> {
+    let Some(value) = value else {
+        return Ok(default);
+    };
+    value
+        .to_str()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .ok_or_else(|| format!("{key} must be a non-negative integer"))
+}
+
+/// The wait before retry number `tried`: `backoff` times `tried`, capped
+/// instead of overflowing for large settings.
+fn retry_delay(backoff: Duration, tried: u64) -> Duration {
+    backoff.saturating_mul(u32::try_from(tried).unwrap_or(u32::MAX))
+}
+
+fn retry_install<T>(
+    retries: u64,
+    backoff: Duration,
+    mut attempt: impl FnMut() -> Result<T, BinaryError>,
+) -> Result<T, BinaryError> {
+    let mut tried = 0;
+    loop {
+        match attempt() {
+            Err(BinaryError::NetworkUnavailable(message)) if tried < retries => {
+                tried += 1;
+                let delay = retry_delay(backoff, tried);
+                eprintln!(
+                    "Warning: formatter install attempt {tried}/{} failed: {message}; retrying in {}ms",
+                    retries.saturating_add(1),
+                    delay.as_millis()
+                );
+                thread::sleep(delay);
+            }
+            other => return other,
+        }
+    }
+}
+
+/// Advisory lock: a `.lock` file created exclusively in the install dir and
+/// removed on drop. Only cooperating server instances honour it. The file
+/// holds a token unique to this holder, so a lock that was taken over as
+/// stale is never removed out from under its new owner.
+struct InstallLock {
+    path: PathBuf,
+    token: String,
+}
+
+impl Drop for InstallLock {
+    fn drop(&mut self) {
+        if fs::read_to_string(&self.path).is_ok_and(|owner| owner.trim() == self.token) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn is_stale_lock(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > STALE_INSTALL_LOCK_AGE)
+}
+
+/// Clears a stale lock by first renaming it to a name only this waiter uses,
+/// so two waiters that both saw it stale cannot each delete a lock the other
+/// has just taken. If what got renamed turns out to be fresh, it is linked
+/// back unless someone else holds the lock by now.
+fn clear_stale_lock(path: &Path, token: &str) {
+    let claimed = path.with_file_name(format!("{INSTALL_LOCK_FILE_NAME}.stale-{token}"));
+    if fs::rename(path, &claimed).is_err() {
+        return;
+    }
+    if is_stale_lock(&claimed) {
+        eprintln!("Warning: removing stale install lock {}", path.display());
+    } else {
+        let _ = fs::hard_link(&claimed, path);
+    }
+    let _ = fs::remove_file(&claimed);
+}
+
+/// Waits up to `timeout` for the install lock of `dir`. `Ok(None)` means the
+/// wait timed out.
+fn acquire_install_lock(dir: &Path, timeout: Duration) -> io::Result<Option<InstallLock>> {
+    static NEXT_LOCK_ID: AtomicU64 = AtomicU64::new(0);
+    let path = dir.join(INSTALL_LOCK_FILE_NAME);
+    let token = format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT_LOCK_ID.fetch_add(1, Ordering::Relaxed)
+    );
+    let started = Instant::now();
+    loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                let _ = writeln!(file, "{token}");
+                return Ok(Some(InstallLock { path, token }));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                if is_stale_lock(&path) {
+                    clear_stale_lock(&path, &token);
+                    continue;
+                }
+                if started.elapsed() >= timeout {
+                    return Ok(None);
+                }
+                thread::sleep(INSTALL_LOCK_POLL);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Runs `install` while holding the install lock of `dir`, so concurrent
+/// server instances never download into the same place at once. If the lock
+/// cannot be taken in time, `on_timeout` may supply a fallback (such as the
+/// already cached binary); otherwise `install` runs unlocked.
+fn with_install_lock<T>(
+    dir: &Path,
+    timeout: Duration,
+    on_timeout: impl FnOnce() -> Option<T>,
+    install: impl FnOnce() -> T,
+) -> T {
+    let _lock = match acquire_install_lock(dir, timeout) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            if let Some(fallback) = on_timeout() {
+                eprintln!(
+                    "Warning: timed out waiting for install lock in {}, using cached formatter",
+                    dir.display()
+                );
+                return fallback;
+            }
+            eprintln!(
+                "Warning: timed out waiting for install lock in {}, installing without it",
+                dir.display()
+            );
+            None
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: could not create install lock in {}: {}",
+                dir.display(),
+                e
+            );
+            None
+        }
+    };
+    install()
+}
+
+fn read_installed_tag(version_file_path: &Path) -> Option<String> {
+    fs::read_to_string(version_file_path)
+        .ok()
+        .map(|s| s.trim().to_owned())
+}
+
+/// Asks for the latest release with the cached ETag. A `304` reuses the cached
+/// install, unless the binary vanished in the meantime, in which case the
+/// lookup is repeated unconditionally so there is a release to install.
+fn fetch_latest_if_changed(
+    cached_etag: Option<&str>,
+    binary_path: &Path,
+    mut fetch: impl FnMut(Option<&str>) -> Result<ReleaseFetch, BinaryError>,
+) -> Result<ReleaseFetch, BinaryError> {
+    match fetch(cached_etag)? {
+        ReleaseFetch::NotModified if !binary_path.exists() => match fetch(None)? {
+            ReleaseFetch::NotModified => Err(unexpected_not_modified("latest release")),
+            modified => Ok(modified),
+        },
+        fetched => Ok(fetched),
+    }
+}
+
+fn unexpected_not_modified(url: &str) -> BinaryError {
+    BinaryError::NetworkUnavailable(format!(
+        "GitHub returned 304 Not Modified for an unconditional request: {url}"
+    ))
+}
+
+/// Remembers the ETag of the installed release; failing to do so only costs
+/// a full lookup next time, so it is not an error.
+fn store_etag(etag_file_path: &Path, etag: Option<&str>) {
+    let result = match etag {
+        Some(etag) => fs::write(etag_file_path, format!("{etag}\n")),
+        None => match fs::remove_file(etag_file_path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        },
+    };
+    if let Err(e) = result {
+        eprintln!(
+            "Warning: could not update {}: {}",
+            etag_file_path.display(),
+            e
+        );
+    }
+}
+
+fn install_release(
+    release: &ReleaseInfo,
+    platform: &PlatformInfo,
+    binary_path: &Path,
+    version_file_path: &Path,
+    download: impl FnMut(&ReleaseInfo, &ReleaseAsset) -> Result<(), AssetError>,
+    refresh: impl FnOnce() -> Result<ReleaseInfo, BinaryError>,
+    force: bool,
+) -> Result<BinaryStatus, BinaryError> {
+    select_asset_for_platform(release, platform)?;
+    if !force
+        && read_installed_tag(version_file_path).as_deref() == Some(release.tag_name.as_str())
+        && binary_path.exists()
+    {
+        return Ok(BinaryStatus {
+            path: binary_path.to_path_buf(),
+            version: Some(release.tag_name.clone()),
+            downloaded: false,
+        });
+    }
+
+    let refreshed_tag = download_with_refresh(release, platform, download, refresh)?;
+    let tag_name = refreshed_tag.unwrap_or_else(|| release.tag_name.clone());
+    fs::write(version_file_path, format!("{tag_name}\n")).map_err(|e| {
+        BinaryError::CacheUnwritable(format!(
+            "Failed to write version file {}: {}",
+            version_file_path.display(),
+            e
+        ))
+    })?;
+    Ok(BinaryStatus {
+        path: binary_path.to_path_buf(),
+        version: Some(tag_name),
+        downloaded: true,
+    })
+}
+
+/// Builds `<api base>/repos/<owner>/<repo>/releases` from the optional
+/// `GDSCRIPT_FORMATTER_MCP_REPO` and `GDSCRIPT_FORMATTER_MCP_API_BASE` values.
+fn releases_api_url(repo: Option<OsString>, api_base: Option<OsString>) -> Result<String, String> {
+    let repo = repo
+        .map(|value| value.to_string_lossy().trim().to_owned())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| DEFAULT_REPO.to_owned());
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part != "."
+            && part != ".."
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    if !repo
+        .split_once('/')
+        .is_some_and(|(owner, name)| valid_part(owner) && valid_part(name))
+    {
+        return Err(format!(
+            "{REPO_ENV} must be in owner/repo form (got {repo})"
+        ));
+    }
+
+    let api_base = api_base
+        .map(|value| value.to_string_lossy().trim().to_owned())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| DEFAULT_API_BASE.to_owned());
+    if !(api_base.starts_with("https://") || api_base.starts_with("http://")) {
+        return Err(format!(
+            "{API_BASE_ENV} must be an http(s) URL (got {api_base})"
+        ));
+    }
+    Ok(format!(
+        "{}/repos/{repo}/releases",
+        api_base.trim_end_matches('/')
+    ))
+}
+
+/// `GDSCRIPT_FORMATTER_MCP_GITHUB_TOKEN` wins over `GITHUB_TOKEN` so a
+/// token scoped to release downloads can differ from the CI job's token.
+fn github_token(mcp_token: Option<OsString>, github_token: Option<OsString>) -> Option<String> {
+    [mcp_token, github_token]
+        .into_iter()
+        .flatten()
+        .map(|value| value.to_string_lossy().trim().to_owned())
+        .find(|token| !token.is_empty())
+}
+
+/// The token only goes over https, and only to the configured API host or
+/// github.com (release downloads), never to a mirror's asset host.
+fn token_allowed_for(url: &str, releases_api_url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    if url.scheme() != "https" {
+        return false;
+    }
+    let api_host = reqwest::Url::parse(releases_api_url)
+        .ok()
+        .filter(|api| api.scheme() == "https")
+        .and_then(|api| api.host_str().map(str::to_owned));
+    url.host_str()
+        .is_some_and(|host| host == "github.com" || Some(host) == api_host.as_deref())
+}
+
+fn rate_limit_error(body: &str, token_set: bool) -> Option<BinaryError> {
+    if !body.to_ascii_lowercase().contains("rate limit") {
+        return None;
+    }
+    let hint = if token_set {
+        "the configured GitHub token has exhausted its limit; wait for it to reset"
+    } else {
+        "set GITHUB_TOKEN or GDSCRIPT_FORMATTER_MCP_GITHUB_TOKEN to raise the limit"
+    };
+    Some(BinaryError::RateLimited(format!(
+        "GitHub API rate limit exceeded; {hint}"
+    )))
+}
+
+fn offline_from_env(value: Option<OsString>) -> bool {
+    value.is_some_and(|value| {
+        matches!(
+            value.to_string_lossy().trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes"
+        )
+    })
+}
+
+fn pinned_tag(value: Option<OsString>) -> Option<String> {
+    value
+        .map(|value| value.to_string_lossy().trim().to_owned())
+        .filter(|tag| !tag.is_empty())
+}
+
+/// Persists the tag `gdscript_update` installed with `pin: true` so
+/// `ensure_binary` keeps using it; `None` returns to tracking the latest
+/// release.
+fn record_pin(platform_dir: &Path, tag: Option<&str>) -> Result<(), BinaryError> {
+    let pin_file_path = platform_dir.join(PINNED_TAG_FILE_NAME);
+    let result = match tag {
+        Some(tag) => fs::write(&pin_file_path, format!("{tag}\n")),
+        None => match fs::remove_file(&pin_file_path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        },
+    };
+    result.map_err(|e| {
+        BinaryError::CacheUnwritable(format!(
+            "Failed to update pin file {}: {}",
+            pin_file_path.display(),
+            e
+        ))
+    })
+}
+
+fn tag_install_dir(cache_root: &Path, platform: &PlatformInfo, tag: &str) -> PathBuf {
+    cache_root
+        .join(platform.cache_dir_name())
+        .join("versions")
+        .join(tag)
+}
+
+fn validate_release_tag(tag: &str) -> Result<(), BinaryError> {
+    if tag.is_empty() || tag.contains(['/', '\\']) || tag.contains("..") {
+        return Err(BinaryError::BinaryInvalid(format!(
+            "Invalid formatter version tag: {tag}"
+        )));
+    }
+    Ok(())
+}
+
+fn download_with_refresh(
+    release: &ReleaseInfo,
+    platform: &PlatformInfo,
+    mut download: impl FnMut(&ReleaseInfo, &ReleaseAsset) -> Result<(), AssetError>,
+    refresh: impl FnOnce() -> Result<ReleaseInfo, BinaryError>,
+) -> Result<Option<String>, BinaryError> {
+    match download(release, select_asset_for_platform(release, platform)?) {
+        Ok(()) => Ok(None),
+        Err(AssetError::NotFound(message)) => {
+            eprintln!("Warning: {message}; re-fetching release metadata and retrying once");
+            let fresh = refresh()?;
+            download(&fresh, select_asset_for_platform(&fresh, platform)?)?;
+            Ok(Some(fresh.tag_name))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Prefers a per-asset `<asset>.sha256` file, then any release-wide
+/// checksums list (e.g. `SHA256SUMS`, `checksums.txt`).
+fn select_checksums_asset<'a>(
+    release: &'a ReleaseInfo,
+    asset: &ReleaseAsset,
+) -> Option<&'a ReleaseAsset> {
+    let sidecar = format!("{}.sha256", asset.name);
+    release
+        .assets
+        .iter()
+        .find(|candidate| candidate.name.eq_ignore_ascii_case(&sidecar))
+        .or_else(|| {
+            release.assets.iter().find(|candidate| {
+                let name = candidate.name.to_ascii_lowercase();
+                !name.ends_with(".zip") && (name.contains("checksum") || name.contains("sha256"))
+            })
+        })
+}
+
+/// Reads `sha256sum`-style lines (`<hex>  <name>` or `<hex> *<name>`); a file
+/// holding a single bare digest is taken as the digest of `asset_name`.
+fn parse_checksum(text: &str, asset_name: &str) -> Option<String> {
+    let is_digest = |token: &str| token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit());
+    let entries = text
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let digest = tokens.next().filter(|token| is_digest(token))?;
+            Some((digest.to_ascii_lowercase(), tokens.next()))
+        })
+        .collect::<Vec<_>>();
+    if let [(digest, None)] = entries.as_slice() {
+        return Some(digest.clone());
+    }
+    entries.into_iter().find_map(|(digest, name)| {
+        let name = name?.trim_start_matches('*');
+        let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        (file_name == asset_name).then_some(digest)
+    })
+}
 
-        match self.fetch_latest_release() {
-            Ok(release) => {
-                let update_result = (|| -> Result<(), String> {
-                    let asset = select_asset_for_platform(&release, platform)?;
-                    let installed_tag = fs::read_to_string(&version_file_path)
-                        .ok()
-                        .map(|s| s.trim().to_owned());
-
-                    if installed_tag.as_deref() == Some(release.tag_name.as_str())
-                        && binary_path.exists()
-                    {
-                        return Ok(());
-                    }
+fn verify_sha256(bytes: &[u8], expected: &str, url: &str) -> Result<(), BinaryError> {
+    let actual = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(BinaryError::BinaryInvalid(format!(
+            "SHA-256 mismatch for {url}: expected {expected}, got {actual} (download truncated or tampered with)"
+        )));
+    }
+    Ok(())
+}
 
-                    self.download_and_extract_asset(&asset.browser_download_url, &binary_path)?;
-                    fs::write(&version_file_path, format!("{}\n", release.tag_name)).map_err(
-                        |e| {
-                            format!(
-                                "Failed to write version file {}: {}",
-                                version_file_path.display(),
-                                e
-                            )
-                        },
-                    )?;
-                    Ok(())
-                })();
+fn is_traversal_entry(name: &str) -> bool {
+    let normalized = name.replace('\\', "/");
+    normalized.starts_with('/')
+        || normalized.as_bytes().get(1) == Some(&b':')
+        || normalized.split('/').any(|component| component == "..")
+}
 
-                match update_result {
-                    Ok(()) => Ok(binary_path),
-                    Err(update_err) => {
-                        if binary_path.exists() {
-                            eprintln!(
-                                "Warning: could not update formatter, using cached binary: {update_err}"
-                            );
-                            Ok(binary_path)
-                        } else {
-                            Err(format!(
-                                "Failed to update formatter and no cached formatter found: {update_err}"
-                            ))
-                        }
-                    }
-                }
-            }
-            Err(fetch_err) => {
-                if binary_path.exists() {
-                    eprintln!(
-                        "Warning: could not fetch latest release, using cached formatter: {fetch_err}"
-                    );
-                    Ok(binary_path)
-                } else {
-                    Err(format!(
-                        "Failed to fetch latest release and no cached formatter found: {fetch_err}"
-                    ))
-                }
-            }
+fn verify_zip_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    expected_binary_name: &str,
+) -> Result<(), BinaryError> {
+    let mut traversal_entries = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(|e| {
+            BinaryError::BinaryInvalid(format!("Failed to read zip entry #{i}: {e}"))
+        })?;
+        eprintln!(
+            "Formatter asset entry: {} ({} bytes)",
+            entry.name(),
+            entry.size()
+        );
+
+        if is_traversal_entry(entry.name()) {
+            traversal_entries.push(entry.name().to_owned());
+            continue;
+        }
+        let file_name = Path::new(entry.name())
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let is_executable =
+            entry.unix_mode().is_some_and(|mode| mode & 0o111 != 0) || file_name.ends_with(".exe");
+        if !entry.is_dir() && is_executable && file_name != expected_binary_name {
+            eprintln!(
+                "Warning: formatter asset contains unexpected executable: {}",
+                entry.name()
+            );
         }
     }
 
-    fn fetch_latest_release(&self) -> Result<ReleaseInfo, String> {
-        self.client
-            .get(LATEST_RELEASE_API_URL)
-            .header(
-                USER_AGENT,
-                format!("{}/{}", SERVER_NAME, env!("CARGO_PKG_VERSION")),
-            )
-            .header(ACCEPT, "application/vnd.github+json")
-            .send()
-            .map_err(|e| format!("HTTP request to GitHub failed: {e}"))?
-            .error_for_status()
-            .map_err(|e| format!("GitHub latest release request failed: {e}"))?
-            .json::<ReleaseInfo>()
-            .map_err(|e| format!("Failed to parse GitHub release JSON: {e}"))
+    if !traversal_entries.is_empty() {
+        return Err(BinaryError::BinaryInvalid(format!(
+            "Formatter asset contains path traversal entries: {}",
+            traversal_entries.join(", ")
+        )));
     }
+    Ok(())
+}
 
-    fn download_and_extract_asset(
-        &self,
-        url: &str,
-        target_binary_path: &Path,
-    ) -> Result<(), String> {
-        let response = self
-            .client
-            .get(url)
-            .header(
-                USER_AGENT,
-                format!("{}/{}", SERVER_NAME, env!("CARGO_PKG_VERSION")),
-            )
-            .send()
-            .map_err(|e| format!("Failed to download asset from {url}: {e}"))?
-            .error_for_status()
-            .map_err(|e| format!("Asset download failed: {e}"))?;
+fn extract_binary_from_zip<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    target_binary_path: &Path,
+) -> Result<(), BinaryError> {
+    extract_binary_from_zip_at_depth(archive, target_binary_path, 0)
+}
 
-        let bytes = response
-            .bytes()
-            .map_err(|e| format!("Failed to read asset bytes: {e}"))?;
+fn single_nested_zip<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Option<ZipArchive<io::Cursor<Vec<u8>>>>, BinaryError> {
+    let nested = (0..archive.len())
+        .filter(|&i| {
+            archive
+                .by_index(i)
+                .is_ok_and(|entry| !entry.is_dir() && entry.name().ends_with(".zip"))
+        })
+        .collect::<Vec<_>>();
+    let [index] = nested[..] else {
+        return Ok(None);
+    };
 
-        let temp_dir = tempdir_in(&self.cache_root)
-            .map_err(|e| format!("Failed to create temp dir in cache: {e}"))?;
-        let zip_path = temp_dir.path().join("asset.zip");
-        fs::write(&zip_path, &bytes).map_err(|e| {
-            format!(
-                "Failed to write downloaded zip to {}: {}",
-                zip_path.display(),
-                e
-            )
+    let entry = archive.by_index(index).map_err(|e| {
+        BinaryError::BinaryInvalid(format!("Failed to read zip entry #{index}: {e}"))
+    })?;
+    let name = entry.name().to_owned();
+    let mut bytes = Vec::new();
+    entry
+        .take(MAX_NESTED_ZIP_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| {
+            BinaryError::BinaryInvalid(format!("Failed to read nested zip {name}: {e}"))
         })?;
+    if bytes.len() as u64 > MAX_NESTED_ZIP_BYTES {
+        return Err(BinaryError::BinaryInvalid(format!(
+            "Nested zip {name} exceeds {MAX_NESTED_ZIP_BYTES} bytes"
+        )));
+    }
+    eprintln!("Formatter binary not found at top level; looking inside nested zip {name}");
+    ZipArchive::new(io::Cursor::new(bytes))
+        .map(Some)
+        .map_err(|e| BinaryError::BinaryInvalid(format!("Failed to read nested zip {name}: {e}")))
+}
 
-        let file = File::open(&zip_path).map_err(|e| {
-            format!(
-                "Failed to open downloaded zip {}: {}",
-                zip_path.display(),
-                e
-            )
+fn extract_binary_from_zip_at_depth<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    target_binary_path: &Path,
+    depth: usize,
+) -> Result<(), BinaryError> {
+    let expected_binary_name = target_binary_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| {
+            BinaryError::BinaryInvalid(format!(
+                "Failed to determine expected binary filename from {}",
+                target_binary_path.display()
+            ))
         })?;
-        let mut archive =
-            ZipArchive::new(file).map_err(|e| format!("Failed to read zip archive: {e}"))?;
+    verify_zip_entries(archive, expected_binary_name)?;
 
-        let expected_binary_name = target_binary_path
+    let mut extracted = false;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| {
+            BinaryError::BinaryInvalid(format!("Failed to read zip entry #{i}: {e}"))
+        })?;
+        if entry.is_dir() {
+            continue;
+        }
+        let is_expected_binary = Path::new(entry.name())
             .file_name()
             .and_then(|n| n.to_str())
-            .ok_or_else(|| {
-                format!(
-                    "Failed to determine expected binary filename from {}",
-                    target_binary_path.display()
-                )
-            })?;
-
-        let mut extracted = false;
-        for i in 0..archive.len() {
-            let mut entry = archive
-                .by_index(i)
-                .map_err(|e| format!("Failed to read zip entry #{i}: {e}"))?;
-            if entry.is_dir() {
-                continue;
-            }
-            let is_expected_binary = Path::new(entry.name())
-                .file_name()
-                .and_then(|n| n.to_str())
-                .map(|n| n == expected_binary_name)
-                .unwrap_or(false);
-            if !is_expected_binary {
-                continue;
-            }
-
-            let temp_output = target_binary_path.with_extension("download");
-            let mut out_file = File::create(&temp_output).map_err(|e| {
-                format!(
-                    "Failed to create temporary binary {}: {}",
-                    temp_output.display(),
-                    e
-                )
-            })?;
+            .map(|n| n == expected_binary_name)
+            .unwrap_or(false);
+        if !is_expected_binary {
+            continue;
+        }
 
-            io::copy(&mut entry, &mut out_file)
-                .map_err(|e| format!("Failed to extract formatter binary: {e}"))?;
+        let temp_output = target_binary_path.with_extension("download");
+        let mut out_file = File::create(&temp_output).map_err(|e| {
+            BinaryError::CacheUnwritable(format!(
+                "Failed to create temporary binary {}: {}",
+                temp_output.display(),
+                e
+            ))
+        })?;
 
-            set_executable_permissions(&temp_output)?;
-            fs::rename(&temp_output, target_binary_path).map_err(|e| {
-                format!(
-                    "Failed to move binary into place {}: {}",
-                    target_binary_path.display(),
-                    e
-                )
-            })?;
+        io::copy(&mut entry, &mut out_file).map_err(|e| {
+            BinaryError::CacheUnwritable(format!("Failed to extract formatter binary: {e}"))
+        })?;
 
-            extracted = true;
-            break;
-        }
+        set_executable_permissions(&temp_output)?;
+        fs::rename(&temp_output, target_binary_path).map_err(|e| {
+            BinaryError::CacheUnwritable(format!(
+                "Failed to move binary into place {}: {}",
+                target_binary_path.display(),
+                e
+            ))
+        })?;
 
-        if !extracted {
-            return Err(format!(
-                "Formatter binary '{}' not found in downloaded zip asset",
-                expected_binary_name
-            ));
-        }
+        extracted = true;
+        break;
+    }
 
-        Ok(())
+    if !extracted
+        && depth == 0
+        && let Some(mut nested) = single_nested_zip(archive)?
+    {
+        return extract_binary_from_zip_at_depth(&mut nested, target_binary_path, depth + 1);
     }
+    if !extracted {
+        return Err(BinaryError::BinaryInvalid(format!(
+            "Formatter binary '{}' not found in downloaded zip asset",
+            expected_binary_name
+        )));
+    }
+
+    Ok(())
 }
 
 fn detect_platform() -> Option<PlatformInfo> {
@@ -291,10 +1614,22 @@ fn detect_platform() -> Option<PlatformInfo> {
     Some(PlatformInfo {
         os: os.to_owned(),
         arch: arch.to_owned(),
+        libc: (os == "linux").then(|| detect_linux_libc(Path::new("/lib"))),
         binary_name: binary_name.to_owned(),
     })
 }
 
+/// musl systems ship their dynamic loader as `/lib/ld-musl-<arch>.so.1`;
+/// anything else is treated as glibc.
+fn detect_linux_libc(lib_dir: &Path) -> String {
+    let has_musl_loader = fs::read_dir(lib_dir).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"))
+    });
+    if has_musl_loader { "musl" } else { "gnu" }.to_owned()
+}
+
 fn default_cache_root() -> PathBuf {
     if let Some(xdg_cache_home) = env::var_os("XDG_CACHE_HOME") {
         return PathBuf::from(xdg_cache_home).join(SERVER_NAME);
@@ -305,7 +1640,7 @@ fn default_cache_root() -> PathBuf {
     PathBuf::from(".gdscript-formatter-mcp-cache")
 }
 
-fn resolve_cache_root() -> Result<PathBuf, String> {
+fn resolve_cache_root() -> Result<CacheRootSelection, String> {
     if let Some(custom) = env::var_os("GDSCRIPT_FORMATTER_MCP_CACHE_DIR") {
         let path = PathBuf::from(custom);
         fs::create_dir_all(&path).map_err(|e| {
@@ -315,65 +1650,998 @@ fn resolve_cache_root() -> Result<PathBuf, String> {
                 e
             )
         })?;
-        return Ok(path);
+        return Ok(CacheRootSelection {
+            path,
+            source: "env",
+            rejected: Vec::new(),
+        });
     }
 
+    select_cache_root(cache_root_candidates())
+}
+
+fn cache_root_candidates() -> Vec<(&'static str, PathBuf)> {
     let mut candidates = Vec::new();
-    candidates.push(default_cache_root());
+    candidates.push(("default", default_cache_root()));
     if let Ok(cwd) = env::current_dir() {
-        candidates.push(cwd.join(".gdscript-formatter-mcp-cache"));
+        candidates.push(("cwd", cwd.join(".gdscript-formatter-mcp-cache")));
     }
-    candidates.push(env::temp_dir().join(SERVER_NAME));
+    candidates.push(("temp", env::temp_dir().join(SERVER_NAME)));
+    candidates
+}
+
+fn with_cache_root_fallback<T>(
+    current: &CacheRootSelection,
+    candidates: Vec<(&'static str, PathBuf)>,
+    mut attempt: impl FnMut(&Path) -> Result<T, BinaryError>,
+) -> (Result<T, BinaryError>, Option<CacheRootSelection>) {
+    let reason = match attempt(&current.path) {
+        Err(BinaryError::CacheUnwritable(reason)) => reason,
+        other => return (other, None),
+    };
+
+    let position = candidates
+        .iter()
+        .position(|(_, path)| *path == current.path);
+    let remaining = candidates
+        .into_iter()
+        .skip(position.map_or(0, |index| index + 1))
+        .collect::<Vec<_>>();
+    let Ok(mut fallback) = select_cache_root(remaining) else {
+        return (Err(BinaryError::CacheUnwritable(reason)), None);
+    };
+    eprintln!(
+        "Warning: cache root {} became unwritable ({reason}); switching to {} (source={})",
+        current.path.display(),
+        fallback.path.display(),
+        fallback.source
+    );
+    fallback.rejected.insert(
+        0,
+        format!("{}: {} ({reason})", current.source, current.path.display()),
+    );
+    (attempt(&fallback.path), Some(fallback))
+}
 
-    let mut errors = Vec::new();
-    for candidate in candidates {
+fn select_cache_root(
+    candidates: Vec<(&'static str, PathBuf)>,
+) -> Result<CacheRootSelection, String> {
+    let mut rejected = Vec::new();
+    for (source, candidate) in candidates {
         match fs::create_dir_all(&candidate) {
-            Ok(_) => return Ok(candidate),
-            Err(err) => errors.push(format!("{} ({})", candidate.display(), err)),
+            Ok(_) => {
+                return Ok(CacheRootSelection {
+                    path: candidate,
+                    source,
+                    rejected,
+                });
+            }
+            Err(err) => rejected.push(format!("{source}: {} ({})", candidate.display(), err)),
         }
     }
 
     Err(format!(
         "Unable to create any cache directory. Tried: {}",
-        errors.join(", ")
+        rejected.join(", ")
     ))
 }
 
 fn select_asset_for_platform<'a>(
     release: &'a ReleaseInfo,
     platform: &PlatformInfo,
-) -> Result<&'a ReleaseAsset, String> {
-    let needle = format!("-{}-{}", platform.os, platform.arch);
-    release
+) -> Result<&'a ReleaseAsset, BinaryError> {
+    let needles = [
+        format!("-{}-{}", platform.os, platform.arch),
+        format!("-{}-musl-{}", platform.os, platform.arch),
+    ];
+    let candidates = release
         .assets
         .iter()
-        .find(|asset| {
+        .filter(|asset| {
             asset.name.starts_with("gdscript-formatter-")
-                && asset.name.contains(&needle)
+                && needles.iter().any(|needle| asset.name.contains(needle))
                 && asset.name.ends_with(".zip")
         })
+        .collect::<Vec<_>>();
+    // Prefer the build matching the detected libc, falling back to whatever
+    // exists (musl builds are usually static, glibc ones may still load).
+    let wants_musl = platform.libc.as_deref() == Some("musl");
+    candidates
+        .iter()
+        .find(|asset| asset.name.contains("musl") == wants_musl)
+        .or_else(|| candidates.first())
+        .copied()
         .ok_or_else(|| {
-            format!(
+            BinaryError::UnsupportedPlatform(format!(
                 "No downloadable formatter asset found for {}-{} in release {}",
                 platform.os, platform.arch, release.tag_name
-            )
+            ))
         })
 }
 
 #[cfg(unix)]
-fn set_executable_permissions(path: &Path) -> Result<(), String> {
+fn set_executable_permissions(path: &Path) -> Result<(), BinaryError> {
     use std::os::unix::fs::PermissionsExt;
     let perms = fs::Permissions::from_mode(0o755);
     fs::set_permissions(path, perms).map_err(|e| {
-        format!(
+        BinaryError::CacheUnwritable(format!(
             "Failed to set executable permissions {}: {}",
             path.display(),
             e
-        )
+        ))
     })
 }
 
 #[cfg(not(unix))]
-fn set_executable_permissions(_path: &Path) -> Result<(), String> {
+fn set_executable_permissions(_path: &Path) -> Result<(), BinaryError> {
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwritable_cache_root_falls_back_to_next_candidate() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let primary = temp.path().join("primary");
+        let fallback = temp.path().join("fallback");
+        let current = CacheRootSelection {
+            path: primary.clone(),
+            source: "default",
+            rejected: Vec::new(),
+        };
+
+        let (result, switched) = with_cache_root_fallback(
+            &current,
+            vec![("default", primary.clone()), ("temp", fallback.clone())],
+            |root| {
+                if root == primary {
+                    Err(BinaryError::CacheUnwritable("disk full".to_owned()))
+                } else {
+                    Ok(root.to_path_buf())
+                }
+            },
+        );
+
+        assert_eq!(result.ok(), Some(fallback.clone()));
+        let switched = switched.expect("switched cache root");
+        assert_eq!(switched.path, fallback);
+        assert_eq!(switched.source, "temp");
+        assert!(switched.rejected[0].starts_with("default: "));
+        assert!(switched.rejected[0].contains("disk full"));
+
+        let (result, switched) =
+            with_cache_root_fallback(&current, vec![("default", primary.clone())], |_| {
+                Err::<PathBuf, _>(BinaryError::NetworkUnavailable("offline".to_owned()))
+            });
+        assert_eq!(
+            result.err().map(|err| err.code()),
+            Some("network_unavailable")
+        );
+        assert!(switched.is_none());
+    }
+
+    #[test]
+    fn select_cache_root_records_rejected_candidates() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let blocker = temp.path().join("blocker");
+        fs::write(&blocker, "").expect("write blocker file");
+        let fallback = temp.path().join("fallback");
+
+        let selection = select_cache_root(vec![
+            ("default", blocker.join("cache")),
+            ("temp", fallback.clone()),
+        ])
+        .expect("select cache root");
+
+        assert_eq!(selection.path, fallback);
+        assert_eq!(selection.source, "temp");
+        assert_eq!(selection.rejected.len(), 1);
+        assert!(selection.rejected[0].starts_with("default: "));
+    }
+
+    #[test]
+    fn binary_error_codes_are_stable() {
+        let cases = [
+            (
+                BinaryError::UnsupportedPlatform(String::new()),
+                "unsupported_platform",
+            ),
+            (
+                BinaryError::NetworkUnavailable(String::new()),
+                "network_unavailable",
+            ),
+            (
+                BinaryError::CacheUnwritable(String::new()),
+                "cache_unwritable",
+            ),
+            (BinaryError::BinaryInvalid(String::new()), "binary_invalid"),
+            (
+                BinaryError::EnvPathMissing(String::new()),
+                "env_path_missing",
+            ),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.code(), code);
+        }
+    }
+
+    #[test]
+    fn binary_error_context_keeps_variant() {
+        let err = BinaryError::NetworkUnavailable("timed out".to_owned()).context("Fetch failed");
+        assert_eq!(err.code(), "network_unavailable");
+        assert_eq!(err.to_string(), "Fetch failed: timed out");
+    }
+
+    fn zip_with_entries(entries: &[(&str, &[u8])]) -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, content) in entries {
+            writer.start_file(*name, options).expect("start zip entry");
+            io::Write::write_all(&mut writer, content).expect("write zip entry");
+        }
+        let cursor = writer.finish().expect("finish zip");
+        ZipArchive::new(cursor).expect("open zip")
+    }
+
+    #[test]
+    fn extract_binary_rejects_path_traversal_entries() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let target = temp.path().join("gdscript-formatter");
+        let mut archive =
+            zip_with_entries(&[("gdscript-formatter", b"binary"), ("../evil", b"payload")]);
+
+        let err = extract_binary_from_zip(&mut archive, &target).expect_err("should reject");
+        assert_eq!(err.code(), "binary_invalid");
+        assert!(err.to_string().contains("../evil"));
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn extract_binary_from_clean_zip() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let target = temp.path().join("gdscript-formatter");
+        let mut archive = zip_with_entries(&[
+            ("README.md", b"docs"),
+            ("bin/gdscript-formatter", b"binary"),
+        ]);
+
+        extract_binary_from_zip(&mut archive, &target).expect("extract");
+        assert_eq!(fs::read(&target).expect("read binary"), b"binary");
+    }
+
+    #[test]
+    fn extract_binary_from_nested_zip() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let target = temp.path().join("gdscript-formatter");
+        let inner = {
+            let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+            let options = zip::write::SimpleFileOptions::default();
+            writer
+                .start_file("gdscript-formatter", options)
+                .expect("start inner entry");
+            io::Write::write_all(&mut writer, b"binary").expect("write inner entry");
+            writer.finish().expect("finish inner zip").into_inner()
+        };
+        let mut archive = zip_with_entries(&[("README.md", b"docs"), ("artifact.zip", &inner)]);
+
+        extract_binary_from_zip(&mut archive, &target).expect("extract nested");
+        assert_eq!(fs::read(&target).expect("read binary"), b"binary");
+    }
+
+    #[test]
+    fn cold_start_install_succeeds_on_second_attempt() {
+        let mut attempts = 0;
+        let result = retry_install(2, Duration::ZERO, || {
+            attempts += 1;
+            if attempts == 1 {
+                Err(BinaryError::NetworkUnavailable("GitHub flaked".to_owned()))
+            } else {
+                Ok("installed")
+            }
+        });
+        assert_eq!(result.ok(), Some("installed"));
+        assert_eq!(attempts, 2);
+
+        let mut attempts = 0;
+        let result = retry_install(2, Duration::ZERO, || {
+            attempts += 1;
+            Err::<(), _>(BinaryError::BinaryInvalid("corrupt".to_owned()))
+        });
+        assert_eq!(result.err().map(|err| err.code()), Some("binary_invalid"));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn retry_delay_saturates_instead_of_overflowing() {
+        assert_eq!(
+            retry_delay(Duration::from_millis(100), 2),
+            Duration::from_millis(200)
+        );
+        assert_eq!(retry_delay(Duration::MAX / 2, 3), Duration::MAX);
+        assert_eq!(
+            retry_delay(Duration::from_millis(1), u64::MAX),
+            Duration::from_millis(u64::from(u32::MAX))
+        );
+    }
+
+    #[test]
+    fn install_env_values_must_be_integers() {
+        let key = INSTALL_RETRIES_ENV;
+        assert_eq!(env_u64(key, None, 3), Ok(3));
+        assert_eq!(env_u64(key, Some(OsString::from(" 5 ")), 3), Ok(5));
+        assert_eq!(env_u64(key, Some(OsString::from("0")), 3), Ok(0));
+        assert!(env_u64(key, Some(OsString::from("-1")), 3).is_err());
+        assert!(env_u64(key, Some(OsString::from("soon")), 3).is_err());
+    }
+
+    fn linux_platform() -> PlatformInfo {
+        PlatformInfo {
+            os: "linux".to_owned(),
+            arch: "x86_64".to_owned(),
+            libc: Some("gnu".to_owned()),
+            binary_name: "gdscript-formatter".to_owned(),
+        }
+    }
+
+    #[test]
+    fn musl_platforms_get_their_own_cache_dir() {
+        let gnu = linux_platform();
+        let musl = PlatformInfo {
+            libc: Some("musl".to_owned()),
+            ..linux_platform()
+        };
+        assert_eq!(gnu.cache_dir_name(), "linux-x86_64");
+        assert_eq!(musl.cache_dir_name(), "linux-x86_64-musl");
+
+        let root = Path::new("/cache");
+        assert_ne!(
+            tag_install_dir(root, &gnu, "0.18.2"),
+            tag_install_dir(root, &musl, "0.18.2")
+        );
+    }
+
+    #[test]
+    fn musl_platforms_prefer_musl_assets() {
+        let asset = |name: &str| ReleaseAsset {
+            name: name.to_owned(),
+            browser_download_url: format!("https://example.invalid/{name}"),
+        };
+        let release = ReleaseInfo {
+            tag_name: "0.18.2".to_owned(),
+            assets: vec![
+                asset("gdscript-formatter-0.18.2-linux-x86_64-musl.zip"),
+                asset("gdscript-formatter-0.18.2-linux-x86_64.zip"),
+                asset("gdscript-formatter-0.18.2-linux-aarch64.zip"),
+            ],
+        };
+        let glibc = linux_platform();
+        let musl = PlatformInfo {
+            libc: Some("musl".to_owned()),
+            ..linux_platform()
+        };
+        let selected = |release: &ReleaseInfo, platform: &PlatformInfo| {
+            select_asset_for_platform(release, platform)
+                .map(|asset| asset.name.clone())
+                .ok()
+        };
+
+        assert_eq!(
+            selected(&release, &musl).as_deref(),
+            Some("gdscript-formatter-0.18.2-linux-x86_64-musl.zip")
+        );
+        assert_eq!(
+            selected(&release, &glibc).as_deref(),
+            Some("gdscript-formatter-0.18.2-linux-x86_64.zip")
+        );
+
+        let glibc_only = release_with_asset("0.18.2", "https://example.invalid/asset.zip");
+        assert_eq!(
+            selected(&glibc_only, &musl).as_deref(),
+            Some("gdscript-formatter-0.18.2-linux-x86_64.zip")
+        );
+    }
+
+    #[test]
+    fn detect_linux_libc_looks_for_the_musl_loader() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        fs::write(temp.path().join("ld-linux-x86-64.so.2"), b"").expect("write loader");
+        assert_eq!(detect_linux_libc(temp.path()), "gnu");
+        fs::write(temp.path().join("ld-musl-x86_64.so.1"), b"").expect("write loader");
+        assert_eq!(detect_linux_libc(temp.path()), "musl");
+    }
+
+    fn release_with_asset(tag: &str, url: &str) -> ReleaseInfo {
+        ReleaseInfo {
+            tag_name: tag.to_owned(),
+            assets: vec![ReleaseAsset {
+                name: format!("gdscript-formatter-{tag}-linux-x86_64.zip"),
+                browser_download_url: url.to_owned(),
+            }],
+        }
+    }
+
+    #[test]
+    fn releases_api_url_follows_repo_and_api_base() {
+        assert_eq!(
+            releases_api_url(None, None).as_deref(),
+            Ok("https://api.github.com/repos/GDQuest/GDScript-formatter/releases")
+        );
+        assert_eq!(
+            releases_api_url(
+                Some(OsString::from("my-org/GDScript-formatter.fork")),
+                Some(OsString::from("https://ghe.example.com/api/v3/"))
+            )
+            .as_deref(),
+            Ok("https://ghe.example.com/api/v3/repos/my-org/GDScript-formatter.fork/releases")
+        );
+        for malformed in [
+            "GDScript-formatter",
+            "a/b/c",
+            "/repo",
+            "owner/..",
+            "own er/repo",
+        ] {
+            assert!(releases_api_url(Some(OsString::from(malformed)), None).is_err());
+        }
+        assert!(releases_api_url(None, Some(OsString::from("ghe.example.com"))).is_err());
+    }
+
+    #[test]
+    fn github_token_prefers_mcp_override() {
+        assert_eq!(github_token(None, None), None);
+        assert_eq!(
+            github_token(None, Some(OsString::from("ci-token"))).as_deref(),
+            Some("ci-token")
+        );
+        assert_eq!(
+            github_token(
+                Some(OsString::from(" mcp-token ")),
+                Some(OsString::from("ci-token"))
+            )
+            .as_deref(),
+            Some("mcp-token")
+        );
+        assert_eq!(
+            github_token(Some(OsString::from("")), Some(OsString::from("ci-token"))).as_deref(),
+            Some("ci-token")
+        );
+    }
+
+    #[test]
+    fn token_is_only_sent_over_https_to_github_hosts() {
+        let api = "https://api.github.com/repos/GDQuest/GDScript-formatter/releases";
+        assert!(token_allowed_for(&format!("{api}/latest"), api));
+        assert!(token_allowed_for(
+            "https://github.com/GDQuest/GDScript-formatter/releases/download/0.18.2/a.zip",
+            api
+        ));
+        assert!(!token_allowed_for("https://mirror.example.com/a.zip", api));
+        assert!(!token_allowed_for("http://api.github.com/x", api));
+
+        let enterprise = "https://ghe.example.com/api/v3/repos/o/r/releases";
+        assert!(token_allowed_for(
+            "https://ghe.example.com/api/v3/repos/o/r/releases/latest",
+            enterprise
+        ));
+        let plain = "http://ghe.example.com/api/v3/repos/o/r/releases";
+        assert!(!token_allowed_for(
+            "http://ghe.example.com/api/v3/repos/o/r/releases/latest",
+            plain
+        ));
+    }
+
+    #[test]
+    fn rate_limit_error_suggests_a_token() {
+        let body = r#"{"message":"API rate limit exceeded for 203.0.113.7.","documentation_url":"https://docs.github.com"}"#;
+        let err = rate_limit_error(body, false).expect("rate limited");
+        assert_eq!(err.code(), "rate_limited");
+        assert!(err.message().contains("GITHUB_TOKEN"));
+        assert!(
+            rate_limit_error(body, true).is_some_and(|err| !err.message().contains("set GITHUB"))
+        );
+        assert!(rate_limit_error(r#"{"message":"Resource not accessible"}"#, false).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cached_output_is_invalidated_when_the_binary_is_replaced() {
+        use crate::tools::test_support::write_fake_formatter;
+
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let manager = FormatterManager::new().expect("create manager");
+        let binary = write_fake_formatter(temp.path(), "echo 0.18.1\n");
+        let version = |manager: &FormatterManager| {
+            manager
+                .cached_output(&binary, &["--version"])
+                .expect("run --version")
+        };
+        assert_eq!(version(&manager).trim(), "0.18.1");
+        assert_eq!(version(&manager).trim(), "0.18.1");
+
+        // Same path, new content: what an in-place update leaves behind.
+        let staged = tempfile::tempdir().expect("create staging dir");
+        let updated = write_fake_formatter(staged.path(), "echo 0.18.2 # updated\n");
+        fs::rename(&updated, &binary).expect("swap binary");
+        assert_eq!(version(&manager).trim(), "0.18.2");
+    }
+
+    #[test]
+    fn update_with_tag_pins_later_ensure_binary_calls() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let mut manager = FormatterManager::new().expect("create manager");
+        manager.offline = true;
+        *manager.cache_root.lock().expect("cache root lock") = CacheRootSelection {
+            path: temp.path().to_path_buf(),
+            source: "env",
+            rejected: Vec::new(),
+        };
+        let Some(platform) = manager.platform.clone() else {
+            return;
+        };
+        if env::var_os("GDSCRIPT_FORMATTER_PATH").is_some()
+            || env::var_os(FORMATTER_VERSION_ENV).is_some()
+        {
+            return;
+        }
+        let platform_dir = temp.path().join(platform.cache_dir_name());
+        let version_dir = tag_install_dir(temp.path(), &platform, "0.18.1");
+        fs::create_dir_all(&version_dir).expect("create version dir");
+        fs::write(version_dir.join(&platform.binary_name), b"old").expect("write pinned binary");
+        fs::write(version_dir.join("VERSION"), "0.18.1\n").expect("write VERSION");
+        fs::write(platform_dir.join(&platform.binary_name), b"latest").expect("write binary");
+        fs::write(platform_dir.join("VERSION"), "0.18.2\n").expect("write VERSION");
+
+        // What `force_update(Some("0.18.1"), Some(true))` records after installing the tag.
+        record_pin(&platform_dir, Some("0.18.1")).expect("record pin");
+        let pinned = manager.ensure_binary_status().expect("use pinned binary");
+        assert_eq!(pinned.path, version_dir.join(&platform.binary_name));
+        assert_eq!(pinned.version.as_deref(), Some("0.18.1"));
+        assert_eq!(manager.installed_formatter().version, "0.18.1");
+
+        record_pin(&platform_dir, None).expect("clear pin");
+        let latest = manager.ensure_binary_status().expect("use latest binary");
+        assert_eq!(latest.path, platform_dir.join(&platform.binary_name));
+        assert_eq!(latest.version.as_deref(), Some("0.18.2"));
+    }
+
+    #[test]
+    fn offline_mode_never_builds_a_request() {
+        assert!(!offline_from_env(None));
+        assert!(!offline_from_env(Some(OsString::from("0"))));
+        assert!(offline_from_env(Some(OsString::from(" TRUE "))));
+
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let mut manager = FormatterManager::new().expect("create manager");
+        manager.offline = true;
+        *manager.cache_root.lock().expect("cache root lock") = CacheRootSelection {
+            path: temp.path().to_path_buf(),
+            source: "env",
+            rejected: Vec::new(),
+        };
+
+        let code = |err: Option<BinaryError>| err.map(|err| err.code());
+        assert_eq!(code(manager.fetch_latest_release().err()), Some("offline"));
+        assert_eq!(
+            code(manager.http_get("https://example.invalid").err()),
+            Some("offline")
+        );
+        assert_eq!(
+            code(manager.force_update(None, None).err()),
+            Some("offline")
+        );
+
+        let Some(platform) = manager.platform.clone() else {
+            return;
+        };
+        if env::var_os("GDSCRIPT_FORMATTER_PATH").is_some()
+            || env::var_os(FORMATTER_VERSION_ENV).is_some()
+        {
+            return;
+        }
+        let cold = manager.ensure_binary_status().err();
+        assert!(
+            cold.is_some_and(|err| err.code() == "offline"
+                && err.message().contains("no cached formatter found"))
+        );
+
+        let platform_dir = temp.path().join(platform.cache_dir_name());
+        fs::create_dir_all(&platform_dir).expect("create platform dir");
+        fs::write(platform_dir.join(&platform.binary_name), b"bin").expect("write binary");
+        fs::write(platform_dir.join("VERSION"), "0.18.2\n").expect("write VERSION");
+        let warm = manager
+            .ensure_binary_status()
+            .expect("use cached binary offline");
+        assert!(!warm.downloaded);
+        assert_eq!(warm.version.as_deref(), Some("0.18.2"));
+    }
+
+    #[test]
+    fn pinned_versions_get_separate_cache_slots() {
+        assert_eq!(pinned_tag(None), None);
+        assert_eq!(pinned_tag(Some(OsString::from("  "))), None);
+        assert_eq!(
+            pinned_tag(Some(OsString::from(" 0.18.2\n"))).as_deref(),
+            Some("0.18.2")
+        );
+
+        let root = Path::new("cache");
+        let platform = linux_platform();
+        let pinned = tag_install_dir(root, &platform, "0.18.2");
+        assert_eq!(
+            pinned,
+            Path::new("cache")
+                .join("linux-x86_64")
+                .join("versions")
+                .join("0.18.2")
+        );
+        assert_ne!(pinned, tag_install_dir(root, &platform, "0.17.0"));
+        assert_ne!(pinned, root.join("linux-x86_64"));
+    }
+
+    #[test]
+    fn download_retries_with_refreshed_url_after_404() {
+        let mut attempts = Vec::new();
+        let refreshed = download_with_refresh(
+            &release_with_asset("v1", "https://example.invalid/old.zip"),
+            &linux_platform(),
+            |_, asset| {
+                let url = &asset.browser_download_url;
+                attempts.push(url.clone());
+                if url.ends_with("old.zip") {
+                    Err(AssetError::NotFound(format!("404: {url}")))
+                } else {
+                    Ok(())
+                }
+            },
+            || Ok(release_with_asset("v2", "https://example.invalid/new.zip")),
+        )
+        .expect("download after refresh");
+
+        assert_eq!(refreshed.as_deref(), Some("v2"));
+        assert_eq!(
+            attempts,
+            vec![
+                "https://example.invalid/old.zip",
+                "https://example.invalid/new.zip"
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_sha256_rejects_mismatched_download() {
+        let bytes = b"gdscript-formatter";
+        let digest = "22463596dde5d0558a649955ea4f69e707dd13f0e0f20aa2b4d3795f0e7d97ab";
+        let other = "0000000000000000000000000000000000000000000000000000000000000000";
+        let sums = format!(
+            "{other}  gdscript-formatter-0.18.2-windows-x86_64.zip\n{}  *dist/gdscript-formatter-0.18.2-linux-x86_64.zip\n",
+            digest.to_ascii_uppercase()
+        );
+
+        let expected = parse_checksum(&sums, "gdscript-formatter-0.18.2-linux-x86_64.zip");
+        assert_eq!(expected.as_deref(), Some(digest));
+        assert!(verify_sha256(bytes, digest, "asset.zip").is_ok());
+        let err = verify_sha256(b"gdscript-formatte", digest, "asset.zip").expect_err("truncated");
+        assert_eq!(err.code(), "binary_invalid");
+        assert!(err.message().contains(digest));
+
+        assert_eq!(
+            parse_checksum(&format!("{digest}\n"), "any.zip").as_deref(),
+            Some(digest)
+        );
+        assert_eq!(parse_checksum(&sums, "missing.zip"), None);
+    }
+
+    #[test]
+    fn select_checksums_asset_prefers_sidecar() {
+        let mut release = release_with_asset("0.18.2", "https://example.invalid/asset.zip");
+        let asset = ReleaseAsset {
+            name: release.assets[0].name.clone(),
+            browser_download_url: release.assets[0].browser_download_url.clone(),
+        };
+        assert!(select_checksums_asset(&release, &asset).is_none());
+
+        for name in [
+            "SHA256SUMS",
+            "gdscript-formatter-0.18.2-linux-x86_64.zip.sha256",
+        ] {
+            release.assets.push(ReleaseAsset {
+                name: name.to_owned(),
+                browser_download_url: format!("https://example.invalid/{name}"),
+            });
+        }
+        assert_eq!(
+            select_checksums_asset(&release, &asset).map(|found| found.name.as_str()),
+            Some("gdscript-formatter-0.18.2-linux-x86_64.zip.sha256")
+        );
+    }
+
+    #[test]
+    fn describe_installed_formatter_reports_unknown_without_binary() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary_path = temp.path().join("gdscript-formatter");
+        let version_file_path = temp.path().join("VERSION");
+
+        let missing = describe_installed_formatter(&binary_path, Some(&version_file_path), |_| {
+            panic!("missing binary should not be probed")
+        });
+        assert!(missing.path.is_none());
+        assert_eq!(missing.version, UNKNOWN_VERSION);
+
+        fs::write(&binary_path, b"bin").expect("write binary");
+        let probed = describe_installed_formatter(&binary_path, Some(&version_file_path), |_| {
+            Some("gdscript-formatter 0.18.2".to_owned())
+        });
+        assert_eq!(probed.path.as_deref(), Some(binary_path.as_path()));
+        assert_eq!(probed.version, "gdscript-formatter 0.18.2");
+
+        fs::write(&version_file_path, "0.18.2\n").expect("write VERSION");
+        let tagged = describe_installed_formatter(&binary_path, Some(&version_file_path), |_| None);
+        assert_eq!(tagged.version, "0.18.2");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn installed_formatter_from_cache_never_runs_the_binary() {
+        use crate::tools::test_support::write_fake_formatter;
+
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let manager = FormatterManager::new().expect("create manager");
+        *manager.cache_root.lock().expect("cache root lock") = CacheRootSelection {
+            path: temp.path().to_path_buf(),
+            source: "env",
+            rejected: Vec::new(),
+        };
+        let Some(platform) = manager.platform.clone() else {
+            return;
+        };
+        if env::var_os("GDSCRIPT_FORMATTER_PATH").is_some()
+            || env::var_os(FORMATTER_VERSION_ENV).is_some()
+        {
+            return;
+        }
+        let marker = temp.path().join("ran");
+        let fake = write_fake_formatter(
+            temp.path(),
+            &format!(
+                "touch '{}'\necho 'gdscript-formatter 0.18.2'\n",
+                marker.display()
+            ),
+        );
+        let platform_dir = temp.path().join(platform.cache_dir_name());
+        fs::create_dir_all(&platform_dir).expect("create platform dir");
+        fs::rename(&fake, platform_dir.join(&platform.binary_name)).expect("install fake");
+
+        assert_eq!(manager.installed_formatter_from_cache().version, "unknown");
+        assert!(!marker.exists());
+        fs::write(platform_dir.join("VERSION"), "0.18.2\n").expect("write VERSION");
+        assert_eq!(manager.installed_formatter_from_cache().version, "0.18.2");
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn not_modified_reuses_cache_unless_binary_is_missing() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary_path = temp.path().join("gdscript-formatter");
+        let mut requests = Vec::new();
+        let mut stub = |etag: Option<&str>| {
+            requests.push(etag.map(str::to_owned));
+            Ok(match etag {
+                Some("\"abc\"") => ReleaseFetch::NotModified,
+                _ => ReleaseFetch::Modified {
+                    release: release_with_asset("0.18.2", "https://example.invalid/asset.zip"),
+                    etag: Some("\"abc\"".to_owned()),
+                },
+            })
+        };
+
+        let missing = fetch_latest_if_changed(Some("\"abc\""), &binary_path, &mut stub)
+            .expect("fetch without cached binary");
+        assert!(matches!(
+            missing,
+            ReleaseFetch::Modified { ref release, ref etag }
+                if release.tag_name == "0.18.2" && etag.as_deref() == Some("\"abc\"")
+        ));
+
+        fs::write(&binary_path, b"binary").expect("write binary");
+        let cached = fetch_latest_if_changed(Some("\"abc\""), &binary_path, &mut stub)
+            .expect("fetch with cached binary");
+        assert!(matches!(cached, ReleaseFetch::NotModified));
+        assert_eq!(
+            requests,
+            vec![Some("\"abc\"".to_owned()), None, Some("\"abc\"".to_owned())]
+        );
+
+        let etag_file_path = temp.path().join("ETAG");
+        store_etag(&etag_file_path, Some("\"abc\""));
+        assert_eq!(
+            read_installed_tag(&etag_file_path).as_deref(),
+            Some("\"abc\"")
+        );
+        store_etag(&etag_file_path, None);
+        assert!(!etag_file_path.exists());
+    }
+
+    #[test]
+    fn install_lock_serializes_concurrent_installs() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let dir = temp.path().to_path_buf();
+        let binary_path = dir.join("gdscript-formatter");
+        let downloads = AtomicU64::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    with_install_lock(
+                        &dir,
+                        Duration::from_secs(10),
+                        || None,
+                        || {
+                            if !binary_path.exists() {
+                                downloads.fetch_add(1, Ordering::SeqCst);
+                                thread::sleep(Duration::from_millis(200));
+                                fs::write(&binary_path, b"binary").expect("write binary");
+                            }
+                        },
+                    );
+                });
+            }
+        });
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+        assert!(!dir.join(INSTALL_LOCK_FILE_NAME).exists());
+
+        let held = acquire_install_lock(&dir, Duration::ZERO)
+            .expect("acquire lock")
+            .expect("lock is free");
+        let result = with_install_lock(&dir, Duration::ZERO, || Some("cached"), || "installed");
+        assert_eq!(result, "cached");
+        let result = with_install_lock(&dir, Duration::ZERO, || None, || "installed");
+        assert_eq!(result, "installed");
+        drop(held);
+        assert!(!dir.join(INSTALL_LOCK_FILE_NAME).exists());
+    }
+
+    fn plant_stale_lock(dir: &Path) {
+        let lock = fs::File::create(dir.join(INSTALL_LOCK_FILE_NAME)).expect("create stale lock");
+        lock.set_modified(SystemTime::now() - STALE_INSTALL_LOCK_AGE * 2)
+            .expect("age stale lock");
+    }
+
+    #[test]
+    fn stale_lock_takeover_admits_one_holder_at_a_time() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let dir = temp.path().to_path_buf();
+        plant_stale_lock(&dir);
+        let holders = AtomicU64::new(0);
+        let overlaps = AtomicU64::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let lock = acquire_install_lock(&dir, Duration::from_secs(10))
+                        .expect("acquire lock")
+                        .expect("lock is free in time");
+                    if holders.fetch_add(1, Ordering::SeqCst) > 0 {
+                        overlaps.fetch_add(1, Ordering::SeqCst);
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                    holders.fetch_sub(1, Ordering::SeqCst);
+                    drop(lock);
+                });
+            }
+        });
+        assert_eq!(overlaps.load(Ordering::SeqCst), 0);
+        let leftovers: Vec<_> = fs::read_dir(&dir).expect("list dir").collect();
+        assert!(leftovers.is_empty());
+
+        // A holder whose lock was taken over must not delete the new owner's.
+        let first = acquire_install_lock(&dir, Duration::ZERO)
+            .expect("acquire lock")
+            .expect("lock is free");
+        fs::write(dir.join(INSTALL_LOCK_FILE_NAME), "other-owner\n").expect("replace lock");
+        drop(first);
+        assert!(dir.join(INSTALL_LOCK_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn concurrent_ensure_binary_calls_share_a_stale_lock_takeover() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let mut manager = FormatterManager::new().expect("create manager");
+        manager.offline = false;
+        // Nothing listens here, so the latest check fails fast and the
+        // cached binary is used.
+        manager.releases_api_url = "http://127.0.0.1:9/repos/o/r/releases".to_owned();
+        *manager.cache_root.lock().expect("cache root lock") = CacheRootSelection {
+            path: temp.path().to_path_buf(),
+            source: "env",
+            rejected: Vec::new(),
+        };
+        let Some(platform) = manager.platform.clone() else {
+            return;
+        };
+        if env::var_os("GDSCRIPT_FORMATTER_PATH").is_some()
+            || env::var_os(FORMATTER_VERSION_ENV).is_some()
+        {
+            return;
+        }
+        let platform_dir = temp.path().join(platform.cache_dir_name());
+        fs::create_dir_all(&platform_dir).expect("create platform dir");
+        fs::write(platform_dir.join(&platform.binary_name), b"bin").expect("write binary");
+        fs::write(platform_dir.join("VERSION"), "0.18.2\n").expect("write VERSION");
+        plant_stale_lock(&platform_dir);
+
+        thread::scope(|scope| {
+            let calls: Vec<_> = (0..2)
+                .map(|_| scope.spawn(|| manager.ensure_binary()))
+                .collect();
+            for call in calls {
+                let path = call.join().expect("join").expect("use cached binary");
+                assert_eq!(path, platform_dir.join(&platform.binary_name));
+            }
+        });
+        let lock_files = fs::read_dir(&platform_dir)
+            .expect("list platform dir")
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(INSTALL_LOCK_FILE_NAME)
+            })
+            .count();
+        assert_eq!(lock_files, 0);
+    }
+
+    #[test]
+    fn download_does_not_refresh_on_other_failures() {
+        let err = download_with_refresh(
+            &release_with_asset("v1", "https://example.invalid/asset.zip"),
+            &linux_platform(),
+            |_, _| Err(BinaryError::BinaryInvalid("corrupt".to_owned()).into()),
+            || panic!("refresh should not run"),
+        )
+        .expect_err("should fail");
+        assert_eq!(err.code(), "binary_invalid");
+    }
+
+    #[test]
+    fn install_release_downloads_on_cold_cache_only() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary_path = temp.path().join("gdscript-formatter");
+        let version_file_path = temp.path().join("VERSION");
+        let platform = linux_platform();
+        let release = release_with_asset("0.18.2", "https://example.invalid/asset.zip");
+        let mut downloads = 0;
+        let mut install = |force| {
+            install_release(
+                &release,
+                &platform,
+                &binary_path,
+                &version_file_path,
+                |_, _| {
+                    downloads += 1;
+                    fs::write(&binary_path, b"binary")
+                        .map_err(|e| BinaryError::CacheUnwritable(e.to_string()).into())
+                },
+                || panic!("refresh should not run"),
+                force,
+            )
+            .expect("install release")
+        };
+
+        let cold = install(false);
+        assert!(cold.downloaded);
+        assert_eq!(cold.version.as_deref(), Some("0.18.2"));
+
+        let warm = install(false);
+        assert!(!warm.downloaded);
+        assert_eq!(warm.version.as_deref(), Some("0.18.2"));
+
+        let forced = install(true);
+        assert!(forced.downloaded);
+        assert_eq!(forced.version.as_deref(), Some("0.18.2"));
+        assert_eq!(downloads, 2);
+    }
+}
@@ -1,3 +1,4 @@
+mod config;
 mod formatter_manager;
 mod mcp;
 mod protocol;
@@ -6,31 +7,330 @@ mod tools;
 
 use crate::formatter_manager::FormatterManager;
 use crate::mcp::handle_request;
-use crate::protocol::{read_mcp_message, write_mcp_message};
-use std::io::{self, BufReader};
+use crate::protocol::{
+    ContentEncoding, Framing, MessageTooLarge, TRANSPORT_ENV, client_accepts_gzip, error_response,
+    parse_mcp_body, recover_request_id,
+};
+use serde_json::{Value, json};
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Write};
 
-fn main() -> io::Result<()> {
-    let manager =
-        FormatterManager::new().map_err(|e| io::Error::other(format!("Init error: {e}")))?;
-    let stdin = io::stdin();
-    let stdout = io::stdout();
-    let mut reader = BufReader::new(stdin.lock());
-    let mut writer = stdout.lock();
+/// Dispatches a single request or a JSON-RPC batch. A batch gets one array
+/// response holding the non-notification replies, in request order.
+fn handle_message(message: &Value, manager: &FormatterManager) -> Option<Value> {
+    let Value::Array(batch) = message else {
+        return handle_request(message, manager);
+    };
+    if batch.is_empty() {
+        return Some(error_response(Value::Null, -32600, "Invalid Request"));
+    }
+    let responses = batch
+        .iter()
+        .filter_map(|request| handle_request(request, manager))
+        .collect::<Vec<_>>();
+    (!responses.is_empty()).then_some(Value::Array(responses))
+}
 
+fn serve<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    manager: &FormatterManager,
+    framing: Framing,
+) -> io::Result<()> {
+    let mut encoding = ContentEncoding::Identity;
     loop {
-        let message = match read_mcp_message(&mut reader) {
-            Ok(Some(msg)) => msg,
+        let body = match framing.read_message(reader) {
+            Ok(Some(body)) => body,
             Ok(None) => break,
             Err(err) => {
                 eprintln!("Failed to read MCP message: {err}");
+                if let Some(too_large) = MessageTooLarge::from_io_error(&err) {
+                    let message = format!("Invalid Request: {too_large}");
+                    let response = error_response(too_large.id.clone(), -32600, &message);
+                    framing.write_message(writer, &response, encoding)?;
+                    continue;
+                }
                 break;
             }
         };
 
-        if let Some(response) = handle_request(&message, &manager) {
-            write_mcp_message(&mut writer, &response)?;
+        let message = match parse_mcp_body(&body) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("Failed to parse MCP message: {err}");
+                let response = error_response(recover_request_id(&body), -32700, "Parse error");
+                framing.write_message(writer, &response, encoding)?;
+                continue;
+            }
+        };
+
+        if let Some(response) = handle_message(&message, manager) {
+            framing.write_message(writer, &response, encoding)?;
+        }
+        if message.get("method").and_then(Value::as_str) == Some("exit") {
+            break;
+        }
+        if message.get("method").and_then(Value::as_str) == Some("initialize") {
+            encoding = if message.get("params").is_some_and(client_accepts_gzip) {
+                ContentEncoding::Gzip
+            } else {
+                ContentEncoding::Identity
+            };
         }
     }
 
     Ok(())
 }
+
+fn ndjson_result(line: &str, manager: &FormatterManager) -> Value {
+    let command = match serde_json::from_str::<Value>(line) {
+        Ok(command) => command,
+        Err(err) => return json!({"error": format!("Invalid JSON line: {err}")}),
+    };
+    let Some(tool) = command.get("tool").and_then(Value::as_str) else {
+        return json!({"error": "Each line must be an object with a string `tool`"});
+    };
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "tools/call",
+        "params": {
+            "name": tool,
+            "arguments": command.get("arguments").cloned().unwrap_or_else(|| json!({}))
+        }
+    });
+    match handle_request(&request, manager) {
+        Some(response) => match response.get("result") {
+            Some(result) => json!({"tool": tool, "result": result}),
+            None => json!({"tool": tool, "error": response["error"]["message"]}),
+        },
+        None => json!({"tool": tool, "error": "No response"}),
+    }
+}
+
+fn serve_ndjson<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    manager: &FormatterManager,
+) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = ndjson_result(&line, manager);
+        serde_json::to_writer(&mut *writer, &result)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// What to run on stdio: the MCP server with a given framing, or the
+/// handshake-free `--ndjson` tool-call mode.
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Serve(Framing),
+    Ndjson,
+}
+
+fn select_mode(ndjson_flag: bool, transport: Option<OsString>) -> Result<Mode, String> {
+    let framing = Framing::from_env_value(transport)?;
+    match (ndjson_flag, framing) {
+        (false, framing) => Ok(Mode::Serve(framing)),
+        (true, Framing::ContentLength) => Ok(Mode::Ndjson),
+        (true, Framing::JsonLines) => Err(format!(
+            "--ndjson (one tool call per line) cannot be combined with {TRANSPORT_ENV}=jsonl (line-delimited JSON-RPC)"
+        )),
+    }
+}
+
+fn main() -> io::Result<()> {
+    let manager =
+        FormatterManager::new().map_err(|e| io::Error::other(format!("Init error: {e}")))?;
+    let selection = manager.cache_root_selection();
+    eprintln!(
+        "Cache root: {} (source={})",
+        selection.path.display(),
+        selection.source
+    );
+    for reason in &selection.rejected {
+        eprintln!("Cache root candidate rejected: {reason}");
+    }
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut writer = stdout.lock();
+
+    let ndjson_flag = env::args().skip(1).any(|arg| arg == "--ndjson");
+    match select_mode(ndjson_flag, env::var_os(TRANSPORT_ENV)).map_err(io::Error::other)? {
+        Mode::Ndjson => serve_ndjson(&mut reader, &mut writer, &manager),
+        Mode::Serve(framing) => serve(&mut reader, &mut writer, &manager, framing),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::read_mcp_frame;
+    use std::io::Cursor;
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    fn read_responses(output: &[u8]) -> Vec<Value> {
+        let mut reader = Cursor::new(output);
+        let mut responses = Vec::new();
+        while let Some(body) = read_mcp_frame(&mut reader).expect("read response frame") {
+            responses.push(parse_mcp_body(&body).expect("parse response"));
+        }
+        responses
+    }
+
+    #[test]
+    fn serve_continues_after_malformed_json_body() {
+        let manager = FormatterManager::new().expect("create manager");
+        let input = frame(r#"{"jsonrpc":"2.0","id":7,"method":"ping""#)
+            + &frame(r#"{"jsonrpc":"2.0","id":8,"method":"ping"}"#);
+        let mut reader = Cursor::new(input.into_bytes());
+        let mut output = Vec::new();
+
+        serve(&mut reader, &mut output, &manager, Framing::ContentLength).expect("serve");
+
+        let responses = read_responses(&output);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], json!(7));
+        assert_eq!(responses[0]["error"]["code"], json!(-32700));
+        assert_eq!(responses[1]["id"], json!(8));
+        assert_eq!(responses[1]["result"], json!({}));
+    }
+
+    #[test]
+    fn serve_rejects_oversized_message_and_keeps_serving() {
+        let manager = FormatterManager::new().expect("create manager");
+        let padding = " ".repeat(33 * 1024 * 1024);
+        let input = frame(&format!(
+            r#"{{"jsonrpc":"2.0","id":5,"method":"ping"}}{padding}"#
+        )) + &frame(r#"{"jsonrpc":"2.0","id":6,"method":"ping"}"#);
+        let mut reader = Cursor::new(input.into_bytes());
+        let mut output = Vec::new();
+
+        serve(&mut reader, &mut output, &manager, Framing::ContentLength).expect("serve");
+
+        let responses = read_responses(&output);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], json!(5));
+        assert_eq!(responses[0]["error"]["code"], json!(-32600));
+        assert_eq!(responses[1]["id"], json!(6));
+        assert_eq!(responses[1]["result"], json!({}));
+    }
+
+    #[test]
+    fn serve_stops_after_shutdown_and_exit() {
+        let manager = FormatterManager::new().expect("create manager");
+        let input = frame(r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#)
+            + &frame(r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#)
+            + &frame(r#"{"jsonrpc":"2.0","method":"exit"}"#)
+            + &frame(r#"{"jsonrpc":"2.0","id":3,"method":"ping"}"#);
+        let mut reader = Cursor::new(input.into_bytes());
+        let mut output = Vec::new();
+
+        assert!(serve(&mut reader, &mut output, &manager, Framing::ContentLength).is_ok());
+
+        let responses = read_responses(&output);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], json!(1));
+        assert_eq!(responses[0]["result"], json!({}));
+        assert_eq!(responses[1]["id"], json!(2));
+        assert_eq!(responses[1]["error"]["code"], json!(-32600));
+        assert!(manager.is_shutting_down());
+    }
+
+    #[test]
+    fn serve_answers_batch_with_one_array_frame() {
+        let manager = FormatterManager::new().expect("create manager");
+        let input = frame(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"ping"},{"jsonrpc":"2.0","method":"notifications/initialized"},{"jsonrpc":"2.0","id":2,"method":"ping"}]"#,
+        ) + &frame(r#"[{"jsonrpc":"2.0","method":"notifications/initialized"}]"#)
+            + &frame("[]");
+        let mut reader = Cursor::new(input.into_bytes());
+        let mut output = Vec::new();
+
+        serve(&mut reader, &mut output, &manager, Framing::ContentLength).expect("serve");
+
+        let responses = read_responses(&output);
+        assert_eq!(responses.len(), 2);
+        let batch = responses[0].as_array().expect("batch response array");
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0]["id"], json!(1));
+        assert_eq!(batch[1]["id"], json!(2));
+        assert_eq!(batch[1]["result"], json!({}));
+        assert_eq!(responses[1]["error"]["code"], json!(-32600));
+    }
+
+    #[test]
+    fn ndjson_flag_is_rejected_with_jsonl_transport() {
+        assert_eq!(select_mode(true, None), Ok(Mode::Ndjson));
+        assert_eq!(
+            select_mode(false, Some(OsString::from("jsonl"))),
+            Ok(Mode::Serve(Framing::JsonLines))
+        );
+        assert_eq!(
+            select_mode(true, Some(OsString::from("content-length"))),
+            Ok(Mode::Ndjson)
+        );
+        assert!(select_mode(true, Some(OsString::from("jsonl"))).is_err());
+    }
+
+    #[test]
+    fn serve_speaks_line_delimited_json_rpc() {
+        let manager = FormatterManager::new().expect("create manager");
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#,
+            "\n\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"ping""#,
+            "\n"
+        );
+        let mut reader = Cursor::new(input.as_bytes());
+        let mut output = Vec::new();
+
+        serve(&mut reader, &mut output, &manager, Framing::JsonLines).expect("serve");
+
+        let lines = String::from_utf8(output).expect("utf-8 output");
+        let responses = lines
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).expect("parse response line"))
+            .collect::<Vec<_>>();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["result"], json!({}));
+        assert_eq!(responses[1]["id"], json!(2));
+        assert_eq!(responses[1]["error"]["code"], json!(-32700));
+    }
+
+    #[test]
+    fn serve_ndjson_writes_one_result_line_per_command() {
+        let manager = FormatterManager::new().expect("create manager");
+        let input = concat!(
+            r#"{"tool":"gdscript_format","arguments":{}}"#,
+            "\n\n",
+            r#"{"tool":"gdscript_nope"}"#,
+            "\n"
+        );
+        let mut reader = Cursor::new(input.as_bytes());
+        let mut output = Vec::new();
+
+        serve_ndjson(&mut reader, &mut output, &manager).expect("serve ndjson");
+
+        let lines = String::from_utf8(output).expect("utf-8 output");
+        let results = lines
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).expect("parse result line"))
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["tool"], json!("gdscript_format"));
+        assert_eq!(results[0]["result"]["isError"], json!(true));
+        assert_eq!(results[1]["error"], json!("Unknown tool name"));
+    }
+}
@@ -1,138 +1,751 @@
+use crate::config::apply_profile;
 use crate::formatter_manager::{FormatterManager, SERVER_NAME};
 use crate::protocol::{error_response, success_response};
-use crate::targets::as_object;
+use crate::targets::{DEFAULT_MAX_FILES, as_object, get_bool};
+use crate::tools::ToolError;
+use crate::tools::buffers::{
+    buffers_structured_content, call_gdscript_format_buffers, render_buffers_summary,
+};
+use crate::tools::changed::{
+    call_gdscript_format_check_changed, check_changed_structured_content,
+    render_check_changed_summary,
+};
+use crate::tools::compare::{
+    call_gdscript_compare_versions, compare_versions_structured_content,
+    render_compare_versions_summary,
+};
+use crate::tools::config_keys::{
+    call_gdscript_config_keys, config_keys_structured_content, render_config_keys_summary,
+};
 use crate::tools::format::{
     call_gdscript_format, format_structured_content, render_format_summary,
 };
+use crate::tools::help::{
+    call_gdscript_formatter_help, help_structured_content, render_help_summary,
+};
 use crate::tools::lint::{
-    DEFAULT_MAX_DIAGNOSTICS, call_gdscript_lint, project_lint_diagnostics, render_lint_summary,
+    call_gdscript_lint, group_diagnostics_by_category, project_lint_diagnostics,
+    render_lint_summary,
+};
+use crate::tools::lint_profile::{
+    DEFAULT_LINT_PROFILE, call_gdscript_write_lint_profile, lint_profile_structured_content,
+    render_lint_profile_summary,
+};
+use crate::tools::preview::{
+    apply_structured_content, call_gdscript_format_apply, call_gdscript_format_preview,
+    preview_structured_content, render_apply_summary, render_preview_summary,
+};
+use crate::tools::prewarm::{
+    call_gdscript_prewarm, prewarm_structured_content, render_prewarm_summary,
+};
+use crate::tools::update::{
+    call_gdscript_update, render_update_summary, update_structured_content,
+};
+use crate::tools::version::{
+    call_gdscript_version, render_version_summary, version_structured_content,
 };
-use serde_json::{Value, json};
+use serde_json::{Map, Value, json};
+use std::env;
 
 pub const PROTOCOL_VERSION: &str = "2024-11-05";
+const ENABLED_TOOLS_ENV: &str = "GDSCRIPT_FORMATTER_MCP_ENABLED_TOOLS";
+
+/// Wraps `properties` in the closed object schema every tool uses.
+fn input_schema(properties: Value, required: &[&str]) -> Value {
+    let mut schema = json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false
+    });
+    if !required.is_empty() {
+        schema["required"] = json!(required);
+    }
+    schema
+}
+
+fn tool(name: &str, description: &str, input_schema: Value) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": input_schema
+    })
+}
+
+/// Merges property maps into one; a later map wins on a shared key.
+fn merge_properties(parts: impl IntoIterator<Item = Value>) -> Value {
+    let mut merged = Map::new();
+    for part in parts {
+        if let Value::Object(map) = part {
+            merged.extend(map);
+        }
+    }
+    Value::Object(merged)
+}
+
+/// `dir` scanning, shared by every tool that resolves target files.
+fn dir_scan_properties() -> Value {
+    json!({
+        "dir": {
+            "type": "string",
+            "description": "Root directory to scan for files."
+        },
+        "include": {
+            "type": "array",
+            "items": {"type": "string"},
+            "description": "Glob patterns relative to dir to include (default: [\"**/*.gd\"])."
+        },
+        "exclude": {
+            "type": "array",
+            "items": {"type": "string"},
+            "description": "Glob patterns relative to dir to exclude."
+        }
+    })
+}
+
+/// Target resolution and reporting switches shared by format and lint.
+fn target_properties() -> Value {
+    json!({
+        "path_style": {
+            "type": "string",
+            "enum": ["as_walked", "absolute", "relative_to_dir"],
+            "description": "How dir-resolved paths are reported and passed to the formatter. relative_to_dir runs the formatter inside dir (default: as_walked)."
+        },
+        "low_priority": {
+            "type": "boolean",
+            "description": "Run the formatter at lowered scheduling priority (nice 10 on Unix, below-normal priority class on Windows) to keep the machine responsive during large jobs."
+        },
+        "absolute_paths": {
+            "type": "boolean",
+            "description": "Canonicalize every resolved file (from files and dir) to an absolute path so all reported paths are absolute. With path_style relative_to_dir, dir-relative paths are canonicalized against dir."
+        },
+        "glob_base": {
+            "type": "string",
+            "enum": ["relative", "absolute"],
+            "description": "Match include/exclude against the path relative to dir (default) or the full absolute path. absolute resolves every walked entry, which is slower on large trees."
+        },
+        "skip_hidden": {
+            "type": "boolean",
+            "description": "Do not descend into directories under dir whose name starts with a dot, such as .git and .godot (default: true). The dir itself is always scanned."
+        },
+        "max_files": {
+            "type": "integer",
+            "minimum": 1,
+            "description": "Maximum number of resolved files to process (default: GDSCRIPT_FORMATTER_MCP_MAX_FILES or 10000)."
+        },
+        "formatter_path": {
+            "type": "string",
+            "description": "Use this formatter executable for this call only, ahead of GDSCRIPT_FORMATTER_PATH and the cache."
+        },
+        "min_formatter_version": {
+            "type": "string",
+            "description": "Fail with error_code formatter_too_old when the binary's --version is below this semver version."
+        },
+        "suppress_text_summary": {
+            "type": "boolean",
+            "description": "Return an empty content array and rely on structuredContent only."
+        },
+        "include_timings": {
+            "type": "boolean",
+            "description": "Include per-phase durations (resolve_targets_ms, resolve_binary_ms, execute_ms, total_ms) in structuredContent.timings."
+        },
+        "scan_stats": {
+            "type": "boolean",
+            "description": "Include directory scan counters (entries_visited, files_matched, files_excluded, elapsed_ms) in structuredContent.scan_stats."
+        },
+        "profile": {
+            "type": "string",
+            "description": "Profile name in .gdscript-formatter-mcp.toml whose values are used as argument defaults."
+        }
+    })
+}
+
+/// Formatter style flags, shared by every tool that formats.
+fn style_properties() -> Value {
+    json!({
+        "use_spaces": {
+            "type": "boolean",
+            "description": "Use spaces for indentation."
+        },
+        "indent_size": {
+            "type": "integer",
+            "minimum": 1,
+            "description": "Number of spaces for indentation when use_spaces is true."
+        },
+        "reorder_code": {
+            "type": "boolean",
+            "description": "Reorder code declarations according to the style guide."
+        },
+        "safe": {
+            "type": "boolean",
+            "description": "Enable safe mode."
+        }
+    })
+}
+
+fn format_tool() -> Value {
+    let modes = json!({
+        "files": {
+            "type": "array",
+            "items": {"type": "string"},
+            "minItems": 1,
+            "description": "Paths to .gd files to format."
+        },
+        "check": {
+            "type": "boolean",
+            "description": "Check formatting only; do not modify files."
+        },
+        "check_ignore": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Glob patterns for files whose not-formatted check verdicts are reported under check_ignored instead of failing the call. Crashes, parse errors and unreadable files still fail. Unlike exclude, matching files are still processed."
+        },
+        "stdout": {
+            "type": "boolean",
+            "description": "Print formatted output to stdout instead of modifying files."
+        },
+        "backup": {
+            "type": "boolean",
+            "description": "Before in-place formatting, copy each file that will change to a sibling <file>.bak (or under backup_dir). Backup locations are returned in backups."
+        },
+        "backup_dir": {
+            "type": "string",
+            "description": "Write backups under this directory, mirroring each file's path, instead of next to the file. Requires backup."
+        },
+        "format": {
+            "type": "string",
+            "enum": ["default", "junit"],
+            "description": "junit (check mode only) renders each file as a JUnit testcase in structuredContent.junit_xml."
+        },
+        "per_file_retries": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Retry a file up to N times with a short backoff when it fails for a transient IO reason such as a file lock. Syntax errors are never retried. Default 0."
+        },
+        "skip_binary_files": {
+            "type": "boolean",
+            "description": "Skip files whose first bytes contain a null byte instead of invoking the formatter; they are reported under structuredContent.skipped (default: true)."
+        },
+        "content_matches": {
+            "type": "string",
+            "description": "Regex matched against each target file's content (first 1 MiB). Files that do not match are not formatted and are reported under structuredContent.skipped with reason content_mismatch."
+        },
+        "skip_unchanged": {
+            "type": "boolean",
+            "description": "Skip files whose mtime, size and content hash match the last successful format with the same options, reporting them in structuredContent.unchanged_cached."
+        },
+        "continue_on_error": {
+            "type": "boolean",
+            "description": "Deprecated compatibility flag. Formatting always continues per file."
+        }
+    });
+    let reporting = json!({
+        "one_line_summary": {
+            "type": "boolean",
+            "description": "Replace the text summary with one grep-friendly line: `format ok files=<n> changed=<n> failed=<n>` (or `format failed ...`), where changed counts files rewritten in place."
+        },
+        "use_binary_summary": {
+            "type": "boolean",
+            "description": "On success, use the formatter's own summary line (e.g. `Formatted 1 file, 1 changed`) as the text summary instead of `Format ok.`. The counts of each per-file run are added up into one line."
+        },
+        "explain_changes": {
+            "type": "boolean",
+            "description": "Format a temp copy of each file and label every changed hunk (indentation, spacing, line_wrap, reordering, other) under structuredContent.change_explanations. Heuristic; originals are not touched."
+        },
+        "reorder_summary": {
+            "type": "boolean",
+            "description": "With reorder_code, attach structuredContent.reorder_summary: [{file, moves}] built from the formatter's \"moved\"/\"reordered\" output lines (empty when it reports nothing)."
+        },
+        "include_exit_codes": {
+            "type": "boolean",
+            "description": "Attach structuredContent.exit_codes mapping each processed file to the formatter's exit code (null when terminated by a signal or not started)."
+        },
+        "idempotency_check": {
+            "type": "boolean",
+            "description": "Format a temp copy of each file twice and list files whose second pass differs under structuredContent.non_idempotent. Originals are not touched."
+        },
+        "include_raw_output": {
+            "type": "boolean",
+            "description": "Include per-file raw stdout/stderr in structuredContent.raw_by_file."
+        },
+        "detect_indentation": {
+            "type": "boolean",
+            "description": "Report each file's current indentation style (tabs, spaces, mixed, unknown) in structuredContent.indentation, sampled before formatting."
+        },
+        "include_indentation_applied": {
+            "type": "boolean",
+            "description": "Report the indentation the formatter actually wrote in structuredContent.indentation_applied ({style: tabs|spaces|mixed, width}), sampled from files changed in place. null when no file changed; not computed with check or stdout."
+        },
+        "include_stats": {
+            "type": "boolean",
+            "description": "Include total_lines and total_bytes of the processed files in structuredContent."
+        }
+    });
+    tool(
+        "gdscript_format",
+        "Format one or more GDScript files using the latest GDQuest formatter binary.",
+        input_schema(
+            merge_properties([
+                modes,
+                dir_scan_properties(),
+                target_properties(),
+                style_properties(),
+                reporting,
+            ]),
+            &[],
+        ),
+    )
+}
+
+fn lint_tool() -> Value {
+    let rules = json!({
+        "files": {
+            "type": "array",
+            "items": {"type": "string"},
+            "description": "Paths to .gd files to lint."
+        },
+        "disable_rules": {
+            "type": "string",
+            "description": "Comma-separated lint rule names to disable."
+        },
+        "strict_rules": {
+            "type": "boolean",
+            "description": "Fail instead of warning when disable_rules names an unknown rule."
+        },
+        "max_line_length": {
+            "type": "integer",
+            "minimum": 1,
+            "description": "Maximum allowed line length."
+        },
+        "list_rules": {
+            "type": "boolean",
+            "description": "List available lint rules."
+        },
+        "pretty": {
+            "type": "boolean",
+            "description": "Use pretty lint output."
+        },
+        "severity_overrides": {
+            "type": "object",
+            "additionalProperties": {"type": "string", "enum": ["error", "warning", "info"]},
+            "description": "Map of rule name to severity applied after parsing. error_count, warning_count and isError are recomputed from the overridden severities."
+        },
+        "rule_priority": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Ordered rule names. Diagnostics are sorted so earlier rules come first, then by file and line; unlisted rules sort last. Applied before max_diagnostics truncation."
+        },
+        "diagnostic_include": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Only keep diagnostics whose file matches one of these glob patterns. Applied before counts and truncation."
+        },
+        "diagnostic_exclude": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Drop diagnostics whose file matches one of these glob patterns. Applied before counts and truncation."
+        }
+    });
+    let reporting = json!({
+        "one_line_summary": {
+            "type": "boolean",
+            "description": "Replace the first line of the text summary with `lint errors=<n> warnings=<n> total=<n>`; warnings and GitHub annotations still follow on later lines."
+        },
+        "include_raw_output": {
+            "type": "boolean",
+            "description": "Include raw stdout/stderr in structuredContent."
+        },
+        "max_diagnostics": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Maximum number of diagnostics to return."
+        },
+        "format": {
+            "type": "string",
+            "enum": ["default", "github"],
+            "description": "github renders diagnostics as GitHub Actions workflow commands in structuredContent.github_annotations and the text content."
+        },
+        "max_diagnostics_per_file": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Maximum number of diagnostics each file contributes before max_diagnostics is applied."
+        },
+        "annotate": {
+            "type": "boolean",
+            "description": "Return each linted file's content with `# LINT: ...` comments inserted above diagnostic lines under structuredContent.annotated. Originals are untouched unless write is true."
+        },
+        "write": {
+            "type": "boolean",
+            "description": "With annotate, write the annotated content back to the original files."
+        },
+        "counts_only": {
+            "type": "boolean",
+            "description": "Return an empty diagnostics array while keeping total_diagnostics, error_count and warning_count accurate."
+        },
+        "dedup": {
+            "type": "boolean",
+            "description": "Collapse diagnostics with identical rule, message and severity into one entry with occurrences and locations ({file, line, column}). Cannot be combined with max_diagnostics_per_file."
+        },
+        "group_by_category": {
+            "type": "boolean",
+            "description": "Also return structuredContent.diagnostics_by_category: diagnostics bucketed by the rule category from `lint --list-rules` (uncategorized when the rule list has none), each with a count over all diagnostics."
+        },
+        "include_fix_hints": {
+            "type": "boolean",
+            "description": "Attach a short fix_hint to diagnostics of rules with a well-known mechanical fix (e.g. max-line-length, trailing-whitespace, unused-argument). Other rules get no fix_hint."
+        }
+    });
+    tool(
+        "gdscript_lint",
+        "Lint GDScript files using the latest GDQuest formatter binary.",
+        input_schema(
+            merge_properties([rules, dir_scan_properties(), target_properties(), reporting]),
+            &[],
+        ),
+    )
+}
 
 fn tools_definition() -> Value {
-    json!([
-        {
-            "name": "gdscript_format",
-            "description": "Format one or more GDScript files using the latest GDQuest formatter binary.",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "files": {
-                        "type": "array",
-                        "items": {"type": "string"},
-                        "minItems": 1,
-                        "description": "Paths to .gd files to format."
-                    },
-                    "dir": {
+    Value::Array(vec![
+        format_tool(),
+        lint_tool(),
+        tool(
+            "gdscript_format_check_changed",
+            "Check formatting of .gd files changed in a git working tree (CI/pre-commit gate).",
+            input_schema(
+                merge_properties([
+                    json!({
+                        "dir": {
+                            "type": "string",
+                            "description": "Directory inside the git repository to inspect (default: \".\")."
+                        },
+                        "base": {
+                            "type": "string",
+                            "description": "Git revision to diff against (default: \"HEAD\"). Untracked files are always included."
+                        }
+                    }),
+                    style_properties(),
+                ]),
+                &[],
+            ),
+        ),
+        tool(
+            "gdscript_compare_versions",
+            "Format files with two formatter release tags and return per-file diffs between their outputs (upgrade impact). Originals are not touched.",
+            input_schema(
+                merge_properties([
+                    json!({
+                        "from": {
+                            "type": "string",
+                            "description": "Release tag of the current formatter, e.g. \"0.18.1\"."
+                        },
+                        "to": {
+                            "type": "string",
+                            "description": "Release tag to compare against, e.g. \"0.18.2\"."
+                        },
+                        "files": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "minItems": 1,
+                            "description": "Paths to .gd files to compare."
+                        },
+                        "max_files": {
+                            "type": "integer",
+                            "minimum": 1,
+                            "description": "Fail when more files than this resolve (default: GDSCRIPT_FORMATTER_MCP_MAX_FILES or 10000)."
+                        }
+                    }),
+                    dir_scan_properties(),
+                    style_properties(),
+                ]),
+                &["from", "to"],
+            ),
+        ),
+        tool(
+            "gdscript_format_preview",
+            "First step of a two-step format: compute per-file diffs without writing, and return a short-lived token bound to the current file contents. Pass the token to gdscript_format_apply to write the changes.",
+            input_schema(
+                merge_properties([
+                    json!({
+                        "files": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "minItems": 1,
+                            "description": "Paths to .gd files to preview."
+                        },
+                        "max_files": {
+                            "type": "integer",
+                            "minimum": 1,
+                            "description": "Fail when more files than this resolve (default: GDSCRIPT_FORMATTER_MCP_MAX_FILES or 10000)."
+                        },
+                        "formatter_path": {
+                            "type": "string",
+                            "description": "Use this formatter executable for this call instead of the cached or GDSCRIPT_FORMATTER_PATH binary."
+                        }
+                    }),
+                    dir_scan_properties(),
+                    style_properties(),
+                ]),
+                &[],
+            ),
+        ),
+        tool(
+            "gdscript_format_apply",
+            "Second step of a two-step format: write the changes from a gdscript_format_preview token. Refuses and writes nothing if any previewed file changed since the preview.",
+            input_schema(
+                json!({
+                    "token": {
                         "type": "string",
-                        "description": "Root directory to scan for files."
-                    },
-                    "include": {
-                        "type": "array",
-                        "items": {"type": "string"},
-                        "description": "Glob patterns relative to dir to include (default: [\"**/*.gd\"])."
-                    },
-                    "exclude": {
-                        "type": "array",
-                        "items": {"type": "string"},
-                        "description": "Glob patterns relative to dir to exclude."
-                    },
-                    "check": {
-                        "type": "boolean",
-                        "description": "Check formatting only; do not modify files."
-                    },
-                    "stdout": {
-                        "type": "boolean",
-                        "description": "Print formatted output to stdout instead of modifying files."
-                    },
-                    "use_spaces": {
-                        "type": "boolean",
-                        "description": "Use spaces for indentation."
-                    },
-                    "indent_size": {
-                        "type": "integer",
-                        "minimum": 1,
-                        "description": "Number of spaces for indentation when use_spaces is true."
-                    },
-                    "reorder_code": {
-                        "type": "boolean",
-                        "description": "Reorder code declarations according to the style guide."
-                    },
-                    "safe": {
-                        "type": "boolean",
-                        "description": "Enable safe mode."
-                    },
-                    "continue_on_error": {
-                        "type": "boolean",
-                        "description": "Deprecated compatibility flag. Formatting always continues per file."
+                        "description": "Token returned by gdscript_format_preview. Each token can be applied once."
                     }
-                },
-                "additionalProperties": false
-            }
-        },
-        {
-            "name": "gdscript_lint",
-            "description": "Lint GDScript files using the latest GDQuest formatter binary.",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "files": {
-                        "type": "array",
-                        "items": {"type": "string"},
-                        "description": "Paths to .gd files to lint."
-                    },
-                    "dir": {
+                }),
+                &["token"],
+            ),
+        ),
+        tool(
+            "gdscript_format_buffers",
+            "Format in-memory GDScript buffers without touching the files on disk.",
+            input_schema(
+                merge_properties([
+                    json!({
+                        "buffers": {
+                            "type": "array",
+                            "minItems": 1,
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "path": {"type": "string"},
+                                    "content": {"type": "string"}
+                                },
+                                "required": ["path", "content"],
+                                "additionalProperties": false
+                            },
+                            "description": "Buffers to format. path identifies the buffer in the result and is never read or written."
+                        }
+                    }),
+                    style_properties(),
+                ]),
+                &["buffers"],
+            ),
+        ),
+        tool(
+            "gdscript_formatter_help",
+            "List the formatter binary's own command-line options parsed from its --help output.",
+            input_schema(json!({}), &[]),
+        ),
+        tool(
+            "gdscript_config_keys",
+            "List the configuration keys the formatter binary recognizes, derived from its --help and lint --help options.",
+            input_schema(json!({}), &[]),
+        ),
+        tool(
+            "gdscript_write_lint_profile",
+            "Save lint arguments (disabled rules, max line length) as a named [profiles.<name>] table in this server's .gdscript-formatter-mcp.toml so later gdscript_lint calls can reuse them via `profile`. This is not a config file for the formatter binary; the settings take effect only through this server.",
+            input_schema(
+                json!({
+                    "path": {
                         "type": "string",
-                        "description": "Root directory to scan for files."
-                    },
-                    "include": {
-                        "type": "array",
-                        "items": {"type": "string"},
-                        "description": "Glob patterns relative to dir to include (default: [\"**/*.gd\"])."
+                        "description": "Config file to write, e.g. <project>/.gdscript-formatter-mcp.toml. Parent directories are created and other tables in an existing file are kept."
                     },
-                    "exclude": {
-                        "type": "array",
-                        "items": {"type": "string"},
-                        "description": "Glob patterns relative to dir to exclude."
+                    "profile_name": {
+                        "type": "string",
+                        "description": "Profile table to write under [profiles.<name>] (default: lint)."
                     },
                     "disable_rules": {
                         "type": "string",
-                        "description": "Comma-separated lint rule names to disable."
+                        "description": "Comma-separated lint rule names to disable. Every name must appear in lint --list-rules."
                     },
                     "max_line_length": {
                         "type": "integer",
                         "minimum": 1,
-                        "description": "Maximum allowed line length."
-                    },
-                    "list_rules": {
-                        "type": "boolean",
-                        "description": "List available lint rules."
-                    },
-                    "pretty": {
-                        "type": "boolean",
-                        "description": "Use pretty lint output."
+                        "description": "Maximum line length for the lint profile."
+                    }
+                }),
+                &["path"],
+            ),
+        ),
+        tool(
+            "gdscript_version",
+            "Report this server's version and the formatter binary currently in use (path and version) without downloading anything. formatter_version is \"unknown\" and formatter_path is null when no formatter is cached yet.",
+            input_schema(json!({}), &[]),
+        ),
+        tool(
+            "gdscript_prewarm",
+            "Download or update the formatter binary into the cache without formatting anything, and report its path and version.",
+            input_schema(json!({}), &[]),
+        ),
+        tool(
+            "gdscript_update",
+            "Force a re-download of the formatter binary, ignoring the cached version. Installs the latest release, or a specific release into its own versions/<tag> cache slot when tag is given. Only changes which version other tools use when pin is set.",
+            input_schema(
+                json!({
+                    "tag": {
+                        "type": "string",
+                        "description": "Release tag to install (e.g. 0.18.2). Omit to re-download the latest release."
                     },
-                    "include_raw_output": {
+                    "pin": {
                         "type": "boolean",
-                        "description": "Include raw stdout/stderr in structuredContent."
-                    },
-                    "max_diagnostics": {
-                        "type": "integer",
-                        "minimum": 0,
-                        "description": "Maximum number of diagnostics to return."
+                        "description": "true: pin later calls (across restarts) to tag; requires tag. false: clear a recorded pin and track the latest release. Omit to leave the pin unchanged. GDSCRIPT_FORMATTER_VERSION still takes precedence."
                     }
-                },
-                "additionalProperties": false
-            }
-        }
+                }),
+                &[],
+            ),
+        ),
     ])
 }
 
+/// The `isError` result a tool returns when its call fails outright.
+fn tool_error_response(id: Value, err: &ToolError) -> Value {
+    tool_error_response_with(
+        id,
+        err,
+        json!([{"type": "text", "text": err.message}]),
+        json!({"error": err.message}),
+    )
+}
+
+/// Like [`tool_error_response`], with tool-specific text and
+/// `structuredContent` fields so the payload keeps the shape of that tool's
+/// successful result.
+fn tool_error_response_with(id: Value, err: &ToolError, content: Value, fields: Value) -> Value {
+    let mut structured = json!({
+        "ok": false,
+        "error_code": err.code
+    });
+    if let (Some(map), Value::Object(fields)) = (structured.as_object_mut(), fields) {
+        map.extend(fields);
+    }
+    success_response(
+        id,
+        json!({
+            "isError": true,
+            "content": content,
+            "structuredContent": structured
+        }),
+    )
+}
+
+fn tool_enabled(enabled_tools: Option<&str>, name: &str) -> bool {
+    enabled_tools.is_none_or(|list| list.split(',').map(str::trim).any(|tool| tool == name))
+}
+
+fn enabled_tools_definition(enabled_tools: Option<&str>) -> Value {
+    let tools = tools_definition()
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|tool| tool_enabled(enabled_tools, tool["name"].as_str().unwrap_or_default()))
+        .cloned()
+        .collect::<Vec<_>>();
+    Value::Array(tools)
+}
+
+/// Reads `suppress_text_summary` the way the tools read their own options,
+/// so a profile can set it and a non-boolean value is rejected.
+fn suppress_text_summary(arguments: &Map<String, Value>) -> Result<bool, ToolError> {
+    let arguments = apply_profile(arguments)?;
+    Ok(get_bool(&arguments, "suppress_text_summary")?)
+}
+
+fn text_content(suppress: bool, text: &str) -> Value {
+    if suppress {
+        json!([])
+    } else {
+        json!([{"type": "text", "text": text}])
+    }
+}
+
+fn argument_default(
+    manager: &FormatterManager,
+    tool: &str,
+    argument: &str,
+    schema: &Value,
+) -> Value {
+    match (tool, argument) {
+        (_, "include") => json!(["**/*.gd"]),
+        (_, "max_files") => json!(DEFAULT_MAX_FILES),
+        (_, "max_diagnostics") => json!(manager.max_diagnostics()),
+        (_, "per_file_retries") => json!(0),
+        (_, "skip_binary_files") => json!(true),
+        (_, "skip_hidden") => json!(true),
+        (_, "path_style") => json!("as_walked"),
+        (_, "base") => json!("HEAD"),
+        (_, "format") => json!("default"),
+        ("gdscript_format_check_changed", "dir") => json!("."),
+        ("gdscript_write_lint_profile", "profile_name") => json!(DEFAULT_LINT_PROFILE),
+        _ if schema.get("type") == Some(&json!("boolean")) => json!(false),
+        _ => Value::Null,
+    }
+}
+
+fn describe_tools(manager: &FormatterManager, enabled_tools: Option<&str>) -> Value {
+    let tools = enabled_tools_definition(enabled_tools)
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|tool| {
+            let name = tool["name"].as_str().unwrap_or_default();
+            let input_schema = &tool["inputSchema"];
+            let required = input_schema
+                .get("required")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            let arguments = input_schema["properties"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(argument, schema)| {
+                    let mut entry = json!({
+                        "name": argument,
+                        "type": schema["type"],
+                        "required": required.contains(&json!(argument)),
+                        "default": argument_default(manager, name, argument, schema)
+                    });
+                    for key in ["minimum", "minItems", "enum"] {
+                        if let Some(value) = schema.get(key)
+                            && let Some(map) = entry.as_object_mut()
+                        {
+                            map.insert(key.to_owned(), value.clone());
+                        }
+                    }
+                    entry
+                })
+                .collect::<Vec<_>>();
+            json!({"name": name, "arguments": arguments})
+        })
+        .collect::<Vec<_>>();
+    json!({ "tools": tools })
+}
+
 pub fn handle_request(request: &Value, manager: &FormatterManager) -> Option<Value> {
-    let id = request.get("id")?.clone();
-    let method = request.get("method")?.as_str()?;
+    let enabled_tools = env::var(ENABLED_TOOLS_ENV).ok();
+    dispatch_request(request, manager, enabled_tools.as_deref())
+}
+
+fn dispatch_request(
+    request: &Value,
+    manager: &FormatterManager,
+    enabled_tools: Option<&str>,
+) -> Option<Value> {
+    let method = request.get("method").and_then(Value::as_str);
+    let Some(id) = request.get("id").cloned() else {
+        // A notification (method but no id) must not get a reply; anything
+        // else without an id is malformed.
+        return match method {
+            Some(_) => None,
+            None => Some(error_response(Value::Null, -32600, "Invalid Request")),
+        };
+    };
+    if id.is_object() || id.is_array() {
+        return Some(error_response(Value::Null, -32600, "Invalid Request"));
+    }
+    let Some(method) = method else {
+        return Some(error_response(id, -32600, "Invalid Request"));
+    };
+    if manager.is_shutting_down() {
+        return Some(error_response(id, -32600, "Server is shutting down"));
+    }
     let params = request.get("params");
 
     match method {
@@ -153,18 +766,24 @@ pub fn handle_request(request: &Value, manager: &FormatterManager) -> Option<Val
                     },
                     "serverInfo": {
                         "name": SERVER_NAME,
-                        "version": env!("CARGO_PKG_VERSION")
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "formatterVersion": manager.installed_formatter_from_cache().version
                     }
                 }),
             ))
         }
         "ping" => Some(success_response(id, json!({}))),
+        "shutdown" => {
+            manager.request_shutdown();
+            Some(success_response(id, json!({})))
+        }
         "tools/list" => Some(success_response(
             id,
             json!({
-                "tools": tools_definition()
+                "tools": enabled_tools_definition(enabled_tools)
             }),
         )),
+        "tools/describe" => Some(success_response(id, describe_tools(manager, enabled_tools))),
         "tools/call" => {
             let name = params
                 .and_then(|v| v.get("name"))
@@ -175,95 +794,326 @@ pub fn handle_request(request: &Value, manager: &FormatterManager) -> Option<Val
                 Ok(args) => args,
                 Err(msg) => return Some(error_response(id, -32602, &msg)),
             };
+            if !tool_enabled(enabled_tools, name) {
+                return Some(error_response(
+                    id,
+                    -32602,
+                    &format!("Tool `{name}` is disabled by {ENABLED_TOOLS_ENV}"),
+                ));
+            }
 
             match name {
-                "gdscript_format" => match call_gdscript_format(manager, &arguments) {
-                    Ok(result) => {
-                        let summary = render_format_summary(&result);
-                        let structured = format_structured_content(&result);
-                        Some(success_response(
+                "gdscript_format" => {
+                    let suppress = match suppress_text_summary(&arguments) {
+                        Ok(suppress) => suppress,
+                        Err(err) => return Some(tool_error_response(id, &err)),
+                    };
+                    match call_gdscript_format(manager, &arguments) {
+                        Ok(result) => {
+                            let summary = render_format_summary(&result);
+                            let structured = format_structured_content(&result);
+                            Some(success_response(
+                                id,
+                                json!({
+                                    "isError": !result.success,
+                                    "content": text_content(suppress, &summary),
+                                    "structuredContent": structured
+                                }),
+                            ))
+                        }
+                        Err(err) => Some(tool_error_response_with(
+                            id,
+                            &err,
+                            text_content(suppress, "Format failed. failed_count=1."),
+                            json!({
+                                "failed_count": 1,
+                                "error_count": 0,
+                                "failures_truncated": false,
+                                "failures": [
+                                    {
+                                        "file": "<internal>",
+                                        "reason": err.message,
+                                        "category": "tool_error"
+                                    }
+                                ],
+                                "errors": []
+                            }),
+                        )),
+                    }
+                }
+                "gdscript_lint" => {
+                    let suppress = match suppress_text_summary(&arguments) {
+                        Ok(suppress) => suppress,
+                        Err(err) => return Some(tool_error_response(id, &err)),
+                    };
+                    match call_gdscript_lint(manager, &arguments) {
+                        Ok(result) => {
+                            let summary = render_lint_summary(&result);
+                            let (diagnostics, diagnostics_truncated, files_truncated) =
+                                if result.counts_only {
+                                    (Vec::new(), false, Vec::new())
+                                } else {
+                                    project_lint_diagnostics(
+                                        &result.diagnostics,
+                                        result.max_diagnostics,
+                                        result.max_diagnostics_per_file,
+                                    )
+                                };
+                            let mut structured = json!({
+                                "ok": result.success,
+                                "exit_code": result.exit_code,
+                                "total_diagnostics": result.total_diagnostics,
+                                "error_count": result.error_count,
+                                "warning_count": result.warning_count,
+                                "max_diagnostics": result.max_diagnostics,
+                                "diagnostics_truncated": diagnostics_truncated,
+                                "diagnostics": diagnostics,
+                                "call_summary": result.call_summary
+                            });
+                            if let Some(limit) = result.max_diagnostics_per_file
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("max_diagnostics_per_file".to_owned(), json!(limit));
+                                map.insert("files_truncated".to_owned(), json!(files_truncated));
+                            }
+                            if let Some(categories) = &result.rule_categories
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert(
+                                    "diagnostics_by_category".to_owned(),
+                                    group_diagnostics_by_category(
+                                        &result.diagnostics,
+                                        &diagnostics,
+                                        categories,
+                                    ),
+                                );
+                            }
+                            if !result.warnings.is_empty()
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("warnings".to_owned(), json!(result.warnings));
+                            }
+                            if let Some(parse_warning) = &result.parse_warning
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("parse_warning".to_owned(), json!(parse_warning));
+                            }
+                            if let Some(annotations) = &result.github_annotations
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("github_annotations".to_owned(), json!(annotations));
+                            }
+                            if let Some(timings) = result.timings
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("timings".to_owned(), timings);
+                            }
+                            if let Some(scan_stats) = result.scan_stats
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("scan_stats".to_owned(), scan_stats);
+                            }
+                            if let Some(annotated) = &result.annotated
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                let annotated = annotated
+                                    .iter()
+                                    .map(
+                                        |(file, content)| json!({"file": file, "content": content}),
+                                    )
+                                    .collect::<Vec<_>>();
+                                map.insert("annotated".to_owned(), Value::Array(annotated));
+                            }
+                            if result.decoded_lossy
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("decoded_lossy".to_owned(), Value::Bool(true));
+                            }
+                            if result.include_raw_output
+                                && let Some(map) = structured.as_object_mut()
+                            {
+                                map.insert("raw_stdout".to_owned(), Value::String(result.stdout));
+                                map.insert("raw_stderr".to_owned(), Value::String(result.stderr));
+                            }
+                            Some(success_response(
+                                id,
+                                json!({
+                                    "isError": !result.success,
+                                    "content": text_content(suppress, &summary),
+                                    "structuredContent": structured
+                                }),
+                            ))
+                        }
+                        Err(err) => Some(tool_error_response_with(
+                            id,
+                            &err,
+                            text_content(suppress, &err.message),
+                            json!({
+                                "exit_code": -1,
+                                "total_diagnostics": 0,
+                                "error_count": 0,
+                                "warning_count": 0,
+                                "max_diagnostics": manager.max_diagnostics(),
+                                "diagnostics_truncated": false,
+                                "diagnostics": []
+                            }),
+                        )),
+                    }
+                }
+                "gdscript_format_check_changed" => {
+                    match call_gdscript_format_check_changed(manager, &arguments) {
+                        Ok(result) => Some(success_response(
                             id,
                             json!({
                                 "isError": !result.success,
                                 "content": [
-                                    {"type": "text", "text": summary}
+                                    {"type": "text", "text": render_check_changed_summary(&result)}
+                                ],
+                                "structuredContent": check_changed_structured_content(&result)
+                            }),
+                        )),
+                        Err(err) => Some(tool_error_response(id, &err)),
+                    }
+                }
+                "gdscript_format_buffers" => {
+                    match call_gdscript_format_buffers(manager, &arguments) {
+                        Ok(result) => Some(success_response(
+                            id,
+                            json!({
+                                "isError": !result.success,
+                                "content": [
+                                    {"type": "text", "text": render_buffers_summary(&result)}
+                                ],
+                                "structuredContent": buffers_structured_content(&result)
+                            }),
+                        )),
+                        Err(err) => Some(tool_error_response(id, &err)),
+                    }
+                }
+                "gdscript_formatter_help" => {
+                    match call_gdscript_formatter_help(manager, &arguments) {
+                        Ok(result) => Some(success_response(
+                            id,
+                            json!({
+                                "isError": false,
+                                "content": [
+                                    {"type": "text", "text": render_help_summary(&result)}
+                                ],
+                                "structuredContent": help_structured_content(&result)
+                            }),
+                        )),
+                        Err(err) => Some(tool_error_response(id, &err)),
+                    }
+                }
+                "gdscript_compare_versions" => {
+                    match call_gdscript_compare_versions(manager, &arguments) {
+                        Ok(result) => Some(success_response(
+                            id,
+                            json!({
+                                "isError": false,
+                                "content": [
+                                    {"type": "text", "text": render_compare_versions_summary(&result)}
+                                ],
+                                "structuredContent": compare_versions_structured_content(&result)
+                            }),
+                        )),
+                        Err(err) => Some(tool_error_response(id, &err)),
+                    }
+                }
+                "gdscript_format_preview" => {
+                    match call_gdscript_format_preview(manager, &arguments) {
+                        Ok(result) => Some(success_response(
+                            id,
+                            json!({
+                                "isError": false,
+                                "content": [
+                                    {"type": "text", "text": render_preview_summary(&result)}
                                 ],
-                                "structuredContent": structured
+                                "structuredContent": preview_structured_content(&result)
                             }),
-                        ))
+                        )),
+                        Err(err) => Some(tool_error_response(id, &err)),
                     }
-                    Err(text) => Some(success_response(
+                }
+                "gdscript_format_apply" => match call_gdscript_format_apply(manager, &arguments) {
+                    Ok(result) => Some(success_response(
                         id,
                         json!({
-                            "isError": true,
+                            "isError": false,
                             "content": [
-                                {"type": "text", "text": "Format failed. failed_count=1."}
+                                {"type": "text", "text": render_apply_summary(&result)}
                             ],
-                            "structuredContent": {
-                                "ok": false,
-                                "failed_count": 1,
-                                "failures_truncated": false,
-                                "failures": [
-                                    {
-                                        "file": "<internal>",
-                                        "reason": text
-                                    }
-                                ]
-                            }
+                            "structuredContent": apply_structured_content(&result)
                         }),
                     )),
+                    Err(err) => Some(tool_error_response(id, &err)),
                 },
-                "gdscript_lint" => match call_gdscript_lint(manager, &arguments) {
-                    Ok(result) => {
-                        let summary = render_lint_summary(&result);
-                        let (diagnostics, diagnostics_truncated) =
-                            project_lint_diagnostics(&result.diagnostics, result.max_diagnostics);
-                        let mut structured = json!({
-                            "ok": result.success,
-                            "exit_code": result.exit_code,
-                            "total_diagnostics": result.diagnostics.len(),
-                            "error_count": result.error_count,
-                            "warning_count": result.warning_count,
-                            "max_diagnostics": result.max_diagnostics,
-                            "diagnostics_truncated": diagnostics_truncated,
-                            "diagnostics": diagnostics
-                        });
-                        if result.include_raw_output
-                            && let Some(map) = structured.as_object_mut()
-                        {
-                            map.insert("raw_stdout".to_owned(), Value::String(result.stdout));
-                            map.insert("raw_stderr".to_owned(), Value::String(result.stderr));
-                        }
-                        Some(success_response(
+                "gdscript_config_keys" => match call_gdscript_config_keys(manager, &arguments) {
+                    Ok(result) => Some(success_response(
+                        id,
+                        json!({
+                            "isError": false,
+                            "content": [
+                                {"type": "text", "text": render_config_keys_summary(&result)}
+                            ],
+                            "structuredContent": config_keys_structured_content(&result)
+                        }),
+                    )),
+                    Err(err) => Some(tool_error_response(id, &err)),
+                },
+                "gdscript_write_lint_profile" => {
+                    match call_gdscript_write_lint_profile(manager, &arguments) {
+                        Ok(result) => Some(success_response(
                             id,
                             json!({
-                                "isError": !result.success,
+                                "isError": false,
                                 "content": [
-                                    {"type": "text", "text": summary}
+                                    {"type": "text", "text": render_lint_profile_summary(&result)}
                                 ],
-                                "structuredContent": structured
+                                "structuredContent": lint_profile_structured_content(&result)
                             }),
-                        ))
+                        )),
+                        Err(err) => Some(tool_error_response(id, &err)),
                     }
-                    Err(text) => Some(success_response(
+                }
+                "gdscript_version" => match call_gdscript_version(manager, &arguments) {
+                    Ok(installed) => Some(success_response(
                         id,
                         json!({
-                            "isError": true,
+                            "isError": false,
                             "content": [
-                                {"type": "text", "text": text}
+                                {"type": "text", "text": render_version_summary(&installed)}
                             ],
-                            "structuredContent": {
-                                "ok": false,
-                                "exit_code": -1,
-                                "total_diagnostics": 0,
-                                "error_count": 0,
-                                "warning_count": 0,
-                                "max_diagnostics": DEFAULT_MAX_DIAGNOSTICS,
-                                "diagnostics_truncated": false,
-                                "diagnostics": []
-                            }
+                            "structuredContent": version_structured_content(&installed)
+                        }),
+                    )),
+                    Err(err) => Some(tool_error_response(id, &err)),
+                },
+                "gdscript_prewarm" => match call_gdscript_prewarm(manager, &arguments) {
+                    Ok(status) => Some(success_response(
+                        id,
+                        json!({
+                            "isError": false,
+                            "content": [
+                                {"type": "text", "text": render_prewarm_summary(&status)}
+                            ],
+                            "structuredContent": prewarm_structured_content(&status, &manager.cache_root_selection())
+                        }),
+                    )),
+                    Err(err) => Some(tool_error_response(id, &err)),
+                },
+                "gdscript_update" => match call_gdscript_update(manager, &arguments) {
+                    Ok(outcome) => Some(success_response(
+                        id,
+                        json!({
+                            "isError": false,
+                            "content": [
+                                {"type": "text", "text": render_update_summary(&outcome)}
+                            ],
+                            "structuredContent": update_structured_content(&outcome)
                         }),
                     )),
+                    Err(err) => Some(tool_error_response(id, &err)),
                 },
                 _ => Some(error_response(id, -32602, "Unknown tool name")),
             }
@@ -271,3 +1121,211 @@ pub fn handle_request(request: &Value, manager: &FormatterManager) -> Option<Val
         _ => Some(error_response(id, -32601, "Method not found")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tools_describe_reports_constraints() {
+        let manager = FormatterManager::new().expect("create manager");
+        let described = describe_tools(&manager, None);
+        let format_tool = described["tools"]
+            .as_array()
+            .and_then(|tools| tools.iter().find(|t| t["name"] == "gdscript_format"))
+            .expect("gdscript_format described");
+        let indent_size = format_tool["arguments"]
+            .as_array()
+            .and_then(|args| args.iter().find(|a| a["name"] == "indent_size"))
+            .expect("indent_size described");
+
+        assert_eq!(indent_size["type"], "integer");
+        assert_eq!(indent_size["minimum"], 1);
+        assert_eq!(indent_size["required"], false);
+
+        let check = format_tool["arguments"]
+            .as_array()
+            .and_then(|args| args.iter().find(|a| a["name"] == "check"))
+            .expect("check described");
+        assert_eq!(check["default"], false);
+    }
+
+    #[test]
+    fn disabled_tools_are_hidden_and_rejected() {
+        let manager = FormatterManager::new().expect("create manager");
+        let enabled = Some("gdscript_lint, gdscript_formatter_help");
+
+        let list = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"});
+        let response = dispatch_request(&list, &manager, enabled).expect("response");
+        let names = response["result"]["tools"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|tool| tool["name"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![json!("gdscript_lint"), json!("gdscript_formatter_help")]
+        );
+
+        let call = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {"name": "gdscript_format", "arguments": {"files": ["a.gd"]}}
+        });
+        let response = dispatch_request(&call, &manager, enabled).expect("response");
+        assert_eq!(response["error"]["code"], -32602);
+        assert!(
+            response["error"]["message"]
+                .as_str()
+                .is_some_and(|message| message.contains("gdscript_format"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lint_dedup_keeps_counting_every_diagnostic() {
+        use crate::tools::test_support::write_fake_formatter;
+
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = write_fake_formatter(
+            temp.path(),
+            "printf 'a.gd:1:class-name:error: bad name\\na.gd:2:max-line-length:warning: too long\\nb.gd:3:max-line-length:warning: too long\\n'\nexit 1\n",
+        );
+        let manager = FormatterManager::new().expect("create manager");
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "gdscript_lint",
+                "arguments": {
+                    "files": ["a.gd", "b.gd"],
+                    "formatter_path": binary.to_string_lossy(),
+                    "dedup": true,
+                    "group_by_category": true,
+                    "one_line_summary": true
+                }
+            }
+        });
+
+        let response = dispatch_request(&request, &manager, None).expect("response");
+        let structured = &response["result"]["structuredContent"];
+        assert_eq!(structured["diagnostics"].as_array().map(Vec::len), Some(2));
+        assert_eq!(structured["total_diagnostics"], 3);
+        assert_eq!(
+            structured["diagnostics_by_category"]["uncategorized"]["count"],
+            3
+        );
+        let text = response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap_or_default();
+        assert!(text.starts_with("lint errors=1 warnings=2 total=3"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lint_counts_only_omits_diagnostics() {
+        use crate::tools::test_support::write_fake_formatter;
+
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = write_fake_formatter(
+            temp.path(),
+            "printf 'a.gd:1:class-name:error: bad name\\na.gd:2:max-line-length:warning: too long\\nb.gd:3:max-line-length:warning: too long\\n'\nexit 1\n",
+        );
+        let manager = FormatterManager::new().expect("create manager");
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "gdscript_lint",
+                "arguments": {
+                    "files": ["a.gd", "b.gd"],
+                    "formatter_path": binary.to_string_lossy(),
+                    "counts_only": true
+                }
+            }
+        });
+
+        let response = dispatch_request(&request, &manager, None).expect("response");
+        let structured = &response["result"]["structuredContent"];
+        assert_eq!(structured["diagnostics"], json!([]));
+        assert_eq!(structured["total_diagnostics"], 3);
+        assert_eq!(structured["error_count"], 1);
+        assert_eq!(structured["warning_count"], 2);
+    }
+
+    #[test]
+    fn handle_request_validates_id_type() {
+        let manager = FormatterManager::new().expect("create manager");
+
+        let numeric = json!({"jsonrpc": "2.0", "id": 7, "method": "ping"});
+        let response = handle_request(&numeric, &manager).expect("response");
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"], json!({}));
+
+        let object = json!({"jsonrpc": "2.0", "id": {"n": 1}, "method": "ping"});
+        let response = handle_request(&object, &manager).expect("response");
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32600);
+    }
+
+    #[test]
+    fn handle_request_separates_notifications_from_malformed_requests() {
+        let manager = FormatterManager::new().expect("create manager");
+
+        let notification = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(handle_request(&notification, &manager).is_none());
+        // With an id it is a request, which always gets an answer.
+        let with_id = json!({"jsonrpc": "2.0", "id": 9, "method": "notifications/initialized"});
+        let response = handle_request(&with_id, &manager).expect("response");
+        assert_eq!(response["id"], 9);
+        assert_eq!(response["error"]["code"], -32601);
+
+        let bodyless = json!({"jsonrpc": "2.0"});
+        let response = handle_request(&bodyless, &manager).expect("response");
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32600);
+
+        let missing_method = json!({"jsonrpc": "2.0", "id": 3});
+        let response = handle_request(&missing_method, &manager).expect("response");
+        assert_eq!(response["id"], 3);
+        assert_eq!(response["error"]["code"], -32600);
+    }
+
+    #[test]
+    fn suppress_text_summary_empties_content() {
+        let manager = FormatterManager::new().expect("create manager");
+        let call = |arguments: Value| {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {"name": "gdscript_lint", "arguments": arguments}
+            });
+            handle_request(&request, &manager).expect("response")
+        };
+
+        let suppressed = call(json!({"suppress_text_summary": true}));
+        assert_eq!(suppressed["result"]["content"], json!([]));
+        assert_eq!(
+            suppressed["result"]["structuredContent"]["ok"],
+            json!(false)
+        );
+
+        let default = call(json!({}));
+        assert_eq!(
+            default["result"]["content"].as_array().map(Vec::len),
+            Some(1)
+        );
+
+        let invalid = call(json!({"suppress_text_summary": "yes"}));
+        assert_eq!(invalid["result"]["isError"], json!(true));
+        assert_eq!(
+            invalid["result"]["structuredContent"]["error"],
+            json!("`suppress_text_summary` must be a boolean")
+        );
+    }
+}
@@ -1,8 +1,187 @@
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use serde_json::{Value, json};
-use std::io::{self, BufRead, Write};
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::thread;
 
-pub fn read_mcp_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 32 * 1024 * 1024;
+const MAX_MESSAGE_BYTES_ENV: &str = "GDSCRIPT_FORMATTER_MCP_MAX_MESSAGE_BYTES";
+/// How much of an oversized body is kept to recover its request id.
+const TOO_LARGE_ID_PREFIX_BYTES: usize = 4096;
+pub const TRANSPORT_ENV: &str = "GDSCRIPT_FORMATTER_MCP_TRANSPORT";
+
+/// How JSON-RPC messages are delimited on stdio.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Framing {
+    /// LSP-style `Content-Length` headers (the MCP default).
+    #[default]
+    ContentLength,
+    /// One JSON-RPC message per line (`jsonl`). Unlike the `--ndjson` mode,
+    /// which takes `{"tool", "arguments"}` lines and skips the MCP handshake,
+    /// this only changes how the usual JSON-RPC messages are delimited.
+    JsonLines,
+}
+
+impl Framing {
+    pub fn from_env_value(value: Option<OsString>) -> Result<Self, String> {
+        let Some(value) = value else {
+            return Ok(Self::ContentLength);
+        };
+        match value
+            .to_str()
+            .map(|v| v.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("" | "content-length") => Ok(Self::ContentLength),
+            Some("jsonl") => Ok(Self::JsonLines),
+            Some("ndjson") => Err(format!(
+                "{TRANSPORT_ENV}=ndjson is not a transport; use \"jsonl\" for line-delimited JSON-RPC, or the --ndjson flag for one tool call per line"
+            )),
+            _ => Err(format!(
+                "{TRANSPORT_ENV} must be \"content-length\" or \"jsonl\" (got {})",
+                value.to_string_lossy()
+            )),
+        }
+    }
+
+    pub fn read_message<R: BufRead>(self, reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            Self::ContentLength => read_mcp_frame(reader),
+            Self::JsonLines => read_json_line(reader),
+        }
+    }
+
+    /// Writes one message. `encoding` only applies to `Content-Length`
+    /// framing; JSON lines are always plain JSON.
+    pub fn write_message<W: Write>(
+        self,
+        writer: &mut W,
+        value: &Value,
+        encoding: ContentEncoding,
+    ) -> io::Result<()> {
+        match self {
+            Self::ContentLength => write_mcp_message(writer, value, encoding),
+            Self::JsonLines => write_json_line(writer, value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ContentEncoding {
+    #[default]
+    Identity,
+    Gzip,
+}
+
+fn parse_content_encoding(value: &str) -> io::Result<ContentEncoding> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "identity" => Ok(ContentEncoding::Identity),
+        "gzip" => Ok(ContentEncoding::Gzip),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported Content-Encoding header: {other}"),
+        )),
+    }
+}
+
+fn max_message_bytes_from_env(value: Option<OsString>) -> usize {
+    value
+        .and_then(|v| v.to_str().and_then(|v| v.trim().parse::<usize>().ok()))
+        .filter(|v| *v >= 1)
+        .unwrap_or(DEFAULT_MAX_MESSAGE_BYTES)
+}
+
+/// Error payload for a message over the size limit. The reader has already
+/// skipped its body, so the stream is positioned at the next message and the
+/// server can answer with `id` and keep going.
+#[derive(Debug)]
+pub struct MessageTooLarge {
+    pub id: Value,
+    pub len: usize,
+    pub max_bytes: usize,
+}
+
+impl fmt::Display for MessageTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MCP message of {} bytes exceeds the {} byte limit",
+            self.len, self.max_bytes
+        )
+    }
+}
+
+impl std::error::Error for MessageTooLarge {}
+
+impl MessageTooLarge {
+    /// The payload of `err`, if it reports a skipped oversized message.
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref::<Self>()
+    }
+}
+
+fn message_too_large(len: usize, max_bytes: usize, body_prefix: &[u8]) -> io::Error {
+    let prefix = &body_prefix[..body_prefix.len().min(TOO_LARGE_ID_PREFIX_BYTES)];
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        MessageTooLarge {
+            id: recover_request_id(prefix),
+            len,
+            max_bytes,
+        },
+    )
+}
+
+/// Reads the first bytes of a `len`-byte body and discards the rest without
+/// buffering it.
+fn skip_body<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut prefix = Vec::new();
+    reader
+        .take(len.min(TOO_LARGE_ID_PREFIX_BYTES) as u64)
+        .read_to_end(&mut prefix)?;
+    let rest = (len - prefix.len()) as u64;
+    if io::copy(&mut reader.take(rest), &mut io::sink())? < rest {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "EOF while skipping oversized MCP message",
+        ));
+    }
+    Ok(prefix)
+}
+
+fn skip_rest_of_line<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        if let Some(end) = buf.iter().position(|b| *b == b'\n') {
+            reader.consume(end + 1);
+            return Ok(());
+        }
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+pub fn read_mcp_frame<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let max_bytes = max_message_bytes_from_env(env::var_os(MAX_MESSAGE_BYTES_ENV));
+    read_mcp_frame_with_limit(reader, max_bytes)
+}
+
+/// Reads one framed message, rejecting a declared (or decompressed) body
+/// larger than `max_bytes` before allocating for it. A rejected body is
+/// skipped and reported as [`MessageTooLarge`].
+fn read_mcp_frame_with_limit<R: BufRead>(
+    reader: &mut R,
+    max_bytes: usize,
+) -> io::Result<Option<Vec<u8>>> {
     let mut content_length: Option<usize> = None;
+    let mut encoding = ContentEncoding::Identity;
 
     loop {
         let mut line = String::new();
@@ -33,6 +212,10 @@ pub fn read_mcp_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>>
                 )
             })?;
             content_length = Some(len);
+        } else if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Encoding")
+        {
+            encoding = parse_content_encoding(value)?;
         }
     }
 
@@ -43,10 +226,95 @@ pub fn read_mcp_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>>
         )
     })?;
 
+    if len > max_bytes {
+        let prefix = skip_body(reader, len)?;
+        // A compressed prefix holds no readable id.
+        let prefix = match encoding {
+            ContentEncoding::Identity => prefix.as_slice(),
+            ContentEncoding::Gzip => &[],
+        };
+        return Err(message_too_large(len, max_bytes, prefix));
+    }
     let mut body = vec![0_u8; len];
     reader.read_exact(&mut body)?;
+    if encoding == ContentEncoding::Gzip {
+        let mut decoded = Vec::new();
+        GzDecoder::new(body.as_slice())
+            .take(max_bytes as u64 + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Failed to decompress gzip MCP body: {e}"),
+                )
+            })?;
+        if decoded.len() > max_bytes {
+            return Err(message_too_large(decoded.len(), max_bytes, &decoded));
+        }
+        body = decoded;
+    }
+    Ok(Some(body))
+}
+
+fn read_json_line<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let max_bytes = max_message_bytes_from_env(env::var_os(MAX_MESSAGE_BYTES_ENV));
+    read_json_line_with_limit(reader, max_bytes)
+}
+
+fn read_json_line_with_limit<R: BufRead>(
+    reader: &mut R,
+    max_bytes: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    loop {
+        let mut line = Vec::new();
+        let bytes_read = reader
+            .take(max_bytes as u64 + 2)
+            .read_until(b'\n', &mut line)?;
+        if bytes_read == 0 {
+            return Ok(None);
+        }
+        let complete = line.last() == Some(&b'\n');
+        while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+            line.pop();
+        }
+        if line.len() > max_bytes {
+            if !complete {
+                skip_rest_of_line(reader)?;
+            }
+            return Err(message_too_large(line.len(), max_bytes, &line));
+        }
+        if !line.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Some(line));
+        }
+    }
+}
+
+fn write_json_line<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+    let mut line = serde_json::to_vec(value).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to serialize JSON response: {e}"),
+        )
+    })?;
+    line.push(b'\n');
+    write_all_retrying(writer, &line)?;
+    flush_retrying(writer)
+}
 
-    serde_json::from_slice(&body).map(Some).map_err(|e| {
+pub fn client_accepts_gzip(initialize_params: &Value) -> bool {
+    initialize_params
+        .pointer("/capabilities/experimental/acceptEncoding")
+        .and_then(Value::as_array)
+        .is_some_and(|encodings| {
+            encodings
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|encoding| encoding.eq_ignore_ascii_case("gzip"))
+        })
+}
+
+pub fn parse_mcp_body(body: &[u8]) -> io::Result<Value> {
+    serde_json::from_slice(body).map_err(|e| {
         io::Error::new(
             io::ErrorKind::InvalidData,
             format!("Invalid JSON body in MCP message: {e}"),
@@ -54,16 +322,139 @@ pub fn read_mcp_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>>
     })
 }
 
-pub fn write_mcp_message<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
-    let body = serde_json::to_vec(value).map_err(|e| {
+/// Best-effort `id` of a request whose body failed to parse, so the error
+/// reply can still be correlated. Only an `"id"` key of the top-level
+/// object counts; one nested in `params` or sitting inside a string is
+/// skipped, and anything else yields `null`.
+pub fn recover_request_id(body: &[u8]) -> Value {
+    let lossy = String::from_utf8_lossy(body);
+    let text = lossy.trim_start();
+    if !text.starts_with('{') {
+        return Value::Null;
+    }
+    let bytes = text.as_bytes();
+    let mut depth = 0usize;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' => {
+                let Some(end) = string_end(bytes, index) else {
+                    return Value::Null;
+                };
+                if depth == 1
+                    && &text[index..=end] == "\"id\""
+                    && let Some(rest) = text[end + 1..].trim_start().strip_prefix(':')
+                {
+                    return request_id_value(rest.trim_start());
+                }
+                index = end;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Value::Null;
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    Value::Null
+}
+
+/// Index of the quote closing the string that opens at `start`.
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut escaped = false;
+    for (offset, byte) in bytes[start + 1..].iter().enumerate() {
+        match byte {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(start + 1 + offset),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn request_id_value(rest: &str) -> Value {
+    if rest.starts_with('"') {
+        return string_end(rest.as_bytes(), 0)
+            .and_then(|end| serde_json::from_str::<String>(&rest[..=end]).ok())
+            .map(Value::String)
+            .unwrap_or(Value::Null);
+    }
+    let digits = rest
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '-')
+        .collect::<String>();
+    digits
+        .parse::<i64>()
+        .map(|id| json!(id))
+        .unwrap_or(Value::Null)
+}
+
+pub fn write_mcp_message<W: Write>(
+    writer: &mut W,
+    value: &Value,
+    encoding: ContentEncoding,
+) -> io::Result<()> {
+    let mut body = serde_json::to_vec(value).map_err(|e| {
         io::Error::new(
             io::ErrorKind::InvalidData,
             format!("Failed to serialize JSON response: {e}"),
         )
     })?;
-    write!(writer, "Content-Length: {}\r\n\r\n", body.len())?;
-    writer.write_all(&body)?;
-    writer.flush()
+    let mut frame = match encoding {
+        ContentEncoding::Identity => format!("Content-Length: {}\r\n\r\n", body.len()),
+        ContentEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&body)?;
+            body = encoder.finish()?;
+            format!(
+                "Content-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+                body.len()
+            )
+        }
+    }
+    .into_bytes();
+    frame.extend_from_slice(&body);
+    write_all_retrying(writer, &frame)?;
+    flush_retrying(writer)
+}
+
+fn flush_retrying<W: Write>(writer: &mut W) -> io::Result<()> {
+    loop {
+        match writer.flush() {
+            Ok(()) => return Ok(()),
+            Err(err) if is_retryable_write_error(&err) => thread::yield_now(),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn is_retryable_write_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    )
+}
+
+fn write_all_retrying<W: Write>(writer: &mut W, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match writer.write(buf) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "Failed to write whole MCP message",
+                ));
+            }
+            Ok(written) => buf = &buf[written..],
+            Err(err) if is_retryable_write_error(&err) => thread::yield_now(),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
 }
 
 pub fn success_response(id: Value, result: Value) -> Value {
@@ -84,3 +475,208 @@ pub fn error_response(id: Value, code: i64, message: &str) -> Value {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TrickleWriter {
+        written: Vec<u8>,
+        chunk_size: usize,
+        calls: usize,
+        flushes: usize,
+    }
+
+    impl Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(2) {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "try again"));
+            }
+            let len = buf.len().min(self.chunk_size);
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_mcp_frame_rejects_oversized_content_length() {
+        let mut reader = io::Cursor::new(b"Content-Length: 9999999999\r\n\r\n{}".to_vec());
+        let err = read_mcp_frame(&mut reader).expect_err("over-limit frame");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let body = br#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+        let frame = [
+            format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes(),
+            body,
+        ]
+        .concat();
+        let mut reader =
+            io::Cursor::new([frame.as_slice(), b"Content-Length: 2\r\n\r\n{}"].concat());
+        let err = read_mcp_frame_with_limit(&mut reader, 8).expect_err("over custom limit");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let too_large = MessageTooLarge::from_io_error(&err).expect("too-large payload");
+        assert_eq!(too_large.id, json!(1));
+        assert_eq!(too_large.len, body.len());
+        let next = read_mcp_frame_with_limit(&mut reader, 8).expect("body was skipped");
+        assert_eq!(next.as_deref(), Some(&b"{}"[..]));
+        let read = read_mcp_frame_with_limit(&mut io::Cursor::new(frame), body.len())
+            .expect("within limit");
+        assert_eq!(read.as_deref(), Some(&body[..]));
+
+        assert_eq!(max_message_bytes_from_env(None), DEFAULT_MAX_MESSAGE_BYTES);
+        assert_eq!(
+            max_message_bytes_from_env(Some(OsString::from("1024"))),
+            1024
+        );
+        assert_eq!(
+            max_message_bytes_from_env(Some(OsString::from("0"))),
+            DEFAULT_MAX_MESSAGE_BYTES
+        );
+    }
+
+    #[test]
+    fn oversized_json_line_is_skipped() {
+        let mut reader = io::Cursor::new(
+            concat!(
+                r#"{"jsonrpc":"2.0","id":"big","method":"ping","params":{"pad":"xxxxxxxx"}}"#,
+                "\n",
+                "{}\n"
+            )
+            .as_bytes(),
+        );
+        let err = read_json_line_with_limit(&mut reader, 32).expect_err("over-limit line");
+        let too_large = MessageTooLarge::from_io_error(&err).expect("too-large payload");
+        assert_eq!(too_large.id, json!("big"));
+        let next = read_json_line_with_limit(&mut reader, 32).expect("line was skipped");
+        assert_eq!(next.as_deref(), Some(&b"{}"[..]));
+    }
+
+    #[test]
+    fn framings_round_trip_messages() {
+        let messages = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}),
+            json!({"jsonrpc": "2.0", "id": 2, "result": {"text": "a\nb"}}),
+        ];
+        for framing in [Framing::ContentLength, Framing::JsonLines] {
+            let mut output = Vec::new();
+            for message in &messages {
+                framing
+                    .write_message(&mut output, message, ContentEncoding::Identity)
+                    .expect("write message");
+            }
+            if framing == Framing::JsonLines {
+                assert_eq!(output.iter().filter(|b| **b == b'\n').count(), 2);
+            }
+
+            let mut reader = io::Cursor::new(output);
+            for message in &messages {
+                let body = framing
+                    .read_message(&mut reader)
+                    .expect("read message")
+                    .expect("message present");
+                assert_eq!(&parse_mcp_body(&body).expect("parse body"), message);
+            }
+            assert!(
+                framing
+                    .read_message(&mut reader)
+                    .expect("read eof")
+                    .is_none()
+            );
+        }
+
+        assert_eq!(Framing::from_env_value(None), Ok(Framing::ContentLength));
+        assert_eq!(
+            Framing::from_env_value(Some(OsString::from("JSONL"))),
+            Ok(Framing::JsonLines)
+        );
+        assert!(Framing::from_env_value(Some(OsString::from("websocket"))).is_err());
+        assert!(Framing::from_env_value(Some(OsString::from("ndjson"))).is_err());
+    }
+
+    #[test]
+    fn recover_request_id_from_malformed_body() {
+        assert_eq!(
+            recover_request_id(br#"{"jsonrpc":"2.0","id": 7, bad"#),
+            json!(7)
+        );
+        assert_eq!(recover_request_id(br#"{"id":"abc", "#), json!("abc"));
+        assert_eq!(recover_request_id(b"not json at all"), Value::Null);
+    }
+
+    #[test]
+    fn recover_request_id_ignores_nested_and_quoted_ids() {
+        assert_eq!(
+            recover_request_id(br#"{"params":{"id":99,"x":"\"id\":5"},"id":7, bad"#),
+            json!(7)
+        );
+        assert_eq!(
+            recover_request_id(br#"{"method":"tools/call","params":{"id":99}, bad"#),
+            Value::Null
+        );
+        assert_eq!(recover_request_id(br#"[{"id":1}, bad"#), Value::Null);
+        assert_eq!(recover_request_id(br#"{"id":"a\"b", bad"#), json!("a\"b"));
+    }
+
+    #[test]
+    fn write_mcp_message_handles_partial_writes() {
+        let mut writer = TrickleWriter {
+            written: Vec::new(),
+            chunk_size: 3,
+            calls: 0,
+            flushes: 0,
+        };
+        let value = json!({"jsonrpc": "2.0", "id": 1, "result": {}});
+        write_mcp_message(&mut writer, &value, ContentEncoding::Identity).expect("write message");
+
+        let body = serde_json::to_vec(&value).expect("serialize");
+        let mut expected = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+        expected.extend_from_slice(&body);
+        assert_eq!(writer.written, expected);
+        assert_eq!(writer.flushes, 1);
+    }
+
+    #[test]
+    fn gzip_request_and_response_round_trip() {
+        let request = json!({"jsonrpc": "2.0", "id": 3, "method": "ping"});
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&serde_json::to_vec(&request).expect("serialize"))
+            .expect("compress");
+        let compressed = encoder.finish().expect("finish gzip");
+        let mut input = format!(
+            "Content-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        input.extend_from_slice(&compressed);
+
+        let body = read_mcp_frame(&mut io::Cursor::new(input))
+            .expect("read frame")
+            .expect("frame present");
+        assert_eq!(parse_mcp_body(&body).expect("parse body"), request);
+
+        let response = success_response(json!(3), json!({}));
+        let mut output = Vec::new();
+        write_mcp_message(&mut output, &response, ContentEncoding::Gzip).expect("write message");
+        let text = String::from_utf8_lossy(&output);
+        assert!(text.contains("Content-Encoding: gzip\r\n"));
+
+        let body = read_mcp_frame(&mut io::Cursor::new(output))
+            .expect("read response frame")
+            .expect("response present");
+        assert_eq!(parse_mcp_body(&body).expect("parse response"), response);
+    }
+
+    #[test]
+    fn client_accepts_gzip_reads_experimental_capability() {
+        let params = json!({"capabilities": {"experimental": {"acceptEncoding": ["gzip"]}}});
+        assert!(client_accepts_gzip(&params));
+        assert!(!client_accepts_gzip(&json!({"capabilities": {}})));
+    }
+}
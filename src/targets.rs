@@ -1,13 +1,59 @@
 use globset::{Glob, GlobSet, GlobSetBuilder};
-use serde_json::{Map, Value};
+use serde_json::{Map, Value, json};
 use std::collections::BTreeSet;
-use std::path::Path;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
+pub const DEFAULT_MAX_FILES: usize = 10_000;
+const MAX_FILES_ENV: &str = "GDSCRIPT_FORMATTER_MCP_MAX_FILES";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PathStyle {
+    #[default]
+    AsWalked,
+    Absolute,
+    RelativeToDir,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GlobBase {
+    #[default]
+    Relative,
+    Absolute,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ScanStats {
+    pub entries_visited: usize,
+    pub files_matched: usize,
+    pub files_excluded: usize,
+    pub elapsed: Duration,
+}
+
+impl ScanStats {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "entries_visited": self.entries_visited,
+            "files_matched": self.files_matched,
+            "files_excluded": self.files_excluded,
+            "elapsed_ms": (self.elapsed.as_secs_f64() * 1_000_000.0).round() / 1000.0
+        })
+    }
+}
+
 pub fn as_object(arguments: Option<&Value>) -> Result<Map<String, Value>, String> {
     match arguments {
         None => Ok(Map::new()),
         Some(Value::Object(map)) => Ok(map.clone()),
+        Some(Value::String(text)) => match serde_json::from_str::<Value>(text) {
+            Ok(Value::Object(map)) => Ok(map),
+            Ok(_) => Err("`arguments` string must encode a JSON object".to_owned()),
+            Err(e) => Err(format!("`arguments` string is not valid JSON: {e}")),
+        },
         Some(_) => Err("`arguments` must be a JSON object".to_owned()),
     }
 }
@@ -20,6 +66,17 @@ pub fn get_bool(arguments: &Map<String, Value>, key: &str) -> Result<bool, Strin
     }
 }
 
+pub fn get_optional_bool(
+    arguments: &Map<String, Value>,
+    key: &str,
+) -> Result<Option<bool>, String> {
+    match arguments.get(key) {
+        None => Ok(None),
+        Some(Value::Bool(value)) => Ok(Some(*value)),
+        Some(_) => Err(format!("`{key}` must be a boolean")),
+    }
+}
+
 pub fn get_optional_i64(arguments: &Map<String, Value>, key: &str) -> Result<Option<i64>, String> {
     match arguments.get(key) {
         None => Ok(None),
@@ -58,7 +115,7 @@ pub fn get_optional_string(
     }
 }
 
-fn get_optional_string_array(
+pub fn get_optional_string_array(
     arguments: &Map<String, Value>,
     key: &str,
 ) -> Result<Option<Vec<String>>, String> {
@@ -91,11 +148,66 @@ fn build_globset(patterns: &[String], key_name: &str) -> Result<GlobSet, String>
         .map_err(|e| format!("Failed to build glob set from `{key_name}`: {e}"))
 }
 
+pub fn get_optional_globset(
+    arguments: &Map<String, Value>,
+    key: &str,
+) -> Result<Option<GlobSet>, String> {
+    get_optional_string_array(arguments, key)?
+        .map(|patterns| build_globset(&patterns, key))
+        .transpose()
+}
+
+fn get_path_style(arguments: &Map<String, Value>) -> Result<PathStyle, String> {
+    match get_optional_string(arguments, "path_style")?.as_deref() {
+        None | Some("as_walked") => Ok(PathStyle::AsWalked),
+        Some("absolute") => Ok(PathStyle::Absolute),
+        Some("relative_to_dir") => Ok(PathStyle::RelativeToDir),
+        Some(other) => Err(format!(
+            "`path_style` must be one of as_walked, absolute, relative_to_dir (got {other})"
+        )),
+    }
+}
+
+fn get_glob_base(arguments: &Map<String, Value>) -> Result<GlobBase, String> {
+    match get_optional_string(arguments, "glob_base")?.as_deref() {
+        None | Some("relative") => Ok(GlobBase::Relative),
+        Some("absolute") => Ok(GlobBase::Absolute),
+        Some(other) => Err(format!(
+            "`glob_base` must be one of relative, absolute (got {other})"
+        )),
+    }
+}
+
+fn absolute_path_string(path: &Path) -> Result<String, String> {
+    path::absolute(path)
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| format!("Failed to make {} absolute: {}", path.display(), e))
+}
+
+/// Resolves symlinks and `..` where the file exists; missing files still get
+/// an absolute path so the formatter can report them. Relative paths are
+/// taken from `working_dir` (where the formatter runs) when there is one.
+fn canonical_path_string(path: &str, working_dir: Option<&Path>) -> Result<String, String> {
+    let path = match working_dir {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    };
+    match fs::canonicalize(&path) {
+        Ok(canonical) => Ok(canonical.to_string_lossy().to_string()),
+        Err(_) => absolute_path_string(&path),
+    }
+}
+
 fn collect_dir_files(
     dir: &str,
     include: &[String],
     exclude: &[String],
+    path_style: PathStyle,
+    glob_base: GlobBase,
+    skip_hidden: bool,
+    stats: &mut ScanStats,
 ) -> Result<Vec<String>, String> {
+    let started = Instant::now();
     let dir_path = Path::new(dir);
     if !dir_path.exists() {
         return Err(format!("`dir` does not exist: {dir}"));
@@ -108,8 +220,16 @@ fn collect_dir_files(
     let exclude_set = build_globset(exclude, "exclude")?;
 
     let mut files = Vec::new();
-    for entry in WalkDir::new(dir_path) {
+    // The root is never pruned, so a `dir` that is itself hidden still scans.
+    let walker = WalkDir::new(dir_path).into_iter().filter_entry(|entry| {
+        !skip_hidden
+            || entry.depth() == 0
+            || !entry.file_type().is_dir()
+            || !entry.file_name().to_string_lossy().starts_with('.')
+    });
+    for entry in walker {
         let entry = entry.map_err(|e| format!("Failed to walk directory '{dir}': {e}"))?;
+        stats.entries_visited += 1;
         if !entry.file_type().is_file() {
             continue;
         }
@@ -123,36 +243,69 @@ fn collect_dir_files(
             )
         })?;
 
-        if !include_set.is_match(relative) {
+        let absolute;
+        let match_path = match glob_base {
+            GlobBase::Relative => relative,
+            GlobBase::Absolute => {
+                absolute = path::absolute(path)
+                    .map_err(|e| format!("Failed to make {} absolute: {}", path.display(), e))?;
+                absolute.as_path()
+            }
+        };
+        if !include_set.is_match(match_path) {
             continue;
         }
-        if exclude_set.is_match(relative) {
+        if exclude_set.is_match(match_path) {
+            stats.files_excluded += 1;
             continue;
         }
+        stats.files_matched += 1;
 
-        files.push(path.to_string_lossy().to_string());
+        files.push(match path_style {
+            PathStyle::AsWalked => path.to_string_lossy().to_string(),
+            PathStyle::Absolute => absolute_path_string(path)?,
+            PathStyle::RelativeToDir => relative.to_string_lossy().to_string(),
+        });
     }
 
+    stats.elapsed += started.elapsed();
     Ok(files)
 }
 
 pub fn resolve_target_files(
     arguments: &Map<String, Value>,
     required: bool,
-) -> Result<Vec<String>, String> {
+) -> Result<(Vec<String>, ScanStats), String> {
     let direct_files = get_optional_string_array(arguments, "files")?.unwrap_or_default();
     let dir = get_optional_string(arguments, "dir")?;
     let include = get_optional_string_array(arguments, "include")?
         .unwrap_or_else(|| vec!["**/*.gd".to_owned()]);
     let exclude = get_optional_string_array(arguments, "exclude")?.unwrap_or_default();
+    let path_style = get_path_style(arguments)?;
+    let glob_base = get_glob_base(arguments)?;
+    let skip_hidden = get_optional_bool(arguments, "skip_hidden")?.unwrap_or(true);
+    let absolute_paths = get_bool(arguments, "absolute_paths")?;
 
+    let mut stats = ScanStats::default();
     let mut unique_files = BTreeSet::new();
     for file in direct_files {
-        unique_files.insert(file);
+        if path_style == PathStyle::AsWalked {
+            unique_files.insert(file);
+        } else {
+            unique_files.insert(absolute_path_string(Path::new(&file))?);
+        }
     }
 
     if let Some(dir) = dir {
-        let dir_files = collect_dir_files(&dir, &include, &exclude)?;
+        let dir_files = collect_dir_files(
+            &dir,
+            &include,
+            &exclude,
+            path_style,
+            glob_base,
+            skip_hidden,
+            &mut stats,
+        )?;
         for file in dir_files {
             unique_files.insert(file);
         }
@@ -164,7 +317,52 @@ pub fn resolve_target_files(
         return Err("Either `files` or `dir` must resolve to at least one file".to_owned());
     }
 
-    Ok(unique_files.into_iter().collect())
+    if absolute_paths {
+        let working_dir = resolve_working_dir(arguments)?;
+        unique_files = unique_files
+            .iter()
+            .map(|file| canonical_path_string(file, working_dir.as_deref()))
+            .collect::<Result<_, _>>()?;
+    }
+
+    Ok((unique_files.into_iter().collect(), stats))
+}
+
+pub fn resolve_working_dir(arguments: &Map<String, Value>) -> Result<Option<PathBuf>, String> {
+    if get_path_style(arguments)? != PathStyle::RelativeToDir {
+        return Ok(None);
+    }
+    Ok(get_optional_string(arguments, "dir")?.map(PathBuf::from))
+}
+
+fn max_files_from_env(value: Option<OsString>) -> Result<usize, String> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_MAX_FILES);
+    };
+    value
+        .to_str()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|v| *v >= 1)
+        .ok_or_else(|| format!("{MAX_FILES_ENV} must be a positive integer"))
+}
+
+pub fn resolve_max_files(arguments: &Map<String, Value>) -> Result<usize, String> {
+    match get_optional_usize(arguments, "max_files")? {
+        Some(0) => Err("`max_files` must be at least 1".to_owned()),
+        Some(value) => Ok(value),
+        None => max_files_from_env(env::var_os(MAX_FILES_ENV)),
+    }
+}
+
+pub fn enforce_max_files(files: &[String], max_files: usize) -> Result<(), String> {
+    if files.len() > max_files {
+        return Err(format!(
+            "Resolved {} files, which exceeds the `max_files` limit of {}. Narrow `dir`/`include` or raise `max_files`.",
+            files.len(),
+            max_files
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -194,7 +392,7 @@ mod tests {
             "exclude": ["sub/d.gd"]
         }));
 
-        let files = resolve_target_files(&args, true).expect("resolve files");
+        let files = resolve_target_files(&args, true).expect("resolve files").0;
         let files: BTreeSet<_> = files.into_iter().collect();
 
         assert!(files.contains(&root.join("a.gd").to_string_lossy().to_string()));
@@ -203,6 +401,102 @@ mod tests {
         assert_eq!(files.len(), 2);
     }
 
+    #[test]
+    fn glob_base_absolute_matches_full_paths() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let root = path::absolute(temp.path()).expect("absolute temp dir");
+        fs::create_dir_all(root.join("sub")).expect("create sub dir");
+        fs::write(root.join("a.gd"), "extends Node\n").expect("write a.gd");
+        fs::write(root.join("sub").join("c.gd"), "extends Node\n").expect("write c.gd");
+        let pattern = format!("{}/sub/*.gd", root.to_string_lossy());
+
+        let relative = map_from_json(json!({
+            "dir": root.to_string_lossy().to_string(),
+            "include": [pattern]
+        }));
+        assert!(
+            resolve_target_files(&relative, false)
+                .expect("resolve files")
+                .0
+                .is_empty()
+        );
+
+        let absolute = map_from_json(json!({
+            "dir": root.to_string_lossy().to_string(),
+            "include": [pattern],
+            "glob_base": "absolute"
+        }));
+        assert_eq!(
+            resolve_target_files(&absolute, true)
+                .expect("resolve files")
+                .0,
+            vec![root.join("sub").join("c.gd").to_string_lossy().to_string()]
+        );
+    }
+
+    #[test]
+    fn as_object_accepts_json_encoded_string() {
+        let object = json!({"check": true});
+        assert_eq!(
+            as_object(Some(&object)).expect("object"),
+            map_from_json(json!({"check": true}))
+        );
+
+        let encoded = json!("{\"check\": true}");
+        assert_eq!(
+            as_object(Some(&encoded)).expect("encoded object"),
+            map_from_json(json!({"check": true}))
+        );
+
+        assert!(as_object(Some(&json!("{not json"))).is_err());
+        assert!(as_object(Some(&json!("[1, 2]"))).is_err());
+    }
+
+    #[test]
+    fn resolve_target_files_skips_hidden_directories() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let root = temp.path().join(".project");
+        for dir in [".git", ".godot/editor", "scenes"] {
+            fs::create_dir_all(root.join(dir)).expect("create dir");
+        }
+        fs::write(root.join(".git").join("hook.gd"), "x\n").expect("write .git file");
+        fs::write(root.join(".godot/editor").join("cache.gd"), "x\n").expect("write .godot file");
+        fs::write(root.join("scenes").join("main.gd"), "x\n").expect("write main.gd");
+        fs::write(root.join(".hidden_script.gd"), "x\n").expect("write hidden file");
+
+        let mut args = map_from_json(json!({
+            "dir": root.to_string_lossy().to_string(),
+            "path_style": "relative_to_dir"
+        }));
+        let files = resolve_target_files(&args, true).expect("resolve files").0;
+        assert_eq!(files, vec![".hidden_script.gd", "scenes/main.gd"]);
+
+        args.insert("skip_hidden".to_owned(), json!(false));
+        let files = resolve_target_files(&args, true).expect("resolve files").0;
+        assert_eq!(files.len(), 4);
+    }
+
+    #[test]
+    fn resolve_target_files_reports_scan_stats() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let root = temp.path();
+        fs::create_dir_all(root.join("sub")).expect("create sub dir");
+        fs::write(root.join("a.gd"), "extends Node\n").expect("write a.gd");
+        fs::write(root.join("b.txt"), "x\n").expect("write b.txt");
+        fs::write(root.join("sub").join("c.gd"), "extends Node\n").expect("write c.gd");
+        fs::write(root.join("sub").join("d.gd"), "extends Node\n").expect("write d.gd");
+
+        let args = map_from_json(json!({
+            "dir": root.to_string_lossy().to_string(),
+            "exclude": ["sub/d.gd"]
+        }));
+
+        let (_, stats) = resolve_target_files(&args, true).expect("resolve files");
+        assert_eq!(stats.entries_visited, 6);
+        assert_eq!(stats.files_matched, 2);
+        assert_eq!(stats.files_excluded, 1);
+    }
+
     #[test]
     fn resolve_target_files_deduplicates_files_and_dir_results() {
         let temp = tempfile::tempdir().expect("create temp dir");
@@ -216,7 +510,7 @@ mod tests {
             "include": ["a.gd"]
         }));
 
-        let files = resolve_target_files(&args, true).expect("resolve files");
+        let files = resolve_target_files(&args, true).expect("resolve files").0;
         assert_eq!(files.len(), 1);
     }
 
@@ -228,4 +522,122 @@ mod tests {
         let err = resolve_target_files(&args, false).expect_err("should fail");
         assert_eq!(err, "`include`/`exclude` can only be used with `dir`");
     }
+
+    #[test]
+    fn enforce_max_files_rejects_too_many_files() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let root = temp.path();
+        for name in ["a.gd", "b.gd", "c.gd"] {
+            fs::write(root.join(name), "extends Node\n").expect("write file");
+        }
+
+        let args = map_from_json(json!({
+            "dir": root.to_string_lossy().to_string(),
+            "max_files": 2
+        }));
+        let files = resolve_target_files(&args, true).expect("resolve files").0;
+        let max_files = resolve_max_files(&args).expect("resolve max_files");
+        let err = enforce_max_files(&files, max_files).expect_err("should fail");
+        assert_eq!(
+            err,
+            "Resolved 3 files, which exceeds the `max_files` limit of 2. Narrow `dir`/`include` or raise `max_files`."
+        );
+    }
+
+    #[test]
+    fn max_files_from_env_validates_value() {
+        assert_eq!(max_files_from_env(None), Ok(DEFAULT_MAX_FILES));
+        assert_eq!(max_files_from_env(Some(OsString::from("25"))), Ok(25));
+        assert!(max_files_from_env(Some(OsString::from("0"))).is_err());
+        assert!(max_files_from_env(Some(OsString::from("many"))).is_err());
+    }
+
+    #[test]
+    fn path_style_normalizes_dir_resolved_paths() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let root = temp.path();
+        fs::create_dir_all(root.join("sub")).expect("create sub dir");
+        fs::write(root.join("sub").join("a.gd"), "extends Node\n").expect("write a.gd");
+        let walked_dir = root.join("sub").join("..");
+        let relative_dir = walked_dir.to_string_lossy().to_string();
+
+        let resolve = |style: &str| {
+            let args = map_from_json(json!({"dir": relative_dir, "path_style": style}));
+            resolve_target_files(&args, true).expect("resolve files").0
+        };
+
+        let as_walked = walked_dir.join("sub").join("a.gd");
+        assert_eq!(resolve("as_walked"), vec![as_walked.to_string_lossy()]);
+        assert_eq!(
+            resolve("absolute"),
+            vec![
+                path::absolute(&as_walked)
+                    .expect("absolute path")
+                    .to_string_lossy()
+            ]
+        );
+        assert_eq!(
+            resolve("relative_to_dir"),
+            vec![Path::new("sub").join("a.gd").to_string_lossy()]
+        );
+
+        let args = map_from_json(json!({"dir": relative_dir, "path_style": "relative_to_dir"}));
+        assert_eq!(
+            resolve_working_dir(&args).expect("working dir"),
+            Some(PathBuf::from(&relative_dir))
+        );
+        let args = map_from_json(json!({"dir": relative_dir, "path_style": "sideways"}));
+        assert!(resolve_target_files(&args, true).is_err());
+    }
+
+    #[test]
+    fn absolute_paths_canonicalizes_every_resolved_file() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let root = temp.path();
+        fs::create_dir_all(root.join("sub")).expect("create sub dir");
+        fs::write(root.join("sub").join("a.gd"), "extends Node\n").expect("write a.gd");
+        fs::write(root.join("b.gd"), "extends Node\n").expect("write b.gd");
+        let dotted_dir = root.join("sub").join("..");
+        let direct_file = root.join("sub").join("..").join("b.gd");
+
+        let args = map_from_json(json!({
+            "dir": dotted_dir.to_string_lossy(),
+            "files": [direct_file.to_string_lossy()],
+            "absolute_paths": true
+        }));
+        let files = resolve_target_files(&args, true).expect("resolve files").0;
+        let canonical_root = fs::canonicalize(root).expect("canonical root");
+        assert_eq!(
+            files,
+            vec![
+                canonical_root.join("b.gd").to_string_lossy().to_string(),
+                canonical_root
+                    .join("sub")
+                    .join("a.gd")
+                    .to_string_lossy()
+                    .to_string(),
+            ]
+        );
+        assert!(files.iter().all(|file| Path::new(file).is_absolute()));
+
+        let args = map_from_json(json!({
+            "dir": dotted_dir.to_string_lossy(),
+            "absolute_paths": true,
+            "path_style": "relative_to_dir"
+        }));
+        assert_eq!(
+            resolve_target_files(&args, true).expect("resolve files").0,
+            files
+        );
+
+        // Relative names resolve against the formatter's working dir, not ours.
+        assert_eq!(
+            canonical_path_string("sub/../b.gd", Some(root)),
+            Ok(canonical_root.join("b.gd").to_string_lossy().to_string())
+        );
+        assert_eq!(
+            canonical_path_string("missing.gd", Some(root)),
+            Ok(root.join("missing.gd").to_string_lossy().to_string())
+        );
+    }
 }
@@ -0,0 +1,247 @@
+use crate::formatter_manager::FormatterManager;
+use crate::tools::ToolError;
+use crate::tools::format::{FormatOptions, run_format_file};
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+pub struct FormatBuffer {
+    pub path: String,
+    pub content: String,
+}
+
+pub struct BufferResult {
+    pub path: String,
+    pub formatted: Option<String>,
+    pub reason: Option<String>,
+    pub was_empty: bool,
+}
+
+pub struct BuffersToolResult {
+    pub success: bool,
+    pub buffers: Vec<BufferResult>,
+}
+
+fn parse_buffers(arguments: &Map<String, Value>) -> Result<Vec<FormatBuffer>, String> {
+    let Some(value) = arguments.get("buffers") else {
+        return Err("`buffers` is required".to_owned());
+    };
+    let Some(items) = value.as_array() else {
+        return Err("`buffers` must be an array of {path, content} objects".to_owned());
+    };
+    if items.is_empty() {
+        return Err("`buffers` must contain at least one buffer".to_owned());
+    }
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let path = item.get("path").and_then(Value::as_str);
+            let content = item.get("content").and_then(Value::as_str);
+            match (path, content) {
+                (Some(path), Some(content)) => Ok(FormatBuffer {
+                    path: path.to_owned(),
+                    content: content.to_owned(),
+                }),
+                _ => Err(format!(
+                    "`buffers[{i}]` must have string `path` and `content`"
+                )),
+            }
+        })
+        .collect()
+}
+
+fn format_buffer(
+    binary: &Path,
+    work_dir: &Path,
+    index: usize,
+    buffer: &FormatBuffer,
+    options: &FormatOptions,
+) -> BufferResult {
+    let failed = |reason: String| BufferResult {
+        path: buffer.path.clone(),
+        formatted: None,
+        reason: Some(reason),
+        was_empty: false,
+    };
+    // The formatter rejects zero-byte files, and there is nothing to format anyway.
+    if buffer.content.is_empty() {
+        return BufferResult {
+            path: buffer.path.clone(),
+            formatted: Some(String::new()),
+            reason: None,
+            was_empty: true,
+        };
+    }
+
+    let file_name = Path::new(&buffer.path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "buffer.gd".to_owned());
+    let buffer_dir = work_dir.join(index.to_string());
+    let temp_file = buffer_dir.join(file_name);
+    if let Err(e) =
+        fs::create_dir(&buffer_dir).and_then(|()| fs::write(&temp_file, &buffer.content))
+    {
+        return failed(format!("Failed to write temp file for buffer: {e}"));
+    }
+
+    let attempt = run_format_file(binary, options, &temp_file.to_string_lossy());
+    if let Some(reason) = attempt.failure_reason {
+        return failed(reason);
+    }
+    match fs::read_to_string(&temp_file) {
+        Ok(formatted) => BufferResult {
+            path: buffer.path.clone(),
+            formatted: Some(formatted),
+            reason: None,
+            was_empty: false,
+        },
+        Err(e) => failed(format!("Failed to read formatted buffer: {e}")),
+    }
+}
+
+pub fn format_buffers(
+    binary: &Path,
+    buffers: &[FormatBuffer],
+    options: &FormatOptions,
+) -> Result<BuffersToolResult, String> {
+    let work_dir = tempdir().map_err(|e| format!("Failed to create temp dir for buffers: {e}"))?;
+    let results = buffers
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| format_buffer(binary, work_dir.path(), index, buffer, options))
+        .collect::<Vec<_>>();
+
+    Ok(BuffersToolResult {
+        success: results.iter().all(|result| result.reason.is_none()),
+        buffers: results,
+    })
+}
+
+pub fn render_buffers_summary(result: &BuffersToolResult) -> String {
+    let failed_count = result
+        .buffers
+        .iter()
+        .filter(|buffer| buffer.reason.is_some())
+        .count();
+    if failed_count == 0 {
+        format!("Buffers formatted. buffer_count={}.", result.buffers.len())
+    } else {
+        format!("Buffer formatting failed. failed_count={failed_count}.")
+    }
+}
+
+pub fn buffers_structured_content(result: &BuffersToolResult) -> Value {
+    let buffers = result
+        .buffers
+        .iter()
+        .map(|buffer| match (&buffer.formatted, &buffer.reason) {
+            (Some(formatted), _) => {
+                let mut entry = json!({
+                    "path": buffer.path,
+                    "ok": true,
+                    "formatted": formatted
+                });
+                if buffer.was_empty
+                    && let Some(map) = entry.as_object_mut()
+                {
+                    map.insert("was_empty".to_owned(), json!(true));
+                }
+                entry
+            }
+            (None, reason) => json!({
+                "path": buffer.path,
+                "ok": false,
+                "reason": reason
+            }),
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "ok": result.success,
+        "buffers": buffers
+    })
+}
+
+pub fn call_gdscript_format_buffers(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<BuffersToolResult, ToolError> {
+    let buffers = parse_buffers(arguments)?;
+    let mut options = FormatOptions::from_arguments(arguments)?;
+    options.check = false;
+    options.stdout = false;
+
+    let binary = manager.ensure_binary()?;
+    Ok(format_buffers(&binary, &buffers, &options)?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::write_fake_formatter;
+
+    #[test]
+    fn format_buffers_handles_failures_per_buffer() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = write_fake_formatter(
+            temp.path(),
+            "for last; do :; done\nif grep -q 'func (' \"$last\"; then echo \"Error: \\\"Failed to format file $last: Topiary formatting failed\\\"\" >&2; exit 1; fi\nsed 's/  */ /g' \"$last\" > \"$last.tmp\" && mv \"$last.tmp\" \"$last\"\n",
+        );
+        let buffers = vec![
+            FormatBuffer {
+                path: "res://player.gd".to_owned(),
+                content: "var  speed  =  1\n".to_owned(),
+            },
+            FormatBuffer {
+                path: "res://broken.gd".to_owned(),
+                content: "func (:\n".to_owned(),
+            },
+        ];
+
+        let result =
+            format_buffers(&binary, &buffers, &FormatOptions::default()).expect("format buffers");
+        assert!(!result.success);
+        assert_eq!(result.buffers[0].path, "res://player.gd");
+        assert_eq!(
+            result.buffers[0].formatted.as_deref(),
+            Some("var speed = 1\n")
+        );
+        assert_eq!(result.buffers[1].path, "res://broken.gd");
+        assert_eq!(
+            result.buffers[1].reason.as_deref(),
+            Some("Topiary formatting failed")
+        );
+    }
+
+    #[test]
+    fn format_buffers_handles_edge_content() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = write_fake_formatter(
+            temp.path(),
+            "for last; do :; done\nif [ ! -s \"$last\" ]; then echo 'Error: empty file' >&2; exit 1; fi\n",
+        );
+        let buffers = ["", "  \n\t\n", "extends Node"]
+            .iter()
+            .map(|content| FormatBuffer {
+                path: "res://edge.gd".to_owned(),
+                content: (*content).to_owned(),
+            })
+            .collect::<Vec<_>>();
+
+        let result =
+            format_buffers(&binary, &buffers, &FormatOptions::default()).expect("format buffers");
+        assert!(result.success);
+        assert_eq!(result.buffers[0].formatted.as_deref(), Some(""));
+        assert!(result.buffers[0].was_empty);
+        assert_eq!(result.buffers[1].formatted.as_deref(), Some("  \n\t\n"));
+        assert!(!result.buffers[1].was_empty);
+        assert_eq!(result.buffers[2].formatted.as_deref(), Some("extends Node"));
+
+        let structured = buffers_structured_content(&result);
+        assert_eq!(structured["buffers"][0]["was_empty"], true);
+        assert!(structured["buffers"][1].get("was_empty").is_none());
+    }
+}
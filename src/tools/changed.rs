@@ -0,0 +1,165 @@
+use crate::formatter_manager::FormatterManager;
+use crate::targets::get_optional_string;
+use crate::tools::ToolError;
+use crate::tools::format::{FormatFailure, FormatOptions, run_format};
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use std::process::Command;
+
+pub struct CheckChangedResult {
+    pub success: bool,
+    pub changed_files: Vec<String>,
+    pub unformatted: Vec<FormatFailure>,
+}
+
+fn git_output(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn list_changed_gdscript_files(repo: &Path, base: &str) -> Result<Vec<String>, String> {
+    let tracked = git_output(
+        repo,
+        &[
+            "diff",
+            "--name-only",
+            "--relative",
+            "--diff-filter=ACMR",
+            base,
+        ],
+    )?;
+    let untracked = git_output(repo, &["ls-files", "--others", "--exclude-standard"])?;
+
+    let mut files = tracked
+        .lines()
+        .chain(untracked.lines())
+        .map(str::trim)
+        .filter(|line| line.ends_with(".gd"))
+        .map(|line| repo.join(line).to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+pub fn check_changed_files(
+    binary: &Path,
+    repo: &Path,
+    base: &str,
+    options: &FormatOptions,
+) -> Result<CheckChangedResult, String> {
+    let changed_files = list_changed_gdscript_files(repo, base)?;
+    let result = run_format(binary, &changed_files, options);
+    Ok(CheckChangedResult {
+        success: result.success,
+        changed_files,
+        unformatted: result.failures,
+    })
+}
+
+pub fn render_check_changed_summary(result: &CheckChangedResult) -> String {
+    if result.success {
+        format!(
+            "Changed files are formatted. changed_count={}.",
+            result.changed_files.len()
+        )
+    } else {
+        format!(
+            "Changed files need formatting. unformatted_count={}.",
+            result.unformatted.len()
+        )
+    }
+}
+
+pub fn check_changed_structured_content(result: &CheckChangedResult) -> Value {
+    json!({
+        "ok": result.success,
+        "changed_count": result.changed_files.len(),
+        "unformatted_count": result.unformatted.len(),
+        "unformatted": result
+            .unformatted
+            .iter()
+            .map(|f| json!({"file": f.file, "reason": f.reason, "category": f.category}))
+            .collect::<Vec<_>>()
+    })
+}
+
+pub fn call_gdscript_format_check_changed(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<CheckChangedResult, ToolError> {
+    let dir = get_optional_string(arguments, "dir")?.unwrap_or_else(|| ".".to_owned());
+    let base = get_optional_string(arguments, "base")?.unwrap_or_else(|| "HEAD".to_owned());
+    let mut options = FormatOptions::from_arguments(arguments)?;
+    options.check = true;
+    options.stdout = false;
+
+    let binary = manager.ensure_binary()?;
+    Ok(check_changed_files(
+        &binary,
+        Path::new(&dir),
+        &base,
+        &options,
+    )?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::write_fake_formatter;
+    use std::fs;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()
+            .expect("run git");
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn check_changed_files_reports_unformatted_changed_file() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let repo = temp.path().join("repo");
+        fs::create_dir(&repo).expect("create repo dir");
+        git(&repo, &["init", "-q"]);
+        fs::write(repo.join("clean.gd"), "extends Node\n").expect("write clean.gd");
+        fs::write(repo.join("untouched.gd"), "unformatted\n").expect("write untouched.gd");
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "init"]);
+
+        fs::write(repo.join("clean.gd"), "extends Node2D\n").expect("modify clean.gd");
+        fs::write(repo.join("new.gd"), "unformatted\n").expect("write new.gd");
+
+        let binary = write_fake_formatter(
+            temp.path(),
+            "for last; do :; done\nif grep -q unformatted \"$last\"; then echo \"Error: \\\"Failed to format file $last: not formatted\\\"\" >&2; exit 1; fi\n",
+        );
+        let options = FormatOptions {
+            check: true,
+            ..Default::default()
+        };
+        let result = check_changed_files(&binary, &repo, "HEAD", &options).expect("check");
+
+        assert!(!result.success);
+        assert_eq!(result.changed_files.len(), 2);
+        assert_eq!(result.unformatted.len(), 1);
+        assert!(result.unformatted[0].file.ends_with("new.gd"));
+        assert_eq!(result.unformatted[0].reason, "not formatted");
+    }
+}
@@ -0,0 +1,153 @@
+use crate::config::apply_profile;
+use crate::formatter_manager::FormatterManager;
+use crate::targets::{
+    enforce_max_files, get_optional_string, resolve_max_files, resolve_target_files,
+};
+use crate::tools::ToolError;
+use crate::tools::explain::unified_diff;
+use crate::tools::format::{FormatOptions, format_copy_passes};
+use serde_json::{Map, Value, json};
+use std::path::Path;
+
+pub struct VersionDiff {
+    pub file: String,
+    pub diff: Option<String>,
+    pub reason: Option<String>,
+}
+
+pub struct CompareVersionsResult {
+    pub from: String,
+    pub to: String,
+    pub files: Vec<VersionDiff>,
+}
+
+fn format_with(binary: &Path, options: &FormatOptions, file: &str) -> Result<String, String> {
+    let (_, passes) = format_copy_passes(binary, options, Path::new(file), 1)?;
+    Ok(String::from_utf8_lossy(&passes[0]).to_string())
+}
+
+pub fn compare_formatter_outputs(
+    from: (&str, &Path),
+    to: (&str, &Path),
+    files: &[String],
+    options: &FormatOptions,
+) -> Vec<VersionDiff> {
+    files
+        .iter()
+        .map(|file| {
+            let outputs = format_with(from.1, options, file)
+                .and_then(|old| Ok((old, format_with(to.1, options, file)?)));
+            match outputs {
+                Ok((old, new)) => {
+                    let diff = unified_diff(&old, &new, from.0, to.0);
+                    VersionDiff {
+                        file: file.clone(),
+                        diff: Some(diff),
+                        reason: None,
+                    }
+                }
+                Err(reason) => VersionDiff {
+                    file: file.clone(),
+                    diff: None,
+                    reason: Some(reason),
+                },
+            }
+        })
+        .collect()
+}
+
+pub fn render_compare_versions_summary(result: &CompareVersionsResult) -> String {
+    let changed = result
+        .files
+        .iter()
+        .filter(|f| f.diff.as_ref().is_some_and(|d| !d.is_empty()))
+        .count();
+    let failed = result.files.iter().filter(|f| f.reason.is_some()).count();
+    format!(
+        "Compared {} -> {}. files={}, changed={}, failed={}.",
+        result.from,
+        result.to,
+        result.files.len(),
+        changed,
+        failed
+    )
+}
+
+pub fn compare_versions_structured_content(result: &CompareVersionsResult) -> Value {
+    let files = result
+        .files
+        .iter()
+        .map(|f| match (&f.diff, &f.reason) {
+            (Some(diff), _) => {
+                json!({"file": f.file, "ok": true, "changed": !diff.is_empty(), "diff": diff})
+            }
+            (None, reason) => json!({"file": f.file, "ok": false, "reason": reason}),
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "ok": result.files.iter().all(|f| f.reason.is_none()),
+        "from": result.from,
+        "to": result.to,
+        "files": files
+    })
+}
+
+pub fn call_gdscript_compare_versions(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<CompareVersionsResult, ToolError> {
+    let arguments = &apply_profile(arguments)?;
+    let from =
+        get_optional_string(arguments, "from")?.ok_or_else(|| "`from` is required".to_owned())?;
+    let to = get_optional_string(arguments, "to")?.ok_or_else(|| "`to` is required".to_owned())?;
+    let (files, _) = resolve_target_files(arguments, true)?;
+    enforce_max_files(&files, resolve_max_files(arguments)?)?;
+    let options = FormatOptions::from_arguments(arguments)?;
+
+    let from_binary = manager.ensure_binary_for_tag(&from)?;
+    let to_binary = manager.ensure_binary_for_tag(&to)?;
+    let files =
+        compare_formatter_outputs((&from, &from_binary), (&to, &to_binary), &files, &options);
+    Ok(CompareVersionsResult { from, to, files })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::write_fake_formatter;
+    use std::fs;
+
+    #[test]
+    fn compare_formatter_outputs_diffs_two_binaries() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let old_dir = temp.path().join("old");
+        let new_dir = temp.path().join("new");
+        fs::create_dir(&old_dir).expect("create old dir");
+        fs::create_dir(&new_dir).expect("create new dir");
+        let old = write_fake_formatter(
+            &old_dir,
+            "for last; do :; done\necho 'var a = 1' > \"$last\"\n",
+        );
+        let new = write_fake_formatter(
+            &new_dir,
+            "for last; do :; done\necho 'var a := 1' > \"$last\"\n",
+        );
+        let file = temp.path().join("a.gd");
+        fs::write(&file, "var a=1\n").expect("write a.gd");
+        let files = vec![file.to_string_lossy().to_string()];
+
+        let diffs = compare_formatter_outputs(
+            ("0.18.1", &old),
+            ("0.18.2", &new),
+            &files,
+            &FormatOptions::default(),
+        );
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].diff.as_deref(),
+            Some("--- 0.18.1\n+++ 0.18.2\n@@ -1,1 +1,1 @@\n-var a = 1\n+var a := 1\n")
+        );
+        assert_eq!(fs::read_to_string(&file).expect("read a.gd"), "var a=1\n");
+    }
+}
@@ -0,0 +1,66 @@
+use crate::formatter_manager::FormatterManager;
+use crate::tools::ToolError;
+use crate::tools::help::parse_help_flags;
+use serde_json::{Map, Value, json};
+
+const NON_CONFIG_FLAGS: [&str; 2] = ["--help", "--version"];
+
+pub struct ConfigKeysToolResult {
+    pub formatter_keys: Vec<String>,
+    pub lint_keys: Vec<String>,
+}
+
+pub fn parse_config_keys(help: &str) -> Vec<String> {
+    parse_help_flags(help)
+        .into_iter()
+        .filter_map(|flag| flag.long)
+        .filter(|long| !NON_CONFIG_FLAGS.contains(&long.as_str()))
+        .map(|long| long.trim_start_matches("--").replace('-', "_"))
+        .collect()
+}
+
+pub fn config_keys_structured_content(result: &ConfigKeysToolResult) -> Value {
+    json!({
+        "ok": true,
+        "source": "help",
+        "formatter_keys": result.formatter_keys,
+        "lint_keys": result.lint_keys
+    })
+}
+
+pub fn render_config_keys_summary(result: &ConfigKeysToolResult) -> String {
+    format!(
+        "Formatter config keys: {}. Lint config keys: {}.",
+        result.formatter_keys.join(", "),
+        result.lint_keys.join(", ")
+    )
+}
+
+pub fn call_gdscript_config_keys(
+    manager: &FormatterManager,
+    _arguments: &Map<String, Value>,
+) -> Result<ConfigKeysToolResult, ToolError> {
+    let binary = manager.ensure_binary()?;
+    let formatter_help = manager.cached_output(&binary, &["--help"])?;
+    let lint_help = manager.cached_output(&binary, &["lint", "--help"])?;
+
+    Ok(ConfigKeysToolResult {
+        formatter_keys: parse_config_keys(&formatter_help),
+        lint_keys: parse_config_keys(&lint_help),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config_keys_normalizes_long_flags() {
+        let help = "Usage: gdscript-formatter [OPTIONS] [FILES]...\n\nOptions:\n  -c, --check                      Check if files are formatted\n      --indent-size <INDENT_SIZE>  Number of spaces to use\n      --use-spaces                 Use spaces for indentation\n      --reorder-code               Reorder code\n  -h, --help                       Print help\n  -V, --version                    Print version\n";
+
+        assert_eq!(
+            parse_config_keys(help),
+            vec!["check", "indent_size", "use_spaces", "reorder_code"]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+pub struct ChangeHunk {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+struct Hunk<'a> {
+    line: usize,
+    new_line: usize,
+    removed: Vec<&'a str>,
+    added: Vec<&'a str>,
+}
+
+fn diff_hunks<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Hunk<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+    let (n, m) = (old.len(), new.len());
+    if n == 0 && m == 0 {
+        return Vec::new();
+    }
+    if (n + 1) * (m + 1) > MAX_DIFF_CELLS {
+        return vec![Hunk {
+            line: prefix + 1,
+            new_line: prefix + 1,
+            removed: old.to_vec(),
+            added: new.to_vec(),
+        }];
+    }
+
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let mut current: Option<Hunk> = None;
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            hunks.extend(current.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let hunk = current.get_or_insert_with(|| Hunk {
+            line: prefix + i + 1,
+            new_line: prefix + j + 1,
+            removed: Vec::new(),
+            added: Vec::new(),
+        });
+        if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            hunk.added.push(new[j]);
+            j += 1;
+        } else {
+            hunk.removed.push(old[i]);
+            i += 1;
+        }
+    }
+    hunks.extend(current);
+    hunks
+}
+
+fn classify_hunk(removed: &[&str], added: &[&str]) -> &'static str {
+    let squash = |lines: &[&str]| {
+        lines
+            .iter()
+            .flat_map(|line| line.chars())
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+    };
+    let same_line_count = removed.len() == added.len();
+    if same_line_count
+        && removed
+            .iter()
+            .zip(added)
+            .all(|(a, b)| a.trim_start() == b.trim_start())
+    {
+        return "indentation";
+    }
+    if squash(removed) == squash(added) {
+        return if same_line_count {
+            "spacing"
+        } else {
+            "line_wrap"
+        };
+    }
+    let sorted = |lines: &[&str]| {
+        let mut trimmed = lines
+            .iter()
+            .map(|line| line.trim().to_owned())
+            .collect::<Vec<_>>();
+        trimmed.sort_unstable();
+        trimmed
+    };
+    if sorted(removed) == sorted(added) {
+        return "reordering";
+    }
+    "other"
+}
+
+pub fn explain_changes(original: &str, formatted: &str) -> Vec<ChangeHunk> {
+    let old = original.lines().collect::<Vec<_>>();
+    let new = formatted.lines().collect::<Vec<_>>();
+    diff_hunks(&old, &new)
+        .into_iter()
+        .map(|hunk| ChangeHunk {
+            line: hunk.line,
+            reason: classify_hunk(&hunk.removed, &hunk.added),
+        })
+        .collect()
+}
+
+pub fn unified_diff(original: &str, changed: &str, from_label: &str, to_label: &str) -> String {
+    let old = original.lines().collect::<Vec<_>>();
+    let new = changed.lines().collect::<Vec<_>>();
+    let hunks = diff_hunks(&old, &new);
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- {from_label}\n+++ {to_label}\n");
+    for hunk in hunks {
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk.line,
+            hunk.removed.len(),
+            hunk.new_line,
+            hunk.added.len()
+        ));
+        for line in hunk.removed {
+            diff.push('-');
+            diff.push_str(line);
+            diff.push('\n');
+        }
+        for line in hunk.added {
+            diff.push('+');
+            diff.push_str(line);
+            diff.push('\n');
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explain_changes_classifies_indentation_and_wrap() {
+        let original = "func a():\n  pass\n\nvar items = [\n\t1,\n\t2\n]\n";
+        let formatted = "func a():\n\tpass\n\nvar items = [1, 2]\n";
+
+        let hunks = explain_changes(original, formatted);
+        let labels = hunks
+            .iter()
+            .map(|hunk| (hunk.line, hunk.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec![(2, "indentation"), (4, "line_wrap")]);
+    }
+}
@@ -1,53 +1,337 @@
+use crate::config::apply_profile;
 use crate::formatter_manager::FormatterManager;
-use crate::targets::{get_bool, get_optional_i64, resolve_target_files};
+use crate::targets::{
+    enforce_max_files, get_bool, get_optional_bool, get_optional_globset, get_optional_i64,
+    get_optional_string, get_optional_usize, resolve_max_files, resolve_target_files,
+    resolve_working_dir,
+};
+use crate::tools::explain::{ChangeHunk, explain_changes, unified_diff};
+use crate::tools::format_cache::{
+    FORMAT_CACHE_FILE_NAME, FormatCache, binary_fingerprint, file_hash,
+};
+use crate::tools::help::formatter_supports_flag;
+use crate::tools::version::enforce_min_formatter_version;
+use crate::tools::{
+    PhaseTimer, TerminationSignal, ToolError, binary_version, call_summary, formatter_command,
+    resolve_binary, strip_ansi, termination_signal,
+};
+use globset::GlobSet;
+use regex::Regex;
 use serde_json::{Map, Value, json};
-use std::path::Path;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{self, Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
 
 const DEFAULT_MAX_FAILURES_RETURNED: usize = 20;
+const BINARY_SNIFF_BYTES: usize = 8000;
+const CONTENT_MATCH_BYTES: u64 = 1024 * 1024;
+const STATS_CHUNK_BYTES: usize = 64 * 1024;
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+const TRANSIENT_REASON_MARKERS: [&str; 6] = [
+    "permission denied",
+    "resource temporarily unavailable",
+    "being used by another process",
+    "locked",
+    "text file busy",
+    "interrupted",
+];
+const MISSING_FILE_REASON_MARKERS: [&str; 2] =
+    ["no such file or directory", "cannot find the file"];
+const NOT_FORMATTED_REASON_MARKERS: [&str; 3] =
+    ["not formatted", "would reformat", "needs formatting"];
+/// Categories meaning the file could not be read at all, reported under
+/// `errors` rather than `failures` so callers fix paths instead of code.
+const ACCESS_ERROR_CATEGORIES: [&str; 2] = ["file_not_found", "permission_denied"];
 
+#[derive(Default)]
 pub struct FormatToolResult {
     pub success: bool,
     pub processed_count: usize,
     pub failures: Vec<FormatFailure>,
+    pub include_raw_output: bool,
+    pub raw_outputs: Vec<FormatRawOutput>,
+    pub include_diffs: bool,
+    pub diffs: Vec<FormatDiff>,
+    pub detect_indentation: bool,
+    pub indentation: Vec<(String, &'static str)>,
+    pub indentation_applied: Option<Value>,
+    pub skipped: Vec<FormatSkip>,
+    pub unchanged_cached: Vec<String>,
+    pub timings: Option<Value>,
+    pub scan_stats: Option<Value>,
+    pub check_ignored: Vec<String>,
+    pub idempotency_check: bool,
+    pub non_idempotent: Vec<String>,
+    pub junit_xml: Option<String>,
+    pub explain_changes: bool,
+    pub change_explanations: Vec<(String, Vec<ChangeHunk>)>,
+    pub exit_codes: Option<Vec<(String, Option<i32>)>>,
+    pub backups: Vec<(String, String)>,
+    pub reorder_summary: Option<Vec<(String, Vec<String>)>>,
+    pub call_summary: Option<Value>,
+    pub one_line_summary: bool,
+    pub changed_count: usize,
+    pub binary_summaries: Option<Vec<String>>,
+    pub stats: Option<FormatStats>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FormatStats {
+    pub total_lines: usize,
+    pub total_bytes: u64,
+}
+
+impl FormatStats {
+    fn add_file(&mut self, path: &Path) {
+        let Ok(mut file) = fs::File::open(path) else {
+            return;
+        };
+        let mut chunk = vec![0; STATS_CHUNK_BYTES];
+        let (mut newlines, mut bytes, mut last) = (0, 0, None);
+        loop {
+            match file.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => {
+                    newlines += chunk[..read].iter().filter(|b| **b == b'\n').count();
+                    bytes += read as u64;
+                    last = Some(chunk[read - 1]);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => return,
+            }
+        }
+        let unterminated = last.is_some_and(|b| b != b'\n');
+        self.total_lines += newlines + usize::from(unterminated);
+        self.total_bytes += bytes;
+    }
+}
+
+pub struct FormatSkip {
+    pub file: String,
+    pub reason: &'static str,
 }
 
 pub struct FormatFailure {
     pub file: String,
     pub reason: String,
+    pub category: &'static str,
+    pub signal: Option<TerminationSignal>,
+}
+
+pub struct FormatDiff {
+    pub file: String,
+    pub diff: String,
+}
+
+pub struct FormatRawOutput {
+    pub file: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub struct FormatOptions {
+    pub check: bool,
+    pub stdout: bool,
+    pub use_spaces: bool,
+    pub indent_size: Option<i64>,
+    pub reorder_code: bool,
+    pub safe: bool,
+    pub include_raw_output: bool,
+    pub per_file_retries: usize,
+    pub native_diff: bool,
+    pub working_dir: Option<PathBuf>,
+    pub detect_indentation: bool,
+    pub skip_binary_files: bool,
+    pub content_matches: Option<Regex>,
+    pub format_cache_path: Option<PathBuf>,
+    pub include_stats: bool,
+    pub check_ignore: Option<GlobSet>,
+    pub scan_dir: Option<PathBuf>,
+    pub idempotency_check: bool,
+    pub explain_changes: bool,
+    pub include_exit_codes: bool,
+    pub low_priority: bool,
+    pub backup: bool,
+    pub backup_dir: Option<PathBuf>,
+    pub reorder_summary: bool,
+    pub use_binary_summary: bool,
+    pub count_changes: bool,
+    pub include_indentation_applied: bool,
+}
+
+/// Matches `from_arguments` with no arguments: binary files are skipped
+/// unless a caller opts out.
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            check: false,
+            stdout: false,
+            use_spaces: false,
+            indent_size: None,
+            reorder_code: false,
+            safe: false,
+            include_raw_output: false,
+            per_file_retries: 0,
+            native_diff: false,
+            working_dir: None,
+            detect_indentation: false,
+            skip_binary_files: true,
+            content_matches: None,
+            format_cache_path: None,
+            include_stats: false,
+            check_ignore: None,
+            scan_dir: None,
+            idempotency_check: false,
+            explain_changes: false,
+            include_exit_codes: false,
+            low_priority: false,
+            backup: false,
+            backup_dir: None,
+            reorder_summary: false,
+            use_binary_summary: false,
+            count_changes: false,
+            include_indentation_applied: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    pub fn from_arguments(arguments: &Map<String, Value>) -> Result<Self, String> {
+        let indent_size = get_optional_i64(arguments, "indent_size")?;
+        if let Some(size) = indent_size
+            && size < 1
+        {
+            return Err("`indent_size` must be at least 1".to_owned());
+        }
+        let backup = get_bool(arguments, "backup")?;
+        let backup_dir = get_optional_string(arguments, "backup_dir")?.map(PathBuf::from);
+        if backup_dir.is_some() && !backup {
+            return Err("`backup_dir` can only be used with `backup`".to_owned());
+        }
+        let reorder_code = get_bool(arguments, "reorder_code")?;
+        let reorder_summary = get_bool(arguments, "reorder_summary")?;
+        let content_matches = get_optional_string(arguments, "content_matches")?
+            .map(|pattern| {
+                Regex::new(&pattern).map_err(|e| format!("Invalid `content_matches` regex: {e}"))
+            })
+            .transpose()?;
+        if reorder_summary && !reorder_code {
+            return Err("`reorder_summary` can only be used with `reorder_code`".to_owned());
+        }
+
+        Ok(Self {
+            check: get_bool(arguments, "check")?,
+            stdout: get_bool(arguments, "stdout")?,
+            use_spaces: get_bool(arguments, "use_spaces")?,
+            indent_size,
+            reorder_code,
+            safe: get_bool(arguments, "safe")?,
+            include_raw_output: get_bool(arguments, "include_raw_output")?,
+            per_file_retries: get_optional_usize(arguments, "per_file_retries")?.unwrap_or(0),
+            native_diff: false,
+            working_dir: resolve_working_dir(arguments)?,
+            detect_indentation: get_bool(arguments, "detect_indentation")?,
+            include_indentation_applied: get_bool(arguments, "include_indentation_applied")?,
+            skip_binary_files: get_optional_bool(arguments, "skip_binary_files")?.unwrap_or(true),
+            content_matches,
+            format_cache_path: None,
+            include_stats: get_bool(arguments, "include_stats")?,
+            check_ignore: get_optional_globset(arguments, "check_ignore")?,
+            scan_dir: get_optional_string(arguments, "dir")?.map(PathBuf::from),
+            idempotency_check: get_bool(arguments, "idempotency_check")?,
+            explain_changes: get_bool(arguments, "explain_changes")?,
+            include_exit_codes: get_bool(arguments, "include_exit_codes")?,
+            low_priority: get_bool(arguments, "low_priority")?,
+            backup,
+            backup_dir,
+            reorder_summary,
+            use_binary_summary: get_bool(arguments, "use_binary_summary")?,
+            count_changes: get_bool(arguments, "one_line_summary")?,
+        })
+    }
+
+    fn output_key(&self) -> String {
+        format!(
+            "use_spaces={} indent_size={:?} reorder_code={} safe={}",
+            self.use_spaces, self.indent_size, self.reorder_code, self.safe
+        )
+    }
+}
+
+/// Only the `--check` verdict itself is forgiven by `check_ignore`: a
+/// silent exit 1 or a "not formatted" message. Crashes, parse errors and
+/// unreadable files still fail the call.
+fn is_not_formatted(attempt: &FileAttempt) -> bool {
+    let Some(reason) = &attempt.failure_reason else {
+        return false;
+    };
+    if attempt.signal.is_some() || failure_category(reason, false) != "format_error" {
+        return false;
+    }
+    let lower = reason.to_ascii_lowercase();
+    NOT_FORMATTED_REASON_MARKERS
+        .iter()
+        .any(|marker| lower.contains(marker))
+        || (attempt.exit_code == Some(1) && attempt.stderr.trim().is_empty())
+}
+
+/// `check_ignore` patterns are written like `include`/`exclude`, so a file
+/// found by scanning `dir` is matched by its path below that directory.
+fn is_check_ignored(options: &FormatOptions, file: &str) -> bool {
+    let Some(globs) = &options.check_ignore else {
+        return false;
+    };
+    let path = Path::new(file);
+    if globs.is_match(path) {
+        return true;
+    }
+    let Some(dir) = &options.scan_dir else {
+        return false;
+    };
+    [
+        Some(dir.clone()),
+        path::absolute(dir).ok(),
+        fs::canonicalize(dir).ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|base| {
+        path.strip_prefix(&base)
+            .is_ok_and(|relative| globs.is_match(relative))
+    })
 }
 
-#[allow(clippy::too_many_arguments)]
-fn build_format_command(
-    binary_path: &Path,
-    check: bool,
-    stdout: bool,
-    use_spaces: bool,
-    indent_size: Option<i64>,
-    reorder_code: bool,
-    safe: bool,
-    files: &[String],
-) -> Command {
-    let mut command = Command::new(binary_path);
-
-    if check {
+fn build_format_command(binary_path: &Path, options: &FormatOptions, files: &[String]) -> Command {
+    let mut command = formatter_command(binary_path, options.low_priority);
+    command.env("NO_COLOR", "1");
+    if let Some(dir) = &options.working_dir {
+        command.current_dir(dir);
+    }
+
+    if options.check {
         command.arg("--check");
     }
-    if stdout {
+    if options.stdout {
         command.arg("--stdout");
     }
-    if use_spaces {
+    if options.use_spaces {
         command.arg("--use-spaces");
     }
-    if let Some(size) = indent_size {
+    if let Some(size) = options.indent_size {
         command.arg("--indent-size").arg(size.to_string());
     }
-    if reorder_code {
+    if options.reorder_code {
         command.arg("--reorder-code");
     }
-    if safe {
+    if options.safe {
         command.arg("--safe");
     }
+    if options.native_diff {
+        command.arg("--diff");
+    }
     command.args(files);
     command
 }
@@ -93,15 +377,521 @@ fn extract_format_failure_reason(stdout: &str, stderr: &str) -> String {
     normalize_reason(stdout)
 }
 
+fn classify_indentation(text: &str) -> &'static str {
+    let mut tabs = false;
+    let mut spaces = false;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match line.chars().next() {
+            Some('\t') => tabs = true,
+            Some(' ') => spaces = true,
+            _ => {}
+        }
+    }
+    match (tabs, spaces) {
+        (true, true) => "mixed",
+        (true, false) => "tabs",
+        (false, true) => "spaces",
+        (false, false) => "unknown",
+    }
+}
+
+/// Samples the indentation of formatted text: `tabs`, `spaces` with the
+/// smallest indent as `width`, or `mixed`. `None` when nothing is indented.
+fn sample_indentation(text: &str) -> Option<Value> {
+    match classify_indentation(text) {
+        "tabs" => Some(json!({"style": "tabs"})),
+        "spaces" => {
+            let width = text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.len() - line.trim_start_matches(' ').len())
+                .filter(|width| *width > 0)
+                .min();
+            Some(json!({"style": "spaces", "width": width}))
+        }
+        "mixed" => Some(json!({"style": "mixed"})),
+        _ => None,
+    }
+}
+
+/// Combines the per-file samples; files that disagree yield `mixed`.
+fn merge_indentation_samples(samples: &[Value]) -> Value {
+    match samples.split_first() {
+        None => Value::Null,
+        Some((first, rest)) if rest.iter().all(|sample| sample == first) => first.clone(),
+        Some(_) => json!({"style": "mixed"}),
+    }
+}
+
+fn resolve_file_path(options: &FormatOptions, file: &str) -> PathBuf {
+    match &options.working_dir {
+        Some(dir) => dir.join(file),
+        None => PathBuf::from(file),
+    }
+}
+
+fn backup_path(file_path: &Path, backup_dir: Option<&Path>) -> PathBuf {
+    let Some(dir) = backup_dir else {
+        let mut name = file_path.as_os_str().to_os_string();
+        name.push(".bak");
+        return PathBuf::from(name);
+    };
+    // Mirror the file's canonical path under the backup dir so same-named
+    // files in different directories, and `..` or relative spellings of
+    // different files, never share a backup. A Windows drive or UNC prefix
+    // becomes the first directory.
+    let absolute = fs::canonicalize(file_path)
+        .or_else(|_| path::absolute(file_path))
+        .unwrap_or_else(|_| file_path.to_path_buf());
+    let mut mirrored = dir.to_path_buf();
+    for component in absolute.components() {
+        match component {
+            path::Component::Prefix(prefix) => mirrored.push(
+                prefix
+                    .as_os_str()
+                    .to_string_lossy()
+                    .replace([':', '\\', '/', '?'], "_")
+                    .trim_matches('_'),
+            ),
+            path::Component::Normal(name) => mirrored.push(name),
+            path::Component::ParentDir => mirrored.push("__parent__"),
+            path::Component::RootDir | path::Component::CurDir => {}
+        }
+    }
+    mirrored
+}
+
+/// Copies `file_path` to a staging file next to its backup location before
+/// an in-place run. [`finish_backup`] keeps it only if the run changed the
+/// file, so an older backup is never replaced by an identical copy.
+fn stage_backup(options: &FormatOptions, file_path: &Path) -> Result<(PathBuf, PathBuf), String> {
+    let destination = backup_path(file_path, options.backup_dir.as_deref());
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let mut staged = destination.as_os_str().to_os_string();
+    staged.push(".partial");
+    let staged = PathBuf::from(staged);
+    fs::copy(file_path, &staged)
+        .map_err(|e| format!("Failed to write backup {}: {}", destination.display(), e))?;
+    Ok((staged, destination))
+}
+
+/// Moves a staged backup into place when the file changed, otherwise
+/// discards it. Returns the backup location when one was kept.
+fn finish_backup(
+    staged: &Path,
+    destination: &Path,
+    changed: bool,
+) -> Result<Option<PathBuf>, String> {
+    if !changed {
+        let _ = fs::remove_file(staged);
+        return Ok(None);
+    }
+    fs::rename(staged, destination)
+        .map_err(|e| format!("Failed to write backup {}: {}", destination.display(), e))?;
+    Ok(Some(destination.to_path_buf()))
+}
+
+/// Picks the lines where the formatter reports moving or reordering
+/// declarations out of its stdout and stderr.
+fn parse_reorder_moves(stdout: &str, stderr: &str) -> Vec<String> {
+    strip_ansi(stdout)
+        .lines()
+        .chain(strip_ansi(stderr).lines())
+        .map(str::trim)
+        .filter(|line| {
+            let lower = line.to_ascii_lowercase();
+            lower.contains("moved") || lower.contains("reordered")
+        })
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Picks the formatter's own summary line (e.g. "Formatted 1 file, 1 changed")
+/// from a run, skipping "..." progress lines and errors. `stdout` is ignored
+/// when it carries formatted code.
+fn parse_binary_summary(stdout: &str, stderr: &str, stdout_is_code: bool) -> Option<String> {
+    let stdout = if stdout_is_code {
+        String::new()
+    } else {
+        strip_ansi(stdout)
+    };
+    strip_ansi(stderr)
+        .lines()
+        .chain(stdout.lines())
+        .map(str::trim)
+        .rev()
+        .find(|line| {
+            let lower = line.to_ascii_lowercase();
+            lower.contains("file")
+                && line.chars().any(|c| c.is_ascii_digit())
+                && !line.ends_with("...")
+                && !lower.contains("error")
+                && !lower.contains("warning")
+        })
+        .map(str::to_owned)
+}
+
+/// Splits a summary line into the text around its numbers and the numbers
+/// themselves, with a "files" right after a number folded to "file" so that
+/// "1 file" and "2 files" share one shape.
+fn summary_shape(line: &str) -> Option<(Vec<String>, Vec<u64>)> {
+    let mut texts = vec![String::new()];
+    let mut counts = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut digits = String::from(c);
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            counts.push(digits.parse().ok()?);
+            texts.push(String::new());
+        } else {
+            texts.last_mut()?.push(c);
+        }
+    }
+    for text in texts.iter_mut().skip(1) {
+        if let Some(rest) = text.strip_prefix(" files")
+            && !rest.starts_with(char::is_alphanumeric)
+        {
+            *text = format!(" file{rest}");
+        }
+    }
+    Some((texts, counts))
+}
+
+/// Folds the per-file summary lines into one by adding up their counts, so a
+/// run over several files reads "Formatted 3 files, 2 changed". Lines that do
+/// not share one shape fall back to the last line.
+fn merge_binary_summaries(lines: &[String]) -> Option<String> {
+    let last = lines.last()?;
+    let Some((texts, mut totals)) = summary_shape(&lines[0]) else {
+        return Some(last.clone());
+    };
+    for line in &lines[1..] {
+        match summary_shape(line) {
+            Some((line_texts, counts)) if line_texts == texts => {
+                for (total, count) in totals.iter_mut().zip(counts) {
+                    *total = total.saturating_add(count);
+                }
+            }
+            _ => return Some(last.clone()),
+        }
+    }
+    let mut merged = texts[0].clone();
+    for (total, text) in totals.iter().zip(&texts[1..]) {
+        merged.push_str(&total.to_string());
+        match text.strip_prefix(" file") {
+            Some(rest) if *total != 1 && !rest.starts_with(char::is_alphanumeric) => {
+                merged.push_str(" files");
+                merged.push_str(rest);
+            }
+            _ => merged.push_str(text),
+        }
+    }
+    Some(merged)
+}
+
+fn detect_file_indentation(options: &FormatOptions, file: &str) -> &'static str {
+    fs::read_to_string(resolve_file_path(options, file))
+        .map(|text| classify_indentation(&text))
+        .unwrap_or("unknown")
+}
+
+fn has_binary_content(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::with_capacity(BINARY_SNIFF_BYTES);
+    if file
+        .take(BINARY_SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .is_err()
+    {
+        return false;
+    }
+    head.contains(&0)
+}
+
+/// Matches `pattern` against the first `CONTENT_MATCH_BYTES` of the file so
+/// a stray large file cannot stall target selection.
+fn content_matches(path: &Path, pattern: &Regex) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::new();
+    if file
+        .take(CONTENT_MATCH_BYTES)
+        .read_to_end(&mut head)
+        .is_err()
+    {
+        return false;
+    }
+    pattern.is_match(&String::from_utf8_lossy(&head))
+}
+
+/// Why an in-place run could not write `path`, probed by opening it for
+/// writing (without truncating) so ACLs, read-only mounts and file flags
+/// count too, not just mode bits. Missing files are left to the formatter.
+fn write_denied_reason(path: &Path) -> Option<String> {
+    match fs::OpenOptions::new().write(true).open(path) {
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+            ) =>
+        {
+            Some(format!("File is not writable (read-only): {e}"))
+        }
+        _ => None,
+    }
+}
+
+fn is_transient_failure(reason: &str) -> bool {
+    let reason = reason.to_ascii_lowercase();
+    TRANSIENT_REASON_MARKERS
+        .iter()
+        .any(|marker| reason.contains(marker))
+}
+
+fn failure_category(reason: &str, crashed: bool) -> &'static str {
+    let lower = reason.to_ascii_lowercase();
+    if crashed {
+        "crash"
+    } else if MISSING_FILE_REASON_MARKERS
+        .iter()
+        .any(|marker| lower.contains(marker))
+    {
+        "file_not_found"
+    } else if lower.contains("permission denied") {
+        "permission_denied"
+    } else if is_transient_failure(reason) {
+        "transient_io"
+    } else {
+        "format_error"
+    }
+}
+
+fn is_access_error(failure: &FormatFailure) -> bool {
+    ACCESS_ERROR_CATEGORIES.contains(&failure.category)
+}
+
+/// `(failed, errors)`: formatter rejections and unreadable files.
+fn failure_counts(result: &FormatToolResult) -> (usize, usize) {
+    let errors = result
+        .failures
+        .iter()
+        .filter(|f| is_access_error(f))
+        .count();
+    (result.failures.len() - errors, errors)
+}
+
 pub fn render_format_summary(result: &FormatToolResult) -> String {
+    let (failed_count, error_count) = failure_counts(result);
+    if result.one_line_summary {
+        let mut line = format!(
+            "format {} files={} changed={} failed={failed_count}",
+            if result.success { "ok" } else { "failed" },
+            result.processed_count,
+            result.changed_count,
+        );
+        if error_count > 0 {
+            line.push_str(&format!(" errors={error_count}"));
+        }
+        return line;
+    }
     if result.success {
-        "Format ok.".to_owned()
+        result
+            .binary_summaries
+            .as_deref()
+            .and_then(merge_binary_summaries)
+            .unwrap_or_else(|| "Format ok.".to_owned())
     } else {
-        format!("Format failed. failed_count={}.", result.failures.len())
+        if error_count > 0 {
+            format!("Format failed. failed_count={failed_count}, error_count={error_count}.")
+        } else {
+            format!("Format failed. failed_count={failed_count}.")
+        }
     }
 }
 
+fn raw_by_file(raw_outputs: &[FormatRawOutput]) -> Value {
+    let map = raw_outputs
+        .iter()
+        .map(|raw| {
+            (
+                raw.file.clone(),
+                json!({
+                    "stdout": raw.stdout,
+                    "stderr": raw.stderr
+                }),
+            )
+        })
+        .collect::<Map<String, Value>>();
+    Value::Object(map)
+}
+
 pub fn format_structured_content(result: &FormatToolResult) -> Value {
+    let mut structured = format_outcome_content(result);
+    if result.include_raw_output
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("raw_by_file".to_owned(), raw_by_file(&result.raw_outputs));
+    }
+    if result.include_diffs
+        && let Some(map) = structured.as_object_mut()
+    {
+        let diffs = result
+            .diffs
+            .iter()
+            .map(|d| json!({"file": d.file, "diff": d.diff}))
+            .collect::<Vec<_>>();
+        map.insert("diffs".to_owned(), Value::Array(diffs));
+    }
+    if !result.skipped.is_empty()
+        && let Some(map) = structured.as_object_mut()
+    {
+        let skipped = result
+            .skipped
+            .iter()
+            .map(|skip| json!({"file": skip.file, "reason": skip.reason}))
+            .collect::<Vec<_>>();
+        map.insert("skipped".to_owned(), Value::Array(skipped));
+    }
+    if let Some(stats) = &result.stats
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("total_lines".to_owned(), json!(stats.total_lines));
+        map.insert("total_bytes".to_owned(), json!(stats.total_bytes));
+    }
+    if let Some(timings) = &result.timings
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("timings".to_owned(), timings.clone());
+    }
+    if !result.check_ignored.is_empty()
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("check_ignored".to_owned(), json!(result.check_ignored));
+    }
+    if result.idempotency_check
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("non_idempotent".to_owned(), json!(result.non_idempotent));
+    }
+    if result.explain_changes
+        && let Some(map) = structured.as_object_mut()
+    {
+        let explanations = result
+            .change_explanations
+            .iter()
+            .map(|(file, hunks)| {
+                let hunks = hunks
+                    .iter()
+                    .map(|hunk| json!({"line": hunk.line, "reason": hunk.reason}))
+                    .collect::<Vec<_>>();
+                json!({"file": file, "hunks": hunks})
+            })
+            .collect::<Vec<_>>();
+        map.insert("change_explanations".to_owned(), Value::Array(explanations));
+    }
+    if let Some(exit_codes) = &result.exit_codes
+        && let Some(map) = structured.as_object_mut()
+    {
+        let exit_codes = exit_codes
+            .iter()
+            .map(|(file, code)| (file.clone(), json!(code)))
+            .collect::<Map<_, _>>();
+        map.insert("exit_codes".to_owned(), Value::Object(exit_codes));
+    }
+    if let Some(summary) = &result.reorder_summary
+        && let Some(map) = structured.as_object_mut()
+    {
+        let summary = summary
+            .iter()
+            .map(|(file, moves)| json!({"file": file, "moves": moves}))
+            .collect::<Vec<_>>();
+        map.insert("reorder_summary".to_owned(), Value::Array(summary));
+    }
+    if !result.backups.is_empty()
+        && let Some(map) = structured.as_object_mut()
+    {
+        let backups = result
+            .backups
+            .iter()
+            .map(|(file, backup)| json!({"file": file, "backup": backup}))
+            .collect::<Vec<_>>();
+        map.insert("backups".to_owned(), Value::Array(backups));
+    }
+    if let Some(call_summary) = &result.call_summary
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("call_summary".to_owned(), call_summary.clone());
+    }
+    if let Some(junit_xml) = &result.junit_xml
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("junit_xml".to_owned(), json!(junit_xml));
+    }
+    if let Some(scan_stats) = &result.scan_stats
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("scan_stats".to_owned(), scan_stats.clone());
+    }
+    if !result.unchanged_cached.is_empty()
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert(
+            "unchanged_cached".to_owned(),
+            json!(result.unchanged_cached),
+        );
+    }
+    if let Some(applied) = &result.indentation_applied
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("indentation_applied".to_owned(), applied.clone());
+    }
+    if result.detect_indentation
+        && let Some(map) = structured.as_object_mut()
+    {
+        let indentation = result
+            .indentation
+            .iter()
+            .map(|(file, style)| (file.clone(), Value::String((*style).to_owned())))
+            .collect::<Map<String, Value>>();
+        map.insert("indentation".to_owned(), Value::Object(indentation));
+    }
+    structured
+}
+
+fn distinct_reasons(failures: &[&FormatFailure]) -> Vec<Value> {
+    let mut reasons: Vec<(&str, usize, &str)> = Vec::new();
+    for failure in failures {
+        match reasons
+            .iter_mut()
+            .find(|(reason, _, _)| *reason == failure.reason)
+        {
+            Some((_, count, _)) => *count += 1,
+            None => reasons.push((&failure.reason, 1, &failure.file)),
+        }
+    }
+    reasons.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    reasons
+        .into_iter()
+        .map(|(reason, count, example_file)| {
+            json!({
+                "reason": reason,
+                "count": count,
+                "example_file": example_file
+            })
+        })
+        .collect()
+}
+
+fn format_outcome_content(result: &FormatToolResult) -> Value {
     if result.success {
         return json!({
             "ok": true,
@@ -109,99 +899,543 @@ pub fn format_structured_content(result: &FormatToolResult) -> Value {
         });
     }
 
-    let failures = result
-        .failures
-        .iter()
-        .take(DEFAULT_MAX_FAILURES_RETURNED)
-        .map(|f| {
-            json!({
-                "file": f.file,
-                "reason": f.reason
-            })
-        })
-        .collect::<Vec<_>>();
-    let failures_truncated = result.failures.len() > DEFAULT_MAX_FAILURES_RETURNED;
+    let mut sorted_failures = result.failures.iter().collect::<Vec<_>>();
+    sorted_failures.sort_by(|a, b| a.file.cmp(&b.file));
+    let distinct_reasons = distinct_reasons(&sorted_failures);
+    let (errors, failures): (Vec<_>, Vec<_>) = sorted_failures
+        .into_iter()
+        .partition(|f| is_access_error(f));
+    let failures_truncated = failures.len() > DEFAULT_MAX_FAILURES_RETURNED
+        || errors.len() > DEFAULT_MAX_FAILURES_RETURNED;
     json!({
         "ok": false,
         "processed_count": result.processed_count,
-        "failed_count": result.failures.len(),
+        "failed_count": failures.len(),
+        "error_count": errors.len(),
         "failures_truncated": failures_truncated,
-        "failures": failures
+        "failures": failure_entries(&failures),
+        "errors": failure_entries(&errors),
+        "distinct_reasons": distinct_reasons
     })
 }
 
-pub fn call_gdscript_format(
-    manager: &FormatterManager,
-    arguments: &Map<String, Value>,
-) -> Result<FormatToolResult, String> {
-    let files = resolve_target_files(arguments, true)?;
-    let check = get_bool(arguments, "check")?;
-    let stdout = get_bool(arguments, "stdout")?;
-    let use_spaces = get_bool(arguments, "use_spaces")?;
-    let reorder_code = get_bool(arguments, "reorder_code")?;
-    let safe = get_bool(arguments, "safe")?;
-    let indent_size = get_optional_i64(arguments, "indent_size")?;
-
-    if let Some(size) = indent_size
-        && size < 1
-    {
-        return Err("`indent_size` must be at least 1".to_owned());
+fn failure_entries(failures: &[&FormatFailure]) -> Vec<Value> {
+    failures
+        .iter()
+        .take(DEFAULT_MAX_FAILURES_RETURNED)
+        .map(|f| {
+            let mut failure = json!({
+                "file": f.file,
+                "reason": f.reason,
+                "category": f.category
+            });
+            if let Some(signal) = &f.signal
+                && let Some(map) = failure.as_object_mut()
+            {
+                map.insert("signal".to_owned(), json!(signal.name));
+                map.insert("signal_number".to_owned(), json!(signal.number));
+            }
+            failure
+        })
+        .collect()
+}
+
+pub struct FileAttempt {
+    pub failure_reason: Option<String>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<TerminationSignal>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub fn run_format_file(binary: &Path, options: &FormatOptions, file: &str) -> FileAttempt {
+    let single_file = vec![file.to_owned()];
+    match build_format_command(binary, options, &single_file).output() {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            let signal = termination_signal(&output.status);
+            let failure_reason = match &signal {
+                Some(signal) => Some(format!("Formatter terminated by signal {}", signal.name)),
+                None => (!output.status.success()).then(|| {
+                    extract_format_failure_reason(&strip_ansi(&stdout), &strip_ansi(&stderr))
+                }),
+            };
+            FileAttempt {
+                failure_reason,
+                exit_code: output.status.code(),
+                signal,
+                stdout,
+                stderr,
+            }
+        }
+        Err(err) => FileAttempt {
+            failure_reason: Some(normalize_reason(&format!(
+                "Failed to execute formatter: {err}"
+            ))),
+            exit_code: None,
+            signal: None,
+            stdout: String::new(),
+            stderr: String::new(),
+        },
     }
+}
 
-    let binary = manager.ensure_binary()?;
-    let mut failures = Vec::new();
+pub fn format_copy_passes(
+    binary: &Path,
+    options: &FormatOptions,
+    file_path: &Path,
+    pass_count: usize,
+) -> Result<(Vec<u8>, Vec<Vec<u8>>), String> {
+    let content = fs::read(file_path).map_err(|e| format!("Failed to read file: {e}"))?;
+    let temp = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {e}"))?;
+    let file_name = file_path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_else(|| "file.gd".into());
+    let temp_file = temp.path().join(file_name);
+    fs::write(&temp_file, &content).map_err(|e| format!("Failed to write temp file: {e}"))?;
 
-    for file in &files {
-        let single_file = vec![file.clone()];
-        let output = build_format_command(
-            binary.as_path(),
-            check,
-            stdout,
-            use_spaces,
-            indent_size,
-            reorder_code,
-            safe,
-            &single_file,
+    let pass_options = FormatOptions {
+        use_spaces: options.use_spaces,
+        indent_size: options.indent_size,
+        reorder_code: options.reorder_code,
+        safe: options.safe,
+        ..Default::default()
+    };
+    let mut passes = Vec::new();
+    for _ in 0..pass_count {
+        let attempt = run_format_file(binary, &pass_options, &temp_file.to_string_lossy());
+        if let Some(reason) = attempt.failure_reason {
+            return Err(reason);
+        }
+        passes.push(fs::read(&temp_file).map_err(|e| format!("Failed to read temp file: {e}"))?);
+    }
+    Ok((content, passes))
+}
+
+fn is_idempotent(binary: &Path, options: &FormatOptions, file_path: &Path) -> Result<bool, String> {
+    let (_, passes) = format_copy_passes(binary, options, file_path, 2)?;
+    Ok(passes[0] == passes[1])
+}
+
+fn explain_file_changes(
+    binary: &Path,
+    options: &FormatOptions,
+    file_path: &Path,
+) -> Result<Vec<ChangeHunk>, String> {
+    let (original, passes) = format_copy_passes(binary, options, file_path, 1)?;
+    Ok(explain_changes(
+        &String::from_utf8_lossy(&original),
+        &String::from_utf8_lossy(&passes[0]),
+    ))
+}
+
+fn client_side_diff(binary: &Path, options: &FormatOptions, file_path: &Path) -> Option<String> {
+    let (original, mut passes) = format_copy_passes(binary, options, file_path, 1).ok()?;
+    let formatted = passes.remove(0);
+    (formatted != original).then(|| {
+        unified_diff(
+            &String::from_utf8_lossy(&original),
+            &String::from_utf8_lossy(&formatted),
+            "original",
+            "formatted",
         )
-        .output();
+    })
+}
+
+pub fn run_format(binary: &Path, files: &[String], options: &FormatOptions) -> FormatToolResult {
+    let mut failures = Vec::new();
+    let mut raw_outputs = Vec::new();
+    let mut diffs = Vec::new();
+    let mut indentation = Vec::new();
+    let mut applied_samples = Vec::new();
+    let mut skipped = Vec::new();
+    let mut unchanged_cached = Vec::new();
+    let mut check_ignored = Vec::new();
+    let mut non_idempotent = Vec::new();
+    let mut change_explanations = Vec::new();
+    let mut exit_codes = options.include_exit_codes.then(Vec::new);
+    let mut backups = Vec::new();
+    let mut reorder_summary = options.reorder_summary.then(Vec::new);
+    let mut binary_summaries = options.use_binary_summary.then(Vec::new);
+    let mut stats = options.include_stats.then(FormatStats::default);
+    let writes_in_place = !options.check && !options.stdout;
+    let sample_applied = options.include_indentation_applied && writes_in_place;
+    let track_changes = writes_in_place && (sample_applied || options.count_changes);
+    let mut changed_count = 0;
+    let mut format_cache = options.format_cache_path.as_deref().map(FormatCache::load);
+    let fingerprint = binary_fingerprint(binary, &options.output_key());
 
-        match output {
-            Ok(output) => {
-                let file_stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let file_stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                if !output.status.success() {
+    for file in files {
+        let file_path = resolve_file_path(options, file);
+        let cache_key = path::absolute(&file_path)
+            .unwrap_or_else(|_| file_path.clone())
+            .to_string_lossy()
+            .to_string();
+        if options.skip_binary_files && has_binary_content(&file_path) {
+            skipped.push(FormatSkip {
+                file: file.clone(),
+                reason: "binary_content",
+            });
+            continue;
+        }
+        if let Some(pattern) = &options.content_matches
+            && !content_matches(&file_path, pattern)
+        {
+            skipped.push(FormatSkip {
+                file: file.clone(),
+                reason: "content_mismatch",
+            });
+            continue;
+        }
+        if writes_in_place && let Some(reason) = write_denied_reason(&file_path) {
+            failures.push(FormatFailure {
+                file: file.clone(),
+                reason,
+                category: "permission_denied",
+                signal: None,
+            });
+            continue;
+        }
+        if let Some(cache) = &format_cache
+            && cache.is_unchanged(&cache_key, &file_path, &fingerprint)
+        {
+            unchanged_cached.push(file.clone());
+            continue;
+        }
+        if let Some(stats) = &mut stats {
+            stats.add_file(&file_path);
+        }
+        if options.detect_indentation {
+            indentation.push((file.clone(), detect_file_indentation(options, file)));
+        }
+        if options.idempotency_check && is_idempotent(binary, options, &file_path) == Ok(false) {
+            non_idempotent.push(file.clone());
+        }
+        if options.explain_changes
+            && let Ok(hunks) = explain_file_changes(binary, options, &file_path)
+            && !hunks.is_empty()
+        {
+            change_explanations.push((file.clone(), hunks));
+        }
+        let staged_backup = if writes_in_place && options.backup {
+            match stage_backup(options, &file_path) {
+                Ok(staged) => Some(staged),
+                Err(reason) => {
                     failures.push(FormatFailure {
                         file: file.clone(),
-                        reason: extract_format_failure_reason(&file_stdout, &file_stderr),
+                        reason,
+                        category: "backup_failed",
+                        signal: None,
                     });
+                    continue;
                 }
             }
-            Err(err) => {
-                failures.push(FormatFailure {
-                    file: file.clone(),
-                    reason: normalize_reason(&format!("Failed to execute formatter: {err}")),
-                });
+        } else {
+            None
+        };
+        let hash_before = (track_changes || staged_backup.is_some())
+            .then(|| file_hash(&file_path).ok())
+            .flatten();
+        let mut attempt = run_format_file(binary, options, file);
+        for retry in 1..=options.per_file_retries {
+            match &attempt.failure_reason {
+                Some(reason) if is_transient_failure(reason) => {
+                    thread::sleep(RETRY_BACKOFF * retry as u32);
+                    attempt = run_format_file(binary, options, file);
+                }
+                _ => break,
             }
         }
-    }
-
-    let success = failures.is_empty();
-    let processed_count = files.len();
-    Ok(FormatToolResult {
-        success,
-        processed_count,
-        failures,
-    })
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn extract_format_failure_reason_from_stderr() {
-        let stderr = "Formatting 1 file...Error: \"Failed to format file /tmp/bad.gd: Topiary formatting failed\"";
-        let reason = extract_format_failure_reason("", stderr);
+        if let Some(exit_codes) = &mut exit_codes {
+            exit_codes.push((file.clone(), attempt.exit_code));
+        }
+        let rewritten = hash_before.is_some() && file_hash(&file_path).ok() != hash_before;
+        if let Some((staged, destination)) = &staged_backup {
+            match finish_backup(staged, destination, rewritten) {
+                Ok(Some(destination)) => {
+                    backups.push((file.clone(), destination.to_string_lossy().to_string()));
+                }
+                Ok(None) => {}
+                Err(reason) => failures.push(FormatFailure {
+                    file: file.clone(),
+                    reason,
+                    category: "backup_failed",
+                    signal: None,
+                }),
+            }
+        }
+        let changed = attempt.failure_reason.is_none() && rewritten;
+        if changed {
+            changed_count += 1;
+        }
+        if changed
+            && sample_applied
+            && let Some(sample) = fs::read_to_string(&file_path)
+                .ok()
+                .and_then(|text| sample_indentation(&text))
+        {
+            applied_samples.push(sample);
+        }
+        if let Some(summaries) = &mut binary_summaries
+            && let Some(line) =
+                parse_binary_summary(&attempt.stdout, &attempt.stderr, options.stdout)
+        {
+            summaries.push(line);
+        }
+        if let Some(summary) = &mut reorder_summary {
+            let moves = parse_reorder_moves(&attempt.stdout, &attempt.stderr);
+            if !moves.is_empty() {
+                summary.push((file.clone(), moves));
+            }
+        }
+        if let Some(cache) = &mut format_cache {
+            if attempt.failure_reason.is_none() && !options.stdout {
+                cache.record(&cache_key, &file_path, &fingerprint);
+            } else {
+                cache.invalidate(&cache_key);
+            }
+        }
+        let needs_client_diff = options.check
+            && !options.stdout
+            && !options.native_diff
+            && attempt.failure_reason.is_some()
+            && attempt.signal.is_none();
+        if options.check && is_not_formatted(&attempt) && is_check_ignored(options, file) {
+            check_ignored.push(file.clone());
+        } else if let Some(reason) = attempt.failure_reason {
+            let category = failure_category(&reason, attempt.signal.is_some());
+            failures.push(FormatFailure {
+                file: file.clone(),
+                reason,
+                category,
+                signal: attempt.signal,
+            });
+        }
+        if options.native_diff && !attempt.stdout.trim().is_empty() {
+            diffs.push(FormatDiff {
+                file: file.clone(),
+                diff: attempt.stdout.clone(),
+            });
+        } else if needs_client_diff
+            && let Some(diff) = client_side_diff(binary, options, &file_path)
+        {
+            diffs.push(FormatDiff {
+                file: file.clone(),
+                diff,
+            });
+        }
+        if options.include_raw_output {
+            raw_outputs.push(FormatRawOutput {
+                file: file.clone(),
+                stdout: attempt.stdout,
+                stderr: attempt.stderr,
+            });
+        }
+    }
+
+    if let Some(cache) = &format_cache
+        && let Err(err) = cache.save()
+    {
+        eprintln!("Warning: {err}");
+    }
+
+    FormatToolResult {
+        success: failures.is_empty(),
+        processed_count: files.len() - skipped.len() - unchanged_cached.len(),
+        failures,
+        include_raw_output: options.include_raw_output,
+        raw_outputs,
+        include_diffs: options.check && !options.stdout,
+        diffs,
+        detect_indentation: options.detect_indentation,
+        indentation,
+        indentation_applied: options
+            .include_indentation_applied
+            .then(|| merge_indentation_samples(&applied_samples)),
+        skipped,
+        unchanged_cached,
+        timings: None,
+        scan_stats: None,
+        check_ignored,
+        idempotency_check: options.idempotency_check,
+        non_idempotent,
+        junit_xml: None,
+        explain_changes: options.explain_changes,
+        change_explanations,
+        exit_codes,
+        backups,
+        reorder_summary,
+        call_summary: None,
+        one_line_summary: false,
+        changed_count,
+        binary_summaries,
+        stats,
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn render_junit_xml(files: &[String], result: &FormatToolResult) -> String {
+    let mut testcases = String::new();
+    for file in files {
+        let name = escape_xml(file);
+        testcases.push_str(&format!(
+            "  <testcase classname=\"gdscript_format\" name=\"{name}\""
+        ));
+        if let Some(failure) = result.failures.iter().find(|f| &f.file == file) {
+            testcases.push_str(&format!(
+                ">\n    <failure type=\"{}\" message=\"{}\"/>\n  </testcase>\n",
+                failure.category,
+                escape_xml(&failure.reason)
+            ));
+        } else if let Some(skip) = result.skipped.iter().find(|s| &s.file == file) {
+            testcases.push_str(&format!(
+                ">\n    <skipped message=\"{}\"/>\n  </testcase>\n",
+                skip.reason
+            ));
+        } else {
+            testcases.push_str("/>\n");
+        }
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuite name=\"gdscript_format\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n{testcases}</testsuite>\n",
+        files.len(),
+        result.failures.len(),
+        result.skipped.len()
+    )
+}
+
+fn enable_native_diff(manager: &FormatterManager, binary: &Path, options: &mut FormatOptions) {
+    options.native_diff =
+        options.check && !options.stdout && formatter_supports_flag(manager, binary, "--diff");
+}
+
+fn format_call_summary(
+    file_count: usize,
+    options: &FormatOptions,
+    result: &FormatToolResult,
+    formatter_version: &str,
+) -> Value {
+    let failed_count = result.failures.len();
+    call_summary(
+        file_count,
+        json!({
+            "check": options.check,
+            "stdout": options.stdout,
+            "use_spaces": options.use_spaces,
+            "indent_size": options.indent_size,
+            "reorder_code": options.reorder_code,
+            "safe": options.safe
+        }),
+        result.processed_count.saturating_sub(failed_count),
+        failed_count,
+        formatter_version,
+    )
+}
+
+pub fn call_gdscript_format(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<FormatToolResult, ToolError> {
+    let mut timer = PhaseTimer::start();
+    let arguments = &apply_profile(arguments)?;
+    let (files, scan_stats) = resolve_target_files(arguments, true)?;
+    enforce_max_files(&files, resolve_max_files(arguments)?)?;
+    let mut options = FormatOptions::from_arguments(arguments)?;
+    if options.backup && (options.check || options.stdout) {
+        return Err(
+            "`backup` only applies to in-place formatting (not `check` or `stdout`)"
+                .to_owned()
+                .into(),
+        );
+    }
+    let junit = match get_optional_string(arguments, "format")?.as_deref() {
+        None | Some("default") => false,
+        Some("junit") if options.check => true,
+        Some("junit") => {
+            return Err("`format: \"junit\"` requires `check: true`"
+                .to_owned()
+                .into());
+        }
+        Some(other) => {
+            return Err(format!("`format` must be \"default\" or \"junit\" (got {other})").into());
+        }
+    };
+    if get_bool(arguments, "skip_unchanged")? {
+        options.format_cache_path = Some(manager.cache_root().join(FORMAT_CACHE_FILE_NAME));
+    }
+
+    timer.mark("resolve_targets");
+
+    let binary = resolve_binary(manager, arguments)?;
+    enforce_min_formatter_version(manager, &binary, arguments)?;
+    enable_native_diff(manager, &binary, &mut options);
+    timer.mark("resolve_binary");
+
+    let mut result = run_format(&binary, &files, &options);
+    timer.mark("execute");
+    if get_bool(arguments, "include_timings")? {
+        result.timings = Some(timer.to_json());
+    }
+    if junit {
+        result.junit_xml = Some(render_junit_xml(&files, &result));
+    }
+    if get_bool(arguments, "scan_stats")? {
+        result.scan_stats = Some(scan_stats.to_json());
+    }
+    result.one_line_summary = get_bool(arguments, "one_line_summary")?;
+    result.call_summary = Some(format_call_summary(
+        files.len(),
+        &options,
+        &result,
+        &binary_version(manager, &binary),
+    ));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::test_support::write_fake_formatter;
+
+    /// A temp dir holding a fake formatter that runs `body`, plus `files`
+    /// written as `(path below the temp dir, content)`. The files come back
+    /// as full paths in the same order.
+    #[cfg(unix)]
+    fn fake_formatter_fixture(
+        body: &str,
+        files: &[(&str, &str)],
+    ) -> (tempfile::TempDir, PathBuf, Vec<String>) {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = write_fake_formatter(temp.path(), body);
+        let files = files
+            .iter()
+            .map(|(name, content)| {
+                let path = temp.path().join(name);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).expect("create parent dir");
+                }
+                fs::write(&path, content).expect("write file");
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+        (temp, binary, files)
+    }
+
+    #[test]
+    fn extract_format_failure_reason_from_stderr() {
+        let stderr = "Formatting 1 file...Error: \"Failed to format file /tmp/bad.gd: Topiary formatting failed\"";
+        let reason = extract_format_failure_reason("", stderr);
         assert_eq!(reason, "Topiary formatting failed");
     }
 
@@ -212,12 +1446,213 @@ mod tests {
         assert_eq!(reason, "No such file or directory (os error 2)");
     }
 
+    #[test]
+    fn parse_reorder_moves_keeps_move_lines_only() {
+        let stdout = "Formatting 1 file...\nMoved function `_ready` above `_process`\n";
+        let stderr = "\u{1b}[33mReordered\u{1b}[0m signal `died` to the top\nDone\n";
+        assert_eq!(
+            parse_reorder_moves(stdout, stderr),
+            vec![
+                "Moved function `_ready` above `_process`",
+                "Reordered signal `died` to the top"
+            ]
+        );
+        assert!(parse_reorder_moves("Formatting 1 file...\n", "").is_empty());
+
+        let result = FormatToolResult {
+            success: true,
+            reorder_summary: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_structured_content(&result)["reorder_summary"],
+            json!([])
+        );
+    }
+
+    #[test]
+    fn format_call_summary_records_inputs_and_outcome() {
+        let options = FormatOptions {
+            check: true,
+            indent_size: Some(2),
+            reorder_code: true,
+            ..Default::default()
+        };
+        let result = FormatToolResult {
+            success: false,
+            processed_count: 3,
+            failures: vec![FormatFailure {
+                file: "b.gd".to_owned(),
+                reason: "would reformat".to_owned(),
+                category: "format_error",
+                signal: None,
+            }],
+            skipped: vec![FormatSkip {
+                file: "c.gd".to_owned(),
+                reason: "binary_content",
+            }],
+            ..Default::default()
+        };
+
+        let mut result = FormatToolResult {
+            call_summary: Some(format_call_summary(4, &options, &result, "0.18.2")),
+            ..result
+        };
+        assert_eq!(
+            format_structured_content(&result)["call_summary"],
+            json!({
+                "file_count": 4,
+                "flags": {
+                    "check": true,
+                    "stdout": false,
+                    "use_spaces": false,
+                    "indent_size": 2,
+                    "reorder_code": true,
+                    "safe": false
+                },
+                "ok_count": 2,
+                "failed_count": 1,
+                "formatter_version": "0.18.2"
+            })
+        );
+        result.call_summary = None;
+        assert!(
+            format_structured_content(&result)
+                .get("call_summary")
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn use_binary_summary_surfaces_formatter_summary_line() {
+        let (temp, binary, files) = fake_formatter_fixture(
+            "echo 'Formatting 1 file...' >&2\necho 'Formatted 1 file, 1 changed' >&2\nexit 0\n",
+            &[("player.gd", "extends Node\n")],
+        );
+
+        let options = FormatOptions {
+            use_binary_summary: true,
+            ..Default::default()
+        };
+        let result = run_format(&binary, &files, &options);
+        assert_eq!(
+            render_format_summary(&result),
+            "Formatted 1 file, 1 changed"
+        );
+
+        let quiet = write_fake_formatter(temp.path(), "echo 'Formatting 1 file...' >&2\nexit 0\n");
+        let result = run_format(&quiet, &files, &options);
+        assert_eq!(render_format_summary(&result), "Format ok.");
+
+        let second = temp.path().join("enemy.gd");
+        fs::write(&second, "extends Node\n").expect("write enemy.gd");
+        let both = [files[0].clone(), second.to_string_lossy().to_string()];
+        let binary = write_fake_formatter(
+            temp.path(),
+            "echo 'Formatted 1 file, 1 changed' >&2\nexit 0\n",
+        );
+        let result = run_format(&binary, &both, &options);
+        assert_eq!(
+            render_format_summary(&result),
+            "Formatted 2 files, 2 changed"
+        );
+    }
+
+    #[test]
+    fn binary_summaries_merge_into_one_line() {
+        let lines = |lines: &[&str]| lines.iter().map(|l| (*l).to_owned()).collect::<Vec<_>>();
+        assert_eq!(
+            merge_binary_summaries(&lines(&[
+                "Formatted 1 file, 1 changed",
+                "Formatted 1 file, 0 changed",
+                "Formatted 2 files, 1 changed",
+            ]))
+            .as_deref(),
+            Some("Formatted 4 files, 2 changed")
+        );
+        assert_eq!(
+            merge_binary_summaries(&lines(&["Formatted 0 files, 0 changed"])).as_deref(),
+            Some("Formatted 0 files, 0 changed")
+        );
+        assert_eq!(
+            merge_binary_summaries(&lines(&["Formatted 1 file", "Checked 1 file"])).as_deref(),
+            Some("Checked 1 file")
+        );
+        assert_eq!(merge_binary_summaries(&[]), None);
+    }
+
+    #[test]
+    fn render_format_summary_one_line() {
+        let success = FormatToolResult {
+            success: true,
+            processed_count: 10,
+            one_line_summary: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_format_summary(&success),
+            "format ok files=10 changed=0 failed=0"
+        );
+        let changed = FormatToolResult {
+            changed_count: 3,
+            ..success
+        };
+        assert_eq!(
+            render_format_summary(&changed),
+            "format ok files=10 changed=3 failed=0"
+        );
+
+        let failed = FormatToolResult {
+            success: false,
+            processed_count: 10,
+            failures: vec![FormatFailure {
+                file: "a.gd".to_owned(),
+                reason: "reason".to_owned(),
+                category: "format_error",
+                signal: None,
+            }],
+            one_line_summary: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_format_summary(&failed),
+            "format failed files=10 changed=0 failed=1"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn one_line_summary_counts_rewritten_files() {
+        let (_temp, binary, files) = fake_formatter_fixture(
+            "for f; do :; done\ncase \"$f\" in *messy.gd) printf 'var a = 1\\n' > \"$f\" ;; esac\nexit 0\n",
+            &[
+                ("messy.gd", "var  a  =  1\n"),
+                ("tidy.gd", "var  a  =  1\n"),
+            ],
+        );
+        let options = FormatOptions {
+            count_changes: true,
+            ..Default::default()
+        };
+
+        let result = FormatToolResult {
+            one_line_summary: true,
+            ..run_format(&binary, &files, &options)
+        };
+        assert_eq!(result.changed_count, 1);
+        assert_eq!(
+            render_format_summary(&result),
+            "format ok files=2 changed=1 failed=0"
+        );
+    }
+
     #[test]
     fn render_format_summary_is_minimal() {
         let success = FormatToolResult {
             success: true,
             processed_count: 5,
-            failures: Vec::new(),
+            ..Default::default()
         };
         assert_eq!(render_format_summary(&success), "Format ok.");
 
@@ -227,7 +1662,10 @@ mod tests {
             failures: vec![FormatFailure {
                 file: "a.gd".to_owned(),
                 reason: "reason".to_owned(),
+                category: "format_error",
+                signal: None,
             }],
+            ..Default::default()
         };
         assert_eq!(
             render_format_summary(&failed),
@@ -240,7 +1678,7 @@ mod tests {
         let success = FormatToolResult {
             success: true,
             processed_count: 10,
-            failures: Vec::new(),
+            ..Default::default()
         };
         let structured = format_structured_content(&success);
         assert_eq!(structured, json!({"ok": true, "processed_count": 10}));
@@ -252,12 +1690,15 @@ mod tests {
             .map(|i| FormatFailure {
                 file: format!("f{i}.gd"),
                 reason: "reason".to_owned(),
+                category: "format_error",
+                signal: None,
             })
             .collect::<Vec<_>>();
         let failed = FormatToolResult {
             success: false,
             processed_count: DEFAULT_MAX_FAILURES_RETURNED + 1,
             failures,
+            ..Default::default()
         };
         let structured = format_structured_content(&failed);
         assert_eq!(
@@ -270,4 +1711,741 @@ mod tests {
             Some(DEFAULT_MAX_FAILURES_RETURNED)
         );
     }
+
+    #[test]
+    fn format_structured_content_includes_raw_output_per_file() {
+        let result = FormatToolResult {
+            success: true,
+            processed_count: 2,
+            include_raw_output: true,
+            raw_outputs: vec![
+                FormatRawOutput {
+                    file: "a.gd".to_owned(),
+                    stdout: "formatted a".to_owned(),
+                    stderr: String::new(),
+                },
+                FormatRawOutput {
+                    file: "b.gd".to_owned(),
+                    stdout: String::new(),
+                    stderr: "warning for b".to_owned(),
+                },
+            ],
+            ..Default::default()
+        };
+        let structured = format_structured_content(&result);
+        assert_eq!(
+            structured["raw_by_file"],
+            json!({
+                "a.gd": {"stdout": "formatted a", "stderr": ""},
+                "b.gd": {"stdout": "", "stderr": "warning for b"}
+            })
+        );
+    }
+
+    #[test]
+    fn format_structured_content_sorts_failures_by_file() {
+        let failures = ["c.gd", "a.gd", "b.gd"]
+            .into_iter()
+            .map(|file| FormatFailure {
+                file: file.to_owned(),
+                reason: "reason".to_owned(),
+                category: "format_error",
+                signal: None,
+            })
+            .collect::<Vec<_>>();
+        let failed = FormatToolResult {
+            success: false,
+            processed_count: 3,
+            failures,
+            ..Default::default()
+        };
+        let structured = format_structured_content(&failed);
+        let files = structured["failures"]
+            .as_array()
+            .expect("failures array")
+            .iter()
+            .map(|f| f["file"].as_str().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(files, vec!["a.gd", "b.gd", "c.gd"]);
+    }
+
+    #[test]
+    fn transient_failures_are_distinguished_from_syntax_errors() {
+        assert!(is_transient_failure(
+            "The process cannot access the file because it is being used by another process. (os error 32)"
+        ));
+        assert!(is_transient_failure("Permission denied (os error 13)"));
+        assert!(!is_transient_failure("Topiary formatting failed"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_format_retries_transient_failure() {
+        let (temp, binary, _) = fake_formatter_fixture(
+            "marker=\"$(dirname \"$0\")/attempted\"\nif [ -f \"$marker\" ]; then exit 0; fi\ntouch \"$marker\"\necho 'Error: \"Failed to read file a.gd: Resource temporarily unavailable (os error 11)\"' >&2\nexit 1\n",
+            &[],
+        );
+        let marker = temp.path().join("attempted");
+        let files = vec!["a.gd".to_owned()];
+
+        let without_retry = run_format(&binary, &files, &FormatOptions::default());
+        assert!(!without_retry.success);
+
+        std::fs::remove_file(&marker).expect("reset marker");
+        let options = FormatOptions {
+            per_file_retries: 2,
+            ..Default::default()
+        };
+        let with_retry = run_format(&binary, &files, &options);
+        assert!(with_retry.success);
+        assert!(with_retry.failures.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_files_are_reported_as_errors_not_failures() {
+        let (temp, binary, mut files) = fake_formatter_fixture(
+            "for f in \"$@\"; do\n  case \"$f\" in\n    *.gd) file=\"$f\" ;;\n  esac\ndone\nif [ ! -f \"$file\" ]; then\n  echo \"Error: \\\"Failed to read file $file: No such file or directory (os error 2)\\\"\" >&2\n  exit 1\nfi\necho \"Error: \\\"Failed to format file $file: Topiary formatting failed\\\"\" >&2\nexit 1\n",
+            &[("broken.gd", "func (\n")],
+        );
+        files.push(temp.path().join("missing.gd").to_string_lossy().to_string());
+
+        let result = run_format(&binary, &files, &FormatOptions::default());
+        assert!(!result.success);
+        let structured = format_structured_content(&result);
+        assert_eq!(structured["failed_count"], json!(1));
+        assert_eq!(structured["error_count"], json!(1));
+        assert_eq!(structured["failures"].as_array().map(Vec::len), Some(1));
+        assert_eq!(structured["failures"][0]["file"], json!(files[0]));
+        assert_eq!(structured["failures"][0]["category"], "format_error");
+        assert_eq!(structured["errors"][0]["file"], json!(files[1]));
+        assert_eq!(structured["errors"][0]["category"], "file_not_found");
+        assert_eq!(
+            structured["errors"][0]["reason"],
+            "No such file or directory (os error 2)"
+        );
+        assert_eq!(
+            render_format_summary(&result),
+            "Format failed. failed_count=1, error_count=1."
+        );
+        let one_line = FormatToolResult {
+            one_line_summary: true,
+            ..result
+        };
+        assert_eq!(
+            render_format_summary(&one_line),
+            "format failed files=2 changed=0 failed=1 errors=1"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn formatter_killed_by_signal_is_reported_as_crash() {
+        let (_temp, binary, _) = fake_formatter_fixture("kill -SEGV $$\n", &[]);
+
+        let result = run_format(&binary, &["a.gd".to_owned()], &FormatOptions::default());
+        assert!(!result.success);
+        assert_eq!(result.failures[0].category, "crash");
+        let signal = result.failures[0].signal.as_ref().expect("signal");
+        assert_eq!(signal.name, "SIGSEGV");
+        assert_eq!(signal.number, libc::SIGSEGV);
+        let structured = format_structured_content(&result);
+        assert_eq!(structured["failures"][0]["signal"], "SIGSEGV");
+        assert_eq!(structured["failures"][0]["signal_number"], libc::SIGSEGV);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn include_exit_codes_reports_every_processed_file() {
+        let (_temp, binary, _) = fake_formatter_fixture(
+            "for last; do :; done\n[ \"$last\" = bad.gd ] && exit 2\nexit 0\n",
+            &[],
+        );
+        let options = FormatOptions {
+            include_exit_codes: true,
+            ..Default::default()
+        };
+
+        let result = run_format(
+            &binary,
+            &["ok.gd".to_owned(), "bad.gd".to_owned()],
+            &options,
+        );
+        assert!(!result.success);
+        assert_eq!(
+            format_structured_content(&result)["exit_codes"],
+            json!({"ok.gd": 0, "bad.gd": 2})
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn backup_copies_only_files_that_change() {
+        let (temp, binary, files) = fake_formatter_fixture(
+            "for last; do :; done\necho run >> \"$(dirname \"$0\")/runs.log\"\nsed 's/  */ /g' \"$last\" > \"$last.tmp\" && mv \"$last.tmp\" \"$last\"\n",
+            &[
+                ("messy.gd", "var  speed  =  1\n"),
+                ("clean.gd", "var speed = 1\n"),
+                ("clean.gd.bak", "older backup\n"),
+            ],
+        );
+        let files = files[..2].to_vec();
+        let messy = PathBuf::from(&files[0]);
+        let options = FormatOptions {
+            backup: true,
+            ..Default::default()
+        };
+
+        let result = run_format(&binary, &files, &options);
+        assert!(result.success);
+        let messy_backup = temp.path().join("messy.gd.bak");
+        assert_eq!(
+            fs::read_to_string(&messy_backup).expect("read backup"),
+            "var  speed  =  1\n"
+        );
+        assert_eq!(
+            fs::read_to_string(temp.path().join("clean.gd.bak")).expect("read old backup"),
+            "older backup\n"
+        );
+        assert!(!temp.path().join("clean.gd.bak.partial").exists());
+        assert_eq!(
+            fs::read_to_string(temp.path().join("runs.log")).expect("read run log"),
+            "run\nrun\n"
+        );
+        assert_eq!(
+            format_structured_content(&result)["backups"],
+            json!([{"file": files[0], "backup": messy_backup.to_string_lossy()}])
+        );
+        assert_eq!(
+            fs::read_to_string(&messy).expect("read messy.gd"),
+            "var speed = 1\n"
+        );
+
+        let backup_dir = temp.path().join("backups");
+        fs::write(&messy, "var  speed  =  2\n").expect("rewrite messy.gd");
+        let options = FormatOptions {
+            backup: true,
+            backup_dir: Some(backup_dir.clone()),
+            ..Default::default()
+        };
+        let result = run_format(&binary, &files, &options);
+        assert_eq!(result.backups.len(), 1);
+        let mirrored = backup_path(&messy, Some(&backup_dir));
+        assert!(mirrored.starts_with(&backup_dir));
+        assert_eq!(
+            fs::read_to_string(&mirrored).expect("read mirrored backup"),
+            "var  speed  =  2\n"
+        );
+    }
+
+    #[test]
+    fn backup_dir_keeps_files_reached_through_parent_dirs_apart() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        fs::create_dir_all(temp.path().join("b")).expect("create b");
+        fs::write(temp.path().join("x.gd"), "var a\n").expect("write x.gd");
+        fs::write(temp.path().join("b/x.gd"), "var b\n").expect("write b/x.gd");
+        let backup_dir = temp.path().join("backups");
+
+        let through_parent = backup_path(&temp.path().join("b/../x.gd"), Some(&backup_dir));
+        let nested = backup_path(&temp.path().join("b/x.gd"), Some(&backup_dir));
+        assert_ne!(through_parent, nested);
+        assert_eq!(
+            through_parent,
+            backup_path(&temp.path().join("x.gd"), Some(&backup_dir))
+        );
+        assert!(through_parent.starts_with(&backup_dir));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_ignored_files_do_not_fail_check() {
+        let (_temp, binary, _) = fake_formatter_fixture("exit 1\n", &[]);
+        let options = FormatOptions {
+            check: true,
+            check_ignore: get_optional_globset(
+                &json!({"check_ignore": ["legacy/**"]})
+                    .as_object()
+                    .cloned()
+                    .unwrap_or_default(),
+                "check_ignore",
+            )
+            .expect("build globset"),
+            ..Default::default()
+        };
+
+        let ignored = run_format(&binary, &["legacy/old.gd".to_owned()], &options);
+        assert!(ignored.success);
+        assert_eq!(ignored.check_ignored, vec!["legacy/old.gd".to_owned()]);
+        assert_eq!(
+            format_structured_content(&ignored)["check_ignored"],
+            json!(["legacy/old.gd"])
+        );
+
+        let enforced = run_format(&binary, &["src/new.gd".to_owned()], &options);
+        assert!(!enforced.success);
+        assert!(enforced.check_ignored.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_ignore_still_reports_crashes_and_parse_errors() {
+        let (_temp, binary, _) = fake_formatter_fixture(
+            "for last; do :; done\ncase \"$last\" in\n  *stale.gd) echo 'Error: \"Failed to format file: File is not formatted\"' >&2; exit 1 ;;\n  *broken.gd) echo 'Error: \"Failed to format file: Topiary formatting failed\"' >&2; exit 1 ;;\n  *missing.gd) echo 'Error: \"Failed to read file: No such file or directory (os error 2)\"' >&2; exit 1 ;;\n  *crash.gd) kill -SEGV $$ ;;\nesac\n",
+            &[],
+        );
+        let options = FormatOptions {
+            check: true,
+            check_ignore: get_optional_globset(
+                &json!({"check_ignore": ["legacy/**"]})
+                    .as_object()
+                    .cloned()
+                    .unwrap_or_default(),
+                "check_ignore",
+            )
+            .expect("build globset"),
+            ..Default::default()
+        };
+        let files = ["stale", "broken", "missing", "crash"]
+            .map(|name| format!("legacy/{name}.gd"))
+            .to_vec();
+
+        let result = run_format(&binary, &files, &options);
+        assert_eq!(result.check_ignored, vec!["legacy/stale.gd".to_owned()]);
+        let categories = result
+            .failures
+            .iter()
+            .map(|failure| (failure.file.as_str(), failure.category))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            categories,
+            vec![
+                ("legacy/broken.gd", "format_error"),
+                ("legacy/missing.gd", "file_not_found"),
+                ("legacy/crash.gd", "crash"),
+            ]
+        );
+        assert!(!result.success);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn absolute_paths_with_relative_to_dir_reports_canonical_failures() {
+        let (temp, binary, _) = fake_formatter_fixture(
+            "for last; do :; done\n[ -f \"$last\" ] || exit 2\necho 'Error: \"Failed to format file: Topiary formatting failed\"' >&2\nexit 1\n",
+            &[("project/sub/bad.gd", "var  x\n")],
+        );
+        let project = temp.path().join("project");
+
+        let arguments = json!({
+            "dir": project.join("sub").join("..").to_string_lossy(),
+            "path_style": "relative_to_dir",
+            "absolute_paths": true
+        })
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+        let (files, _) = resolve_target_files(&arguments, true).expect("scan dir");
+        let options = FormatOptions::from_arguments(&arguments).expect("parse options");
+
+        let result = run_format(&binary, &files, &options);
+        let expected = fs::canonicalize(project.join("sub/bad.gd")).expect("canonical path");
+        let structured = format_structured_content(&result);
+        assert_eq!(
+            structured["failures"],
+            json!([{
+                "file": expected.to_string_lossy(),
+                "reason": "Topiary formatting failed",
+                "category": "format_error"
+            }])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_ignore_matches_paths_relative_to_scanned_dir() {
+        let (temp, binary, _) = fake_formatter_fixture(
+            "exit 1\n",
+            &[
+                ("project/legacy/old.gd", "var a = 1\n"),
+                ("project/src/new.gd", "var b = 2\n"),
+            ],
+        );
+        let project = temp.path().join("project");
+
+        let arguments = json!({
+            "dir": project.to_string_lossy(),
+            "check": true,
+            "check_ignore": ["legacy/**"]
+        })
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+        let (files, _) = resolve_target_files(&arguments, true).expect("scan dir");
+        let options = FormatOptions::from_arguments(&arguments).expect("parse options");
+
+        let result = run_format(&binary, &files, &options);
+        assert_eq!(result.check_ignored.len(), 1);
+        assert!(result.check_ignored[0].ends_with("legacy/old.gd"));
+        assert_eq!(result.failures.len(), 1);
+        assert!(result.failures[0].file.ends_with("src/new.gd"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn idempotency_check_flags_files_that_change_on_second_pass() {
+        let (_temp, binary, files) = fake_formatter_fixture(
+            "for arg; do [ \"$arg\" = --check ] && exit 0; last=$arg; done\necho '# pass' >> \"$last\"\n",
+            &[("a.gd", "extends Node\n")],
+        );
+        let options = FormatOptions {
+            check: true,
+            idempotency_check: true,
+            ..Default::default()
+        };
+
+        let result = run_format(&binary, &files, &options);
+        assert_eq!(result.non_idempotent, files);
+        assert_eq!(
+            format_structured_content(&result)["non_idempotent"],
+            json!(files)
+        );
+        assert_eq!(
+            fs::read_to_string(&files[0]).expect("read a.gd"),
+            "extends Node\n"
+        );
+    }
+
+    #[test]
+    fn render_junit_xml_reports_passing_and_failing_files() {
+        let files = vec!["ok.gd".to_owned(), "bad<1>.gd".to_owned()];
+        let result = FormatToolResult {
+            success: false,
+            processed_count: 2,
+            failures: vec![FormatFailure {
+                file: "bad<1>.gd".to_owned(),
+                reason: "File is not formatted".to_owned(),
+                category: "format_error",
+                signal: None,
+            }],
+            ..Default::default()
+        };
+
+        let xml = render_junit_xml(&files, &result);
+        assert!(xml.contains(
+            "<testsuite name=\"gdscript_format\" tests=\"2\" failures=\"1\" skipped=\"0\">"
+        ));
+        assert!(xml.contains("<testcase classname=\"gdscript_format\" name=\"ok.gd\"/>"));
+        assert!(xml.contains(
+            "<testcase classname=\"gdscript_format\" name=\"bad&lt;1&gt;.gd\">\n    <failure type=\"format_error\" message=\"File is not formatted\"/>\n  </testcase>"
+        ));
+    }
+
+    #[test]
+    fn format_structured_content_counts_distinct_reasons() {
+        let failures = [
+            ("c.gd", "Topiary formatting failed"),
+            ("b.gd", "Topiary formatting failed"),
+            ("a.gd", "No such file or directory (os error 2)"),
+            ("d.gd", "Topiary formatting failed"),
+        ]
+        .into_iter()
+        .map(|(file, reason)| FormatFailure {
+            file: file.to_owned(),
+            reason: reason.to_owned(),
+            category: "format_error",
+            signal: None,
+        })
+        .collect::<Vec<_>>();
+        let failed = FormatToolResult {
+            success: false,
+            processed_count: 4,
+            failures,
+            ..Default::default()
+        };
+        let structured = format_structured_content(&failed);
+        assert_eq!(
+            structured["distinct_reasons"],
+            json!([
+                {"reason": "Topiary formatting failed", "count": 3, "example_file": "b.gd"},
+                {"reason": "No such file or directory (os error 2)", "count": 1, "example_file": "a.gd"}
+            ])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn native_diff_is_captured_when_flag_is_supported() {
+        let (_temp, binary, _) = fake_formatter_fixture(
+            "for arg; do [ \"$arg\" = --help ] && { printf 'Options:\\n  -c, --check  Check only\\n      --diff   Print a diff\\n'; exit 0; }; done\nfor arg; do [ \"$arg\" = --diff ] && { printf -- '-var  x\\n+var x\\n'; exit 1; }; done\nexit 1\n",
+            &[],
+        );
+        let manager = FormatterManager::new().expect("create manager");
+        let mut options = FormatOptions {
+            check: true,
+            ..Default::default()
+        };
+        enable_native_diff(&manager, &binary, &mut options);
+        assert!(options.native_diff);
+
+        let result = run_format(&binary, &["a.gd".to_owned()], &options);
+        let structured = format_structured_content(&result);
+        assert_eq!(
+            structured["diffs"],
+            json!([{"file": "a.gd", "diff": "-var  x\n+var x\n"}])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_falls_back_to_client_side_diff_without_native_flag() {
+        let (temp, binary, _) = fake_formatter_fixture(
+            "for arg; do [ \"$arg\" = --help ] && { printf 'Options:\\n  -c, --check  Check only\\n'; exit 0; }; done\nfor arg; do [ \"$arg\" = --check ] && { echo 'Error: \"Failed to format file: not formatted\"' >&2; exit 1; }; last=$arg; done\nprintf 'var x\\n' > \"$last\"\n",
+            &[("a.gd", "var  x\n")],
+        );
+        let manager = FormatterManager::new().expect("create manager");
+        let mut options = FormatOptions {
+            check: true,
+            working_dir: Some(temp.path().to_path_buf()),
+            ..Default::default()
+        };
+        enable_native_diff(&manager, &binary, &mut options);
+        assert!(!options.native_diff);
+
+        let result = run_format(&binary, &["a.gd".to_owned()], &options);
+        let structured = format_structured_content(&result);
+        let diffs = structured["diffs"].as_array().expect("diffs array");
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0]["file"], "a.gd");
+        let diff = diffs[0]["diff"].as_str().expect("diff text");
+        assert!(
+            diff.contains("-var  x") && diff.contains("+var x"),
+            "{diff}"
+        );
+        assert_eq!(
+            fs::read_to_string(temp.path().join("a.gd")).expect("read file"),
+            "var  x\n"
+        );
+    }
+
+    #[test]
+    fn classify_indentation_detects_tabs_spaces_and_mixed() {
+        assert_eq!(
+            classify_indentation("func a():\n\tpass\n\n\tvar x = 1\n"),
+            "tabs"
+        );
+        assert_eq!(classify_indentation("func a():\n    pass\n"), "spaces");
+        assert_eq!(
+            classify_indentation("func a():\n\tpass\nfunc b():\n    pass\n"),
+            "mixed"
+        );
+        assert_eq!(classify_indentation("extends Node\n"), "unknown");
+    }
+
+    #[test]
+    fn detect_file_indentation_reads_files() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        fs::write(temp.path().join("tabs.gd"), "func a():\n\tpass\n").expect("write tabs.gd");
+        fs::write(temp.path().join("spaces.gd"), "func a():\n    pass\n").expect("write spaces.gd");
+        let options = FormatOptions {
+            working_dir: Some(temp.path().to_path_buf()),
+            detect_indentation: true,
+            ..Default::default()
+        };
+        assert_eq!(detect_file_indentation(&options, "tabs.gd"), "tabs");
+        assert_eq!(detect_file_indentation(&options, "spaces.gd"), "spaces");
+        assert_eq!(detect_file_indentation(&options, "missing.gd"), "unknown");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn indentation_applied_samples_changed_files_after_formatting() {
+        let (temp, binary, _) = fake_formatter_fixture(
+            "for f in \"$@\"; do file=\"$f\"; done\ncase \"$file\" in\n  *messy.gd) printf 'func a():\\n    if true:\\n        pass\\n' > \"$file\" ;;\nesac\n",
+            &[
+                ("messy.gd", "func a():\n\tif true:\n\t\tpass\n"),
+                ("tabs.gd", "func b():\n\tpass\n"),
+            ],
+        );
+        let files = vec!["messy.gd".to_owned(), "tabs.gd".to_owned()];
+        let options = FormatOptions {
+            working_dir: Some(temp.path().to_path_buf()),
+            include_indentation_applied: true,
+            ..Default::default()
+        };
+
+        let result = run_format(&binary, &files, &options);
+        assert!(result.success);
+        let structured = format_structured_content(&result);
+        assert_eq!(
+            structured["indentation_applied"],
+            json!({"style": "spaces", "width": 4})
+        );
+
+        let unchanged = run_format(&binary, &files[1..], &options);
+        assert_eq!(
+            format_structured_content(&unchanged)["indentation_applied"],
+            Value::Null
+        );
+        let disabled = FormatOptions {
+            working_dir: Some(temp.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert!(
+            format_structured_content(&run_format(&binary, &files, &disabled))
+                .get("indentation_applied")
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_format_keeps_only_files_matching_content_pattern() {
+        let (_temp, binary, files) = fake_formatter_fixture(
+            "exit 0\n",
+            &[
+                ("sprite.gd", "extends Node2D\nvar speed = 1\n"),
+                ("control.gd", "extends Control\n"),
+            ],
+        );
+
+        let mut arguments = Map::new();
+        arguments.insert("content_matches".to_owned(), json!(r"^extends\s+Node2D\b"));
+        let options = FormatOptions::from_arguments(&arguments).expect("parse options");
+        let result = run_format(&binary, &files, &options);
+        assert!(result.success);
+        assert_eq!(result.processed_count, 1);
+        assert_eq!(
+            format_structured_content(&result)["skipped"],
+            json!([{"file": files[1], "reason": "content_mismatch"}])
+        );
+
+        arguments.insert("content_matches".to_owned(), json!("extends ("));
+        assert!(FormatOptions::from_arguments(&arguments).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_format_skips_files_with_null_bytes() {
+        let (_temp, binary, files) = fake_formatter_fixture(
+            "exit 0\n",
+            &[
+                ("blob.gd", "GDSC\0\0\x01\x02"),
+                ("script.gd", "extends Node\n"),
+            ],
+        );
+
+        let options = FormatOptions::default();
+        assert!(options.skip_binary_files);
+        assert!(
+            FormatOptions::from_arguments(&Map::new())
+                .expect("parse options")
+                .skip_binary_files
+        );
+        let result = run_format(&binary, &files, &options);
+        assert!(result.success);
+        assert_eq!(result.processed_count, 1);
+        let structured = format_structured_content(&result);
+        assert_eq!(
+            structured["skipped"],
+            json!([{"file": files[0], "reason": "binary_content"}])
+        );
+
+        let disabled = FormatOptions {
+            skip_binary_files: false,
+            ..Default::default()
+        };
+        let result = run_format(&binary, &files, &disabled);
+        assert_eq!(result.processed_count, 2);
+        assert!(result.skipped.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_format_skips_unchanged_file_on_second_run() {
+        let (temp, binary, files) = fake_formatter_fixture(
+            "echo run >> \"$(dirname \"$0\")/invocations.log\"\n",
+            &[("script.gd", "extends Node\n")],
+        );
+        let log = temp.path().join("invocations.log");
+        let options = FormatOptions {
+            format_cache_path: Some(temp.path().join(FORMAT_CACHE_FILE_NAME)),
+            include_stats: true,
+            ..Default::default()
+        };
+
+        let first = run_format(&binary, &files, &options);
+        assert!(first.unchanged_cached.is_empty());
+        assert_eq!(first.processed_count, 1);
+        assert_eq!(first.stats.as_ref().map(|s| s.total_bytes), Some(13));
+        let second = run_format(&binary, &files, &options);
+        assert_eq!(second.unchanged_cached, files);
+        assert_eq!(second.processed_count, 0);
+        assert_eq!(second.stats.as_ref().map(|s| s.total_lines), Some(0));
+        assert_eq!(second.stats.as_ref().map(|s| s.total_bytes), Some(0));
+        assert_eq!(
+            fs::read_to_string(&log).expect("read log").lines().count(),
+            1
+        );
+
+        fs::write(&files[0], "extends Node2D\n").expect("modify script.gd");
+        let third = run_format(&binary, &files, &options);
+        assert!(third.unchanged_cached.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_format_totals_lines_and_bytes() {
+        let (_temp, binary, files) = fake_formatter_fixture(
+            "exit 0\n",
+            &[
+                ("a.gd", "extends Node\n\nfunc _ready():\n\tpass\n"),
+                ("b.gd", "extends Node"),
+            ],
+        );
+        let options = FormatOptions {
+            include_stats: true,
+            ..Default::default()
+        };
+
+        let result = run_format(&binary, &files, &options);
+        let structured = format_structured_content(&result);
+        assert_eq!(structured["total_lines"], json!(5));
+        assert_eq!(structured["total_bytes"], json!(47));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_format_reports_read_only_files_up_front() {
+        let (temp, binary, files) = fake_formatter_fixture(
+            "echo \"$@\" >> \"$(dirname \"$0\")/invocations.log\"\n",
+            &[("locked.gd", "extends Node\n")],
+        );
+        let log = temp.path().join("invocations.log");
+        let locked = PathBuf::from(&files[0]);
+        let mut permissions = fs::metadata(&locked).expect("metadata").permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&locked, permissions).expect("make read-only");
+        if fs::OpenOptions::new().write(true).open(&locked).is_ok() {
+            // Running as root: mode bits do not stop writes.
+            return;
+        }
+
+        let result = run_format(&binary, &files, &FormatOptions::default());
+        assert!(!result.success);
+        assert!(
+            result.failures[0]
+                .reason
+                .starts_with("File is not writable (read-only): "),
+            "{}",
+            result.failures[0].reason
+        );
+        assert_eq!(result.failures[0].category, "permission_denied");
+        assert!(!log.exists());
+
+        let options = FormatOptions {
+            check: true,
+            ..Default::default()
+        };
+        assert!(run_format(&binary, &files, &options).success);
+    }
 }
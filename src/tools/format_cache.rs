@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub const FORMAT_CACHE_FILE_NAME: &str = "format-state.json";
+const HASH_CHUNK_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct FileState {
+    mtime_nanos: u64,
+    size: u64,
+    formatted_hash: String,
+    fingerprint: String,
+}
+
+pub struct FormatCache {
+    path: PathBuf,
+    entries: BTreeMap<String, FileState>,
+}
+
+fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime_nanos = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    Some((u64::try_from(mtime_nanos).ok()?, metadata.len()))
+}
+
+pub fn bytes_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Hashes `reader` in `HASH_CHUNK_BYTES` chunks, so peak memory does not
+/// grow with the file size.
+fn reader_hash(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0; HASH_CHUNK_BYTES];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => hasher.update(&chunk[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// Same digest as `bytes_hash` of the file's content, read in chunks.
+pub fn file_hash(path: &Path) -> io::Result<String> {
+    reader_hash(File::open(path)?)
+}
+
+fn content_hash(path: &Path) -> Option<String> {
+    file_hash(path).ok()
+}
+
+pub fn binary_fingerprint(binary: &Path, options_key: &str) -> String {
+    let (mtime_nanos, size) = file_stamp(binary).unwrap_or_default();
+    format!(
+        "{}|{mtime_nanos}|{size}|{options_key}",
+        binary.to_string_lossy()
+    )
+}
+
+impl FormatCache {
+    pub fn load(path: &Path) -> Self {
+        let entries = fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    pub fn is_unchanged(&self, key: &str, path: &Path, fingerprint: &str) -> bool {
+        let Some(state) = self.entries.get(key) else {
+            return false;
+        };
+        state.fingerprint == fingerprint
+            && file_stamp(path) == Some((state.mtime_nanos, state.size))
+            && content_hash(path).as_deref() == Some(state.formatted_hash.as_str())
+    }
+
+    pub fn record(&mut self, key: &str, path: &Path, fingerprint: &str) {
+        match (file_stamp(path), content_hash(path)) {
+            (Some((mtime_nanos, size)), Some(formatted_hash)) => {
+                self.entries.insert(
+                    key.to_owned(),
+                    FileState {
+                        mtime_nanos,
+                        size,
+                        formatted_hash,
+                        fingerprint: fingerprint.to_owned(),
+                    },
+                );
+            }
+            _ => self.invalidate(key),
+        }
+    }
+
+    pub fn invalidate(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let bytes = serde_json::to_vec(&self.entries)
+            .map_err(|e| format!("Failed to serialize format cache: {e}"))?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, bytes).map_err(|e| {
+            format!(
+                "Failed to write format cache {}: {}",
+                temp_path.display(),
+                e
+            )
+        })?;
+        fs::rename(&temp_path, &self.path).map_err(|e| {
+            format!(
+                "Failed to move format cache into place {}: {}",
+                self.path.display(),
+                e
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ChunkRecorder<R> {
+        inner: R,
+        largest_read: usize,
+        reads: usize,
+    }
+
+    impl<R: Read> Read for ChunkRecorder<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.largest_read = self.largest_read.max(buf.len());
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    #[test]
+    fn large_files_are_hashed_in_bounded_chunks() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let path = temp.path().join("generated.gd");
+        let content = "var value := 1\n".repeat(64 * 1024);
+        fs::write(&path, &content).expect("write large file");
+
+        let mut recorder = ChunkRecorder {
+            inner: File::open(&path).expect("open large file"),
+            largest_read: 0,
+            reads: 0,
+        };
+        let streamed = reader_hash(&mut recorder).expect("hash large file");
+        assert_eq!(streamed, bytes_hash(content.as_bytes()));
+        assert_eq!(recorder.largest_read, HASH_CHUNK_BYTES);
+        assert!(recorder.reads > content.len() / HASH_CHUNK_BYTES);
+        assert_eq!(file_hash(&path).ok(), Some(streamed));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::formatter_manager::FormatterManager;
+use crate::tools::ToolError;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+
+pub struct HelpFlag {
+    pub short: Option<String>,
+    pub long: Option<String>,
+    pub value: Option<String>,
+    pub description: String,
+}
+
+pub struct HelpToolResult {
+    pub formatter_flags: Vec<HelpFlag>,
+    pub lint_flags: Vec<HelpFlag>,
+}
+
+fn parse_flag_spec(spec: &str) -> (Option<String>, Option<String>, Option<String>) {
+    let mut short = None;
+    let mut long = None;
+    let mut value = None;
+
+    for token in spec.split([',', ' ']).filter(|t| !t.is_empty()) {
+        if let Some(name) = token.strip_prefix("--") {
+            let (name, inline_value) = match name.split_once('=') {
+                Some((name, inline_value)) => (name, Some(inline_value)),
+                None => (name, None),
+            };
+            long = Some(format!("--{name}"));
+            if let Some(inline_value) = inline_value {
+                value = Some(inline_value.trim_matches(['<', '>', '[', ']']).to_owned());
+            }
+        } else if token.starts_with('-') {
+            short = Some(token.to_owned());
+        } else if token.starts_with('<') || token.starts_with('[') {
+            value = Some(token.trim_matches(['<', '>', '[', ']']).to_owned());
+        }
+    }
+
+    (short, long, value)
+}
+
+pub fn parse_help_flags(text: &str) -> Vec<HelpFlag> {
+    let mut flags: Vec<HelpFlag> = Vec::new();
+    let mut continuing = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || !line.starts_with(' ') {
+            continuing = false;
+            continue;
+        }
+
+        if trimmed.starts_with('-') {
+            let (spec, description) = match trimmed.split_once("  ") {
+                Some((spec, description)) => (spec, description.trim()),
+                None => (trimmed, ""),
+            };
+            let (short, long, value) = parse_flag_spec(spec);
+            flags.push(HelpFlag {
+                short,
+                long,
+                value,
+                description: description.to_owned(),
+            });
+            continuing = true;
+        } else if continuing && let Some(flag) = flags.last_mut() {
+            if !flag.description.is_empty() {
+                flag.description.push(' ');
+            }
+            flag.description.push_str(trimmed);
+        }
+    }
+
+    flags
+}
+
+fn flags_to_json(flags: &[HelpFlag]) -> Vec<Value> {
+    flags
+        .iter()
+        .map(|flag| {
+            json!({
+                "short": flag.short,
+                "long": flag.long,
+                "value": flag.value,
+                "description": flag.description
+            })
+        })
+        .collect()
+}
+
+pub fn help_structured_content(result: &HelpToolResult) -> Value {
+    json!({
+        "ok": true,
+        "formatter_options": flags_to_json(&result.formatter_flags),
+        "lint_options": flags_to_json(&result.lint_flags)
+    })
+}
+
+pub fn render_help_summary(result: &HelpToolResult) -> String {
+    format!(
+        "Formatter help parsed. formatter_options={}, lint_options={}.",
+        result.formatter_flags.len(),
+        result.lint_flags.len()
+    )
+}
+
+pub fn formatter_supports_flag(manager: &FormatterManager, binary: &Path, flag: &str) -> bool {
+    match manager.cached_output(binary, &["--help"]) {
+        Ok(help) => parse_help_flags(&help)
+            .iter()
+            .any(|parsed| parsed.long.as_deref() == Some(flag)),
+        Err(err) => {
+            eprintln!("Warning: could not probe formatter for {flag}: {err}");
+            false
+        }
+    }
+}
+
+pub fn call_gdscript_formatter_help(
+    manager: &FormatterManager,
+    _arguments: &Map<String, Value>,
+) -> Result<HelpToolResult, ToolError> {
+    let binary = manager.ensure_binary()?;
+    let formatter_help = manager.cached_output(&binary, &["--help"])?;
+    let lint_help = manager.cached_output(&binary, &["lint", "--help"])?;
+
+    Ok(HelpToolResult {
+        formatter_flags: parse_help_flags(&formatter_help),
+        lint_flags: parse_help_flags(&lint_help),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_help_flags_parses_clap_output() {
+        let help = "A GDScript formatter\n\nUsage: gdscript-formatter [OPTIONS] [FILES]... [COMMAND]\n\nCommands:\n  lint  Lint GDScript files\n\nOptions:\n  -c, --check                      Check if files are formatted\n      --indent-size <INDENT_SIZE>  Number of spaces to use\n                                   [default: 4]\n      --use-spaces                 Use spaces for indentation\n  -h, --help                       Print help\n";
+        let flags = parse_help_flags(help);
+
+        assert_eq!(flags.len(), 4);
+        assert_eq!(flags[0].short.as_deref(), Some("-c"));
+        assert_eq!(flags[0].long.as_deref(), Some("--check"));
+        assert_eq!(flags[0].value, None);
+        assert_eq!(flags[0].description, "Check if files are formatted");
+        assert_eq!(flags[1].short, None);
+        assert_eq!(flags[1].long.as_deref(), Some("--indent-size"));
+        assert_eq!(flags[1].value.as_deref(), Some("INDENT_SIZE"));
+        assert_eq!(flags[1].description, "Number of spaces to use [default: 4]");
+        assert_eq!(flags[3].long.as_deref(), Some("--help"));
+    }
+}
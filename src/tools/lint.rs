@@ -1,11 +1,58 @@
+use crate::config::apply_profile;
 use crate::formatter_manager::FormatterManager;
 use crate::targets::{
-    get_bool, get_optional_i64, get_optional_string, get_optional_usize, resolve_target_files,
+    enforce_max_files, get_bool, get_optional_globset, get_optional_i64, get_optional_string,
+    get_optional_string_array, get_optional_usize, resolve_max_files, resolve_target_files,
+    resolve_working_dir,
 };
+use crate::tools::version::enforce_min_formatter_version;
+use crate::tools::{
+    PhaseTimer, ToolError, binary_version, call_summary, formatter_command, output_retrying_once,
+    resolve_binary, strip_ansi,
+};
+use globset::GlobSet;
 use serde_json::{Map, Value, json};
-use std::process::Command;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub const DEFAULT_MAX_DIAGNOSTICS: usize = 500;
+const PARSE_WARNING_SNIPPET_CHARS: usize = 200;
+pub const UNCATEGORIZED: &str = "uncategorized";
+const CATEGORY_HEADING_FILLER: &[&str] = &["available", "lint", "rule", "rules"];
+/// Rules whose fix is mechanical enough to describe up front. Unknown rules
+/// get no hint rather than a guess.
+const FIX_HINTS: &[(&str, &str)] = &[
+    (
+        "max-line-length",
+        "Wrap the line: break after an opening bracket or operator, or extract a sub-expression into a variable.",
+    ),
+    (
+        "trailing-whitespace",
+        "Delete the spaces or tabs at the end of the line.",
+    ),
+    (
+        "unused-variable",
+        "Remove the variable, or prefix its name with `_` if it is intentionally unused.",
+    ),
+    (
+        "unused-argument",
+        "Prefix the argument name with `_` to mark it as intentionally unused.",
+    ),
+    (
+        "unnecessary-pass",
+        "Delete the `pass` statement; the block already has other statements.",
+    ),
+    (
+        "no-else-return",
+        "Drop the `else:` after the `return` and dedent its body.",
+    ),
+    ("class-name", "Rename the class to PascalCase."),
+    ("function-name", "Rename the function to snake_case."),
+    ("variable-name", "Rename the variable to snake_case."),
+    ("signal-name", "Rename the signal to snake_case."),
+    ("constant-name", "Rename the constant to CONSTANT_CASE."),
+];
 
 pub struct LintToolResult {
     pub success: bool,
@@ -13,10 +60,31 @@ pub struct LintToolResult {
     pub stdout: String,
     pub stderr: String,
     pub diagnostics: Vec<Value>,
+    /// Diagnostics before `dedup` grouped them.
+    pub total_diagnostics: usize,
     pub include_raw_output: bool,
     pub max_diagnostics: usize,
+    pub max_diagnostics_per_file: Option<usize>,
     pub error_count: usize,
     pub warning_count: usize,
+    pub warnings: Vec<String>,
+    pub decoded_lossy: bool,
+    pub github_annotations: Option<String>,
+    pub timings: Option<Value>,
+    pub scan_stats: Option<Value>,
+    pub annotated: Option<Vec<(String, String)>>,
+    pub counts_only: bool,
+    pub parse_warning: Option<String>,
+    pub call_summary: Value,
+    pub rule_categories: Option<BTreeMap<String, String>>,
+    pub one_line_summary: bool,
+}
+
+fn decode_lint_stdout(bytes: &[u8]) -> (String, bool) {
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(text) => (text, false),
+        Err(_) => (String::from_utf8_lossy(bytes).to_string(), true),
+    }
 }
 
 fn parse_lint_diagnostics(stdout: &str) -> Vec<Value> {
@@ -32,28 +100,37 @@ fn parse_lint_diagnostics(stdout: &str) -> Vec<Value> {
             continue;
         };
 
-        let mut parts = header.rsplitn(4, ':');
+        // Peel fields off the right so colons inside the path (e.g. a Windows
+        // drive letter) stay part of `file`.
+        let mut parts = header.rsplitn(3, ':');
         let Some(severity) = parts.next() else {
             continue;
         };
         let Some(rule) = parts.next() else {
             continue;
         };
-        let Some(line_no) = parts.next() else {
+        let Some(location) = parts.next() else {
             continue;
         };
-        let Some(file_path) = parts.next() else {
+        let Some((prefix, last)) = location.rsplit_once(':') else {
             continue;
         };
-
-        let Ok(line_number) = line_no.parse::<u64>() else {
+        let Ok(last_number) = last.parse::<u64>() else {
             continue;
         };
+        // `file:line:col` when the field before the last one is numeric too.
+        let (file_path, line_number, column) = match prefix
+            .rsplit_once(':')
+            .and_then(|(file, line)| Some((file, line.parse::<u64>().ok()?)))
+        {
+            Some((file, line)) => (file, line, json!(last_number)),
+            None => (prefix, last_number, Value::Null),
+        };
 
         diagnostics.push(json!({
             "file": file_path,
             "line": line_number,
-            "column": Value::Null,
+            "column": column,
             "rule": rule,
             "severity": severity,
             "message": message
@@ -63,60 +140,622 @@ fn parse_lint_diagnostics(stdout: &str) -> Vec<Value> {
     diagnostics
 }
 
+fn parse_mismatch_warning(
+    exit_ok: bool,
+    parsed: usize,
+    stdout: &str,
+    stderr: &str,
+) -> Option<String> {
+    if exit_ok || parsed > 0 {
+        return None;
+    }
+    let raw = if stdout.trim().is_empty() {
+        stderr
+    } else {
+        stdout
+    };
+    let snippet = raw
+        .trim()
+        .chars()
+        .take(PARSE_WARNING_SNIPPET_CHARS)
+        .collect::<String>();
+    Some(format!(
+        "Linter reported a failure but no diagnostics were parsed; raw output: {snippet}"
+    ))
+}
+
+fn escape_workflow_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_workflow_property(text: &str) -> String {
+    escape_workflow_data(text)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+fn github_annotation(diagnostic: &Value) -> String {
+    let field = |key: &str| {
+        diagnostic
+            .get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    };
+    let command = match field("severity") {
+        "error" => "error",
+        "warning" => "warning",
+        _ => "notice",
+    };
+    let mut properties = vec![format!("file={}", escape_workflow_property(field("file")))];
+    if let Some(line) = diagnostic.get("line").and_then(Value::as_u64) {
+        properties.push(format!("line={line}"));
+    }
+    if let Some(column) = diagnostic.get("column").and_then(Value::as_u64) {
+        properties.push(format!("col={column}"));
+    }
+    if !field("rule").is_empty() {
+        properties.push(format!("title={}", escape_workflow_property(field("rule"))));
+    }
+    format!(
+        "::{command} {}::{}",
+        properties.join(","),
+        escape_workflow_data(field("message"))
+    )
+}
+
+fn render_github_annotations(diagnostics: &[Value]) -> String {
+    diagnostics
+        .iter()
+        .map(github_annotation)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn resolve_max_diagnostics(
+    arguments: &Map<String, Value>,
+    default: usize,
+) -> Result<usize, String> {
+    Ok(get_optional_usize(arguments, "max_diagnostics")?.unwrap_or(default))
+}
+
+fn filter_diagnostics_by_file(
+    diagnostics: Vec<Value>,
+    include: Option<&GlobSet>,
+    exclude: Option<&GlobSet>,
+) -> Vec<Value> {
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            let file = diagnostic
+                .get("file")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            include.is_none_or(|set| set.is_match(file))
+                && !exclude.is_some_and(|set| set.is_match(file))
+        })
+        .collect()
+}
+
+/// Orders diagnostics so rules listed earlier in `priority` come first, then
+/// by file and line. Unlisted rules sort after every listed one.
+fn sort_by_rule_priority(diagnostics: &mut [Value], priority: &[String]) {
+    diagnostics.sort_by_cached_key(|diagnostic| {
+        let rule = diagnostic
+            .get("rule")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        (
+            priority
+                .iter()
+                .position(|name| name == rule)
+                .unwrap_or(priority.len()),
+            diagnostic
+                .get("file")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned(),
+            diagnostic.get("line").and_then(Value::as_u64).unwrap_or(0),
+        )
+    });
+}
+
+fn dedup_lint_diagnostics(diagnostics: &[Value]) -> Vec<Value> {
+    let mut groups: Vec<Value> = Vec::new();
+    let mut index_by_key: BTreeMap<(String, String, String), usize> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        let field = |key: &str| {
+            diagnostic
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned()
+        };
+        let key = (field("rule"), field("message"), field("severity"));
+        let location = json!({
+            "file": diagnostic["file"],
+            "line": diagnostic["line"],
+            "column": diagnostic["column"]
+        });
+        let index = *index_by_key.entry(key).or_insert_with(|| {
+            groups.push(json!({
+                "rule": diagnostic["rule"],
+                "severity": diagnostic["severity"],
+                "message": diagnostic["message"],
+                "occurrences": 0,
+                "locations": []
+            }));
+            groups.len() - 1
+        });
+        let group = &mut groups[index];
+        group["occurrences"] = json!(group["occurrences"].as_u64().unwrap_or_default() + 1);
+        if let Some(locations) = group["locations"].as_array_mut() {
+            locations.push(location);
+        }
+    }
+    groups
+}
+
+fn annotate_source(content: &str, diagnostics: &[&Value]) -> String {
+    let mut comments: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        let field = |key: &str| {
+            diagnostic
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+        };
+        let line = diagnostic.get("line").and_then(Value::as_u64).unwrap_or(1);
+        comments.entry(line.max(1)).or_default().push(format!(
+            "# LINT: {} {}: {}",
+            field("severity"),
+            field("rule"),
+            field("message")
+        ));
+    }
+
+    let mut annotated = String::with_capacity(content.len());
+    let mut line_count = 0;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        line_count = index as u64 + 1;
+        if let Some(lines) = comments.remove(&line_count) {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let indent = indent.trim_end_matches(['\r', '\n']);
+            for comment in lines {
+                annotated.push_str(indent);
+                annotated.push_str(&comment);
+                annotated.push('\n');
+            }
+        }
+        annotated.push_str(line);
+    }
+    for (_, lines) in comments.range(line_count + 1..) {
+        if !annotated.is_empty() && !annotated.ends_with('\n') {
+            annotated.push('\n');
+        }
+        for comment in lines {
+            annotated.push_str(comment);
+            annotated.push('\n');
+        }
+    }
+    annotated
+}
+
+fn annotate_files(
+    diagnostics: &[Value],
+    working_dir: Option<&Path>,
+    write: bool,
+) -> Result<Vec<(String, String)>, String> {
+    let mut by_file: BTreeMap<&str, Vec<&Value>> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        if let Some(file) = diagnostic.get("file").and_then(Value::as_str) {
+            by_file.entry(file).or_default().push(diagnostic);
+        }
+    }
+
+    let mut annotated = Vec::new();
+    for (file, file_diagnostics) in by_file {
+        let path = match working_dir {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        };
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {file} for annotation: {e}"))?;
+        let content = annotate_source(&content, &file_diagnostics);
+        if write {
+            fs::write(&path, &content)
+                .map_err(|e| format!("Failed to write annotations to {file}: {e}"))?;
+        }
+        annotated.push((file.to_owned(), content));
+    }
+    Ok(annotated)
+}
+
+const OVERRIDE_SEVERITIES: [&str; 3] = ["error", "warning", "info"];
+
+fn get_severity_overrides(arguments: &Map<String, Value>) -> Result<Map<String, Value>, String> {
+    let Some(value) = arguments.get("severity_overrides") else {
+        return Ok(Map::new());
+    };
+    let Some(overrides) = value.as_object() else {
+        return Err("`severity_overrides` must be an object of rule -> severity".to_owned());
+    };
+    for (rule, severity) in overrides {
+        if !severity
+            .as_str()
+            .is_some_and(|s| OVERRIDE_SEVERITIES.contains(&s))
+        {
+            return Err(format!(
+                "`severity_overrides.{rule}` must be one of {}",
+                OVERRIDE_SEVERITIES.join(", ")
+            ));
+        }
+    }
+    Ok(overrides.clone())
+}
+
+fn apply_severity_overrides(diagnostics: &mut [Value], overrides: &Map<String, Value>) {
+    for diagnostic in diagnostics {
+        let Some(severity) = diagnostic
+            .get("rule")
+            .and_then(Value::as_str)
+            .and_then(|rule| overrides.get(rule))
+            .cloned()
+        else {
+            continue;
+        };
+        if let Some(map) = diagnostic.as_object_mut() {
+            map.insert("severity".to_owned(), severity);
+        }
+    }
+}
+
+fn fix_hint(rule: &str) -> Option<&'static str> {
+    FIX_HINTS
+        .iter()
+        .find(|(known, _)| *known == rule)
+        .map(|(_, hint)| *hint)
+}
+
+fn attach_fix_hints(diagnostics: &mut [Value]) {
+    for diagnostic in diagnostics {
+        let Some(hint) = diagnostic
+            .get("rule")
+            .and_then(Value::as_str)
+            .and_then(fix_hint)
+        else {
+            continue;
+        };
+        if let Some(map) = diagnostic.as_object_mut() {
+            map.insert("fix_hint".to_owned(), json!(hint));
+        }
+    }
+}
+
+fn count_severity(diagnostics: &[Value], severity: &str) -> usize {
+    diagnostics
+        .iter()
+        .filter(|d| d.get("severity").and_then(Value::as_str) == Some(severity))
+        .count()
+}
+
+/// A file counts as failed when any of its diagnostics is an error after
+/// severity overrides.
+fn lint_call_summary(
+    file_count: usize,
+    flags: Value,
+    diagnostics: &[Value],
+    formatter_version: &str,
+) -> Value {
+    let failed_files = diagnostics
+        .iter()
+        .filter(|d| d.get("severity").and_then(Value::as_str) == Some("error"))
+        .filter_map(|d| d.get("file").and_then(Value::as_str))
+        .collect::<BTreeSet<_>>();
+    call_summary(
+        file_count,
+        flags,
+        file_count.saturating_sub(failed_files.len()),
+        failed_files.len(),
+        formatter_version,
+    )
+}
+
+pub fn parse_rule_list(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim().trim_start_matches(['-', '*']).trim_start();
+            let name = trimmed.split([' ', '\t', ':']).next()?;
+            let is_rule_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+            is_rule_name.then(|| name.to_owned())
+        })
+        .collect()
+}
+
+fn category_heading(line: &str) -> Option<Option<String>> {
+    let heading = line.trim().trim_start_matches('#').trim();
+    let heading = heading.strip_suffix(':')?;
+    let words = heading
+        .split_whitespace()
+        .map(str::to_ascii_lowercase)
+        .filter(|word| !CATEGORY_HEADING_FILLER.contains(&word.as_str()))
+        .collect::<Vec<_>>();
+    Some((!words.is_empty()).then(|| words.join("-")))
+}
+
+/// Maps rule names to categories from `--list-rules` output. Accepts an inline
+/// `rule (category)` / `rule [category]` tag or a `Category:` heading above a
+/// block of rules; rules with neither are left out (callers fall back to
+/// `uncategorized`).
+pub fn parse_rule_categories(text: &str) -> BTreeMap<String, String> {
+    let mut categories = BTreeMap::new();
+    let mut current = None;
+    for line in text.lines() {
+        let indented = line.starts_with([' ', '\t', '-', '*']);
+        if !indented && let Some(heading) = category_heading(line) {
+            current = heading;
+            continue;
+        }
+        let Some(rule) = parse_rule_list(line).pop() else {
+            continue;
+        };
+        let rest = line
+            .trim()
+            .trim_start_matches(['-', '*'])
+            .trim_start()
+            .trim_start_matches(rule.as_str())
+            .trim_start();
+        let inline = rest
+            .strip_prefix('(')
+            .and_then(|tag| tag.split_once(')'))
+            .or_else(|| rest.strip_prefix('[').and_then(|tag| tag.split_once(']')))
+            .map(|(tag, _)| tag.trim().to_ascii_lowercase())
+            .filter(|tag| !tag.is_empty());
+        if let Some(category) = inline.or_else(|| current.clone()) {
+            categories.insert(rule, category);
+        }
+    }
+    categories
+}
+
+/// Buckets `shown` (the projected diagnostics) by rule category, with counts
+/// taken from `all` so truncation does not hide how many each category has.
+pub fn group_diagnostics_by_category(
+    all: &[Value],
+    shown: &[Value],
+    categories: &BTreeMap<String, String>,
+) -> Value {
+    let category_of = |diagnostic: &Value| {
+        diagnostic
+            .get("rule")
+            .and_then(Value::as_str)
+            .and_then(|rule| categories.get(rule))
+            .map_or(UNCATEGORIZED, String::as_str)
+            .to_owned()
+    };
+    let mut groups: BTreeMap<String, (usize, Vec<Value>)> = BTreeMap::new();
+    for diagnostic in all {
+        // A `dedup` group stands for `occurrences` diagnostics.
+        let count = diagnostic
+            .get("occurrences")
+            .and_then(Value::as_u64)
+            .map_or(1, |n| n as usize);
+        groups.entry(category_of(diagnostic)).or_default().0 += count;
+    }
+    for diagnostic in shown {
+        groups
+            .entry(category_of(diagnostic))
+            .or_default()
+            .1
+            .push(diagnostic.clone());
+    }
+    groups
+        .into_iter()
+        .map(|(category, (count, diagnostics))| {
+            (
+                category,
+                json!({"count": count, "diagnostics": diagnostics}),
+            )
+        })
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+pub fn validate_disable_rules(
+    disable_rules: &str,
+    known_rules: &[String],
+    strict: bool,
+) -> Result<Vec<String>, String> {
+    let unknown = disable_rules
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty() && !known_rules.iter().any(|known| known == rule))
+        .collect::<Vec<_>>();
+    if unknown.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let message = format!("Unknown rule(s) in `disable_rules`: {}", unknown.join(", "));
+    if strict {
+        Err(message)
+    } else {
+        Ok(vec![message])
+    }
+}
+
 pub fn project_lint_diagnostics(
     diagnostics: &[Value],
     max_diagnostics: usize,
-) -> (Vec<Value>, bool) {
-    let projected = diagnostics
+    max_per_file: Option<usize>,
+) -> (Vec<Value>, bool, Vec<String>) {
+    let mut per_file_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut files_truncated = BTreeSet::new();
+    let per_file = diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            let Some(limit) = max_per_file else {
+                return true;
+            };
+            let file = diagnostic
+                .get("file")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let count = per_file_counts.entry(file).or_default();
+            *count += 1;
+            if *count > limit {
+                files_truncated.insert(file.to_owned());
+                return false;
+            }
+            true
+        })
+        .collect::<Vec<_>>();
+
+    let projected = per_file
         .iter()
         .take(max_diagnostics)
-        .cloned()
+        .map(|diagnostic| (*diagnostic).clone())
         .collect::<Vec<_>>();
     let truncated = diagnostics.len() > projected.len();
-    (projected, truncated)
+    (projected, truncated, files_truncated.into_iter().collect())
+}
+
+fn one_line_lint_summary(error_count: usize, warning_count: usize, total: usize) -> String {
+    format!("lint errors={error_count} warnings={warning_count} total={total}")
 }
 
+/// Warnings and GitHub annotations still follow on later lines so the first
+/// line stays the only one a log scraper needs to match.
 pub fn render_lint_summary(result: &LintToolResult) -> String {
-    format!(
-        "Lint {}. diagnostics: total={}, errors={}, warnings={}",
-        if result.success {
-            "completed successfully"
-        } else {
-            "failed"
-        },
-        result.diagnostics.len(),
-        result.error_count,
-        result.warning_count
-    )
+    let mut summary = if result.one_line_summary {
+        one_line_lint_summary(
+            result.error_count,
+            result.warning_count,
+            result.total_diagnostics,
+        )
+    } else {
+        format!(
+            "Lint {}. diagnostics: total={}, errors={}, warnings={}",
+            if result.success {
+                "completed successfully"
+            } else {
+                "failed"
+            },
+            result.total_diagnostics,
+            result.error_count,
+            result.warning_count
+        )
+    };
+    for warning in &result.warnings {
+        summary.push_str("\nWarning: ");
+        summary.push_str(warning);
+    }
+    if let Some(annotations) = &result.github_annotations
+        && !annotations.is_empty()
+    {
+        summary.push('\n');
+        summary.push_str(annotations);
+    }
+    summary
 }
 
 pub fn call_gdscript_lint(
     manager: &FormatterManager,
     arguments: &Map<String, Value>,
-) -> Result<LintToolResult, String> {
-    let files = resolve_target_files(arguments, false)?;
+) -> Result<LintToolResult, ToolError> {
+    let mut timer = PhaseTimer::start();
+    let arguments = &apply_profile(arguments)?;
+    let (files, scan_stats) = resolve_target_files(arguments, false)?;
+    enforce_max_files(&files, resolve_max_files(arguments)?)?;
     let disable_rules = get_optional_string(arguments, "disable_rules")?;
     let max_line_length = get_optional_i64(arguments, "max_line_length")?;
     let list_rules = get_bool(arguments, "list_rules")?;
     let pretty = get_bool(arguments, "pretty")?;
     let include_raw_output = get_bool(arguments, "include_raw_output")?;
-    let max_diagnostics =
-        get_optional_usize(arguments, "max_diagnostics")?.unwrap_or(DEFAULT_MAX_DIAGNOSTICS);
+    let strict_rules = get_bool(arguments, "strict_rules")?;
+    let severity_overrides = get_severity_overrides(arguments)?;
+    let github_format = match get_optional_string(arguments, "format")?.as_deref() {
+        None | Some("default") => false,
+        Some("github") => true,
+        Some(other) => {
+            return Err(format!("`format` must be \"default\" or \"github\" (got {other})").into());
+        }
+    };
+    let max_diagnostics = resolve_max_diagnostics(arguments, manager.max_diagnostics())?;
+    let max_diagnostics_per_file = get_optional_usize(arguments, "max_diagnostics_per_file")?;
+    let annotate = get_bool(arguments, "annotate")?;
+    let write = get_bool(arguments, "write")?;
+    let dedup = get_bool(arguments, "dedup")?;
+    let low_priority = get_bool(arguments, "low_priority")?;
+    let diagnostic_include = get_optional_globset(arguments, "diagnostic_include")?;
+    let diagnostic_exclude = get_optional_globset(arguments, "diagnostic_exclude")?;
+    let rule_priority = get_optional_string_array(arguments, "rule_priority")?;
+    let group_by_category = get_bool(arguments, "group_by_category")?;
+    let include_fix_hints = get_bool(arguments, "include_fix_hints")?;
+    if dedup && max_diagnostics_per_file.is_some() {
+        return Err("`dedup` cannot be combined with `max_diagnostics_per_file`"
+            .to_owned()
+            .into());
+    }
+    if write && !annotate {
+        return Err("`write` can only be used with `annotate`".to_owned().into());
+    }
+    let working_dir = resolve_working_dir(arguments)?;
 
     if let Some(value) = max_line_length
         && value < 1
     {
-        return Err("`max_line_length` must be at least 1".to_owned());
+        return Err("`max_line_length` must be at least 1".to_owned().into());
     }
     if files.is_empty() && !list_rules {
         return Err(
             "Either `files` or `dir` must resolve to at least one file unless `list_rules` is true"
-                .to_owned(),
+                .to_owned()
+                .into(),
         );
     }
 
-    let binary = manager.ensure_binary()?;
-    let mut command = Command::new(binary);
+    timer.mark("resolve_targets");
+
+    let binary = resolve_binary(manager, arguments)?;
+    enforce_min_formatter_version(manager, &binary, arguments)?;
+    timer.mark("resolve_binary");
+    let mut warnings = Vec::new();
+    if let Some(disable) = disable_rules.as_deref() {
+        match manager.cached_output(&binary, &["lint", "--list-rules"]) {
+            Ok(rule_list) => warnings.extend(validate_disable_rules(
+                disable,
+                &parse_rule_list(&rule_list),
+                strict_rules,
+            )?),
+            Err(err) => warnings.push(format!("Could not validate `disable_rules`: {err}")),
+        }
+    }
+    let rule_categories = group_by_category.then(|| {
+        match manager.cached_output(&binary, &["lint", "--list-rules"]) {
+            Ok(rule_list) => parse_rule_categories(&rule_list),
+            Err(err) => {
+                warnings.push(format!("Could not read rule categories: {err}"));
+                BTreeMap::new()
+            }
+        }
+    });
+
+    let flags = json!({
+        "disable_rules": disable_rules,
+        "max_line_length": max_line_length,
+        "list_rules": list_rules,
+        "severity_overrides": !severity_overrides.is_empty()
+    });
+
+    let mut command = formatter_command(&binary, low_priority);
+    command.env("NO_COLOR", "1");
+    if let Some(dir) = &working_dir {
+        command.current_dir(dir);
+    }
     command.arg("lint");
 
     if let Some(disable) = disable_rules {
@@ -133,22 +772,61 @@ pub fn call_gdscript_lint(
     }
     command.args(&files);
 
-    let output = command
-        .output()
+    let output = output_retrying_once(|| command.output())
         .map_err(|e| format!("Failed to execute linter: {e}"))?;
-    let stdout_text = String::from_utf8_lossy(&output.stdout).to_string();
+    timer.mark("execute");
+    let (stdout_text, decoded_lossy) = decode_lint_stdout(&output.stdout);
     let stderr_text = String::from_utf8_lossy(&output.stderr).to_string();
-    let diagnostics = parse_lint_diagnostics(&stdout_text);
-    let error_count = diagnostics
-        .iter()
-        .filter(|d| d.get("severity").and_then(Value::as_str) == Some("error"))
-        .count();
-    let warning_count = diagnostics
-        .iter()
-        .filter(|d| d.get("severity").and_then(Value::as_str) == Some("warning"))
-        .count();
-    let success = output.status.success();
+    let mut diagnostics = parse_lint_diagnostics(&strip_ansi(&stdout_text));
+    let parsed_count = diagnostics.len();
+    apply_severity_overrides(&mut diagnostics, &severity_overrides);
+    if diagnostic_include.is_some() || diagnostic_exclude.is_some() {
+        diagnostics = filter_diagnostics_by_file(
+            diagnostics,
+            diagnostic_include.as_ref(),
+            diagnostic_exclude.as_ref(),
+        );
+    }
+    if let Some(priority) = &rule_priority {
+        sort_by_rule_priority(&mut diagnostics, priority);
+    }
+    let error_count = count_severity(&diagnostics, "error");
+    let warning_count = count_severity(&diagnostics, "warning");
+    let call_summary = lint_call_summary(
+        files.len(),
+        flags,
+        &diagnostics,
+        &binary_version(manager, &binary),
+    );
+    let github_annotations = github_format.then(|| render_github_annotations(&diagnostics));
+    let annotated = if annotate {
+        Some(annotate_files(&diagnostics, working_dir.as_deref(), write)?)
+    } else {
+        None
+    };
+    let total_diagnostics = diagnostics.len();
+    if dedup {
+        diagnostics = dedup_lint_diagnostics(&diagnostics);
+    }
+    if include_fix_hints {
+        attach_fix_hints(&mut diagnostics);
+    }
     let exit_code = output.status.code().unwrap_or(-1);
+    let parse_warning = (!list_rules)
+        .then(|| {
+            parse_mismatch_warning(
+                output.status.success(),
+                parsed_count,
+                &stdout_text,
+                &stderr_text,
+            )
+        })
+        .flatten();
+    let success = if severity_overrides.is_empty() {
+        output.status.success()
+    } else {
+        matches!(exit_code, 0 | 1) && error_count == 0
+    };
 
     Ok(LintToolResult {
         success,
@@ -156,10 +834,23 @@ pub fn call_gdscript_lint(
         stdout: stdout_text,
         stderr: stderr_text,
         diagnostics,
+        total_diagnostics,
         include_raw_output,
         max_diagnostics,
+        max_diagnostics_per_file,
         error_count,
         warning_count,
+        warnings,
+        decoded_lossy,
+        github_annotations,
+        timings: get_bool(arguments, "include_timings")?.then(|| timer.to_json()),
+        scan_stats: get_bool(arguments, "scan_stats")?.then(|| scan_stats.to_json()),
+        annotated,
+        counts_only: get_bool(arguments, "counts_only")?,
+        parse_warning,
+        call_summary,
+        rule_categories,
+        one_line_summary: get_bool(arguments, "one_line_summary")?,
     })
 }
 
@@ -167,6 +858,72 @@ pub fn call_gdscript_lint(
 mod tests {
     use super::*;
 
+    fn map_from_json(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn one_line_lint_summary_format() {
+        assert_eq!(
+            one_line_lint_summary(0, 0, 0),
+            "lint errors=0 warnings=0 total=0"
+        );
+        assert_eq!(
+            one_line_lint_summary(2, 5, 7),
+            "lint errors=2 warnings=5 total=7"
+        );
+    }
+
+    #[test]
+    fn group_diagnostics_by_category_buckets_rules() {
+        let categories = parse_rule_categories(
+            "Available rules:\nStyle:\n  class-name: Class names use PascalCase\nCorrectness:\n  unused-argument\nmax-line-length [style]\nno-else-return\n",
+        );
+        assert_eq!(
+            categories.get("class-name").map(String::as_str),
+            Some("style")
+        );
+        assert_eq!(
+            categories.get("unused-argument").map(String::as_str),
+            Some("correctness")
+        );
+
+        let diagnostics = vec![
+            json!({"file":"a.gd","line":1,"severity":"warning","rule":"class-name","message":"m"}),
+            json!({"file":"a.gd","line":2,"severity":"error","rule":"unused-argument","message":"m"}),
+            json!({"file":"b.gd","line":3,"severity":"warning","rule":"mystery","message":"m"}),
+        ];
+        let grouped = group_diagnostics_by_category(&diagnostics, &diagnostics[..2], &categories);
+        assert_eq!(grouped["style"]["count"], json!(1));
+        assert_eq!(grouped["style"]["diagnostics"], json!([diagnostics[0]]));
+        assert_eq!(
+            grouped["correctness"]["diagnostics"],
+            json!([diagnostics[1]])
+        );
+        assert_eq!(
+            grouped[UNCATEGORIZED],
+            json!({"count": 1, "diagnostics": []})
+        );
+    }
+
+    #[test]
+    fn lint_call_summary_counts_files_with_errors_as_failed() {
+        let diagnostics = parse_lint_diagnostics(
+            "a.gd:1:class-name:error: bad\na.gd:2:class-name:error: bad\nb.gd:3:max-line-length:warning: long\n",
+        );
+        let flags = json!({"disable_rules": null, "max_line_length": 100});
+        assert_eq!(
+            lint_call_summary(3, flags.clone(), &diagnostics, "0.18.2"),
+            json!({
+                "file_count": 3,
+                "flags": flags,
+                "ok_count": 2,
+                "failed_count": 1,
+                "formatter_version": "0.18.2"
+            })
+        );
+    }
+
     #[test]
     fn parse_lint_diagnostics_parses_standard_output() {
         let stdout = "/tmp/a.gd:10:class-name:error: bad class name\n/tmp/a.gd:20:max-line-length:warning: too long\n";
@@ -180,14 +937,311 @@ mod tests {
         assert_eq!(diagnostics[1]["severity"], "warning");
     }
 
+    #[test]
+    fn parse_lint_diagnostics_reads_optional_column() {
+        let stdout = "/tmp/a.gd:10:class-name:error: bad class name\n/tmp/a.gd:12:5:unused-variable:warning: unused\nC:\\game\\b.gd:3:7:class-name:error: bad\n";
+        let diagnostics = parse_lint_diagnostics(stdout);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0]["line"], 10);
+        assert_eq!(diagnostics[0]["column"], Value::Null);
+        assert_eq!(diagnostics[1]["file"], "/tmp/a.gd");
+        assert_eq!(diagnostics[1]["line"], 12);
+        assert_eq!(diagnostics[1]["column"], 5);
+        assert_eq!(diagnostics[1]["rule"], "unused-variable");
+        assert_eq!(diagnostics[1]["severity"], "warning");
+        assert_eq!(diagnostics[2]["file"], "C:\\game\\b.gd");
+        assert_eq!(diagnostics[2]["line"], 3);
+        assert_eq!(diagnostics[2]["column"], 7);
+    }
+
+    #[test]
+    fn parse_lint_diagnostics_keeps_windows_drive_letter() {
+        let stdout = "C:\\project\\player.gd:10:class-name:error: bad class name\n";
+        let diagnostics = parse_lint_diagnostics(stdout);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["file"], "C:\\project\\player.gd");
+        assert_eq!(diagnostics[0]["line"], 10);
+        assert_eq!(diagnostics[0]["rule"], "class-name");
+        assert_eq!(diagnostics[0]["severity"], "error");
+    }
+
+    #[test]
+    fn resolve_max_diagnostics_prefers_the_argument() {
+        assert_eq!(resolve_max_diagnostics(&Map::new(), 50), Ok(50));
+
+        let per_call = map_from_json(json!({"max_diagnostics": 7}));
+        assert_eq!(resolve_max_diagnostics(&per_call, 50), Ok(7));
+        let invalid = map_from_json(json!({"max_diagnostics": -1}));
+        assert!(resolve_max_diagnostics(&invalid, 50).is_err());
+    }
+
+    #[test]
+    fn filter_diagnostics_by_file_keeps_matching_subdirectory() {
+        let diagnostics = parse_lint_diagnostics(
+            "game/ui/menu.gd:1:class-name:error: bad\ngame/player.gd:2:max-line-length:warning: long\ngame/ui/hud.gd:3:max-line-length:warning: long\n",
+        );
+        let include = get_optional_globset(
+            &map_from_json(json!({"diagnostic_include": ["**/ui/**"]})),
+            "diagnostic_include",
+        )
+        .expect("valid globs");
+        let exclude = get_optional_globset(
+            &map_from_json(json!({"diagnostic_exclude": ["**/hud.gd"]})),
+            "diagnostic_exclude",
+        )
+        .expect("valid globs");
+
+        let filtered = filter_diagnostics_by_file(diagnostics.clone(), include.as_ref(), None);
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.iter().all(|d| {
+            d["file"]
+                .as_str()
+                .unwrap_or_default()
+                .starts_with("game/ui/")
+        }));
+        assert_eq!(count_severity(&filtered, "warning"), 1);
+
+        let filtered = filter_diagnostics_by_file(diagnostics, include.as_ref(), exclude.as_ref());
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0]["file"], "game/ui/menu.gd");
+    }
+
+    #[test]
+    fn sort_by_rule_priority_puts_listed_rules_first() {
+        let mut diagnostics = parse_lint_diagnostics(
+            "b.gd:9:max-line-length:warning: long\na.gd:4:unused-variable:warning: unused\nb.gd:2:class-name:error: bad\na.gd:7:max-line-length:warning: long\nc.gd:1:comment-style:warning: style\n",
+        );
+        sort_by_rule_priority(
+            &mut diagnostics,
+            &["class-name".to_owned(), "max-line-length".to_owned()],
+        );
+
+        let order = diagnostics
+            .iter()
+            .map(|d| {
+                format!(
+                    "{}:{}:{}",
+                    d["rule"].as_str().unwrap_or_default(),
+                    d["file"].as_str().unwrap_or_default(),
+                    d["line"]
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            vec![
+                "class-name:b.gd:2",
+                "max-line-length:a.gd:7",
+                "max-line-length:b.gd:9",
+                "unused-variable:a.gd:4",
+                "comment-style:c.gd:1",
+            ]
+        );
+    }
+
+    #[test]
+    fn dedup_lint_diagnostics_groups_identical_entries() {
+        let diagnostics = [
+            json!({"file":"a.gd","line":1,"column":null,"severity":"warning","rule":"max-line-length","message":"too long"}),
+            json!({"file":"b.gd","line":4,"column":null,"severity":"error","rule":"class-name","message":"bad name"}),
+            json!({"file":"b.gd","line":9,"column":null,"severity":"warning","rule":"max-line-length","message":"too long"}),
+            json!({"file":"c.gd","line":2,"column":null,"severity":"warning","rule":"max-line-length","message":"too long"}),
+        ];
+
+        let grouped = dedup_lint_diagnostics(&diagnostics);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0]["rule"], "max-line-length");
+        assert_eq!(grouped[0]["occurrences"], 3);
+        assert_eq!(
+            grouped[0]["locations"],
+            json!([
+                {"file": "a.gd", "line": 1, "column": null},
+                {"file": "b.gd", "line": 9, "column": null},
+                {"file": "c.gd", "line": 2, "column": null}
+            ])
+        );
+        assert_eq!(grouped[1]["occurrences"], 1);
+    }
+
+    #[test]
+    fn parse_mismatch_warning_flags_failure_without_diagnostics() {
+        let warning = parse_mismatch_warning(false, 0, "a.gd|3|new-format|error|bad\n", "")
+            .expect("mismatch warning");
+        assert!(warning.contains("no diagnostics were parsed"));
+        assert!(warning.contains("a.gd|3|new-format|error|bad"));
+
+        assert!(parse_mismatch_warning(false, 1, "a.gd:3:rule:error: bad\n", "").is_none());
+        assert!(parse_mismatch_warning(true, 0, "", "").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filtered_out_diagnostics_do_not_trigger_parse_warning() {
+        use crate::tools::test_support::write_fake_formatter;
+
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary =
+            write_fake_formatter(temp.path(), "echo 'a.gd:1:class-name:error: bad'\nexit 1\n");
+        let manager = FormatterManager::new().expect("create manager");
+        let result = call_gdscript_lint(
+            &manager,
+            &map_from_json(json!({
+                "files": ["a.gd"],
+                "formatter_path": binary.to_string_lossy(),
+                "diagnostic_exclude": ["a.gd"]
+            })),
+        )
+        .ok()
+        .expect("lint runs");
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.parse_warning, None);
+    }
+
+    #[test]
+    fn annotate_source_places_comments_above_target_lines() {
+        let diagnostics = [
+            json!({"file":"a.gd","line":3,"severity":"warning","rule":"max-line-length","message":"too long"}),
+            json!({"file":"a.gd","line":1,"severity":"error","rule":"class-name","message":"bad name"}),
+            json!({"file":"a.gd","line":3,"severity":"error","rule":"unused-argument","message":"x unused"}),
+        ];
+        let refs = diagnostics.iter().collect::<Vec<_>>();
+        let content = "class_name bad\nfunc f(x):\n\tvar long = 1\n";
+
+        assert_eq!(
+            annotate_source(content, &refs),
+            "# LINT: error class-name: bad name\n\
+             class_name bad\n\
+             func f(x):\n\
+             \t# LINT: warning max-line-length: too long\n\
+             \t# LINT: error unused-argument: x unused\n\
+             \tvar long = 1\n"
+        );
+    }
+
     #[test]
     fn project_lint_diagnostics_respects_max() {
         let diagnostics = vec![
             json!({"file":"a.gd","line":1,"severity":"warning","rule":"x","message":"m"}),
             json!({"file":"b.gd","line":2,"severity":"error","rule":"y","message":"m"}),
         ];
-        let (projected, truncated) = project_lint_diagnostics(&diagnostics, 1);
+        let (projected, truncated, files_truncated) =
+            project_lint_diagnostics(&diagnostics, 1, None);
         assert_eq!(projected.len(), 1);
         assert!(truncated);
+        assert!(files_truncated.is_empty());
+    }
+
+    #[test]
+    fn project_lint_diagnostics_spreads_budget_across_files() {
+        let mut diagnostics = (0..10)
+            .map(|line| json!({"file":"noisy.gd","line":line,"severity":"warning","rule":"x","message":"m"}))
+            .collect::<Vec<_>>();
+        for file in ["a.gd", "b.gd", "c.gd"] {
+            diagnostics
+                .push(json!({"file":file,"line":1,"severity":"error","rule":"y","message":"m"}));
+        }
+
+        let (projected, truncated, files_truncated) =
+            project_lint_diagnostics(&diagnostics, 6, Some(2));
+        let files = projected
+            .iter()
+            .map(|d| d["file"].as_str().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(files, vec!["noisy.gd", "noisy.gd", "a.gd", "b.gd", "c.gd"]);
+        assert!(truncated);
+        assert_eq!(files_truncated, vec!["noisy.gd"]);
+    }
+
+    #[test]
+    fn validate_disable_rules_warns_about_unknown_rules() {
+        let known = parse_rule_list("Available rules:\n  class-name\n  max-line-length\n");
+        assert_eq!(known, vec!["class-name", "max-line-length"]);
+
+        let warnings = validate_disable_rules("class-name, max-line-lenght", &known, false)
+            .expect("non-strict validation");
+        assert_eq!(
+            warnings,
+            vec!["Unknown rule(s) in `disable_rules`: max-line-lenght"]
+        );
+
+        let err = validate_disable_rules("max-line-lenght", &known, true).expect_err("strict");
+        assert_eq!(err, "Unknown rule(s) in `disable_rules`: max-line-lenght");
+    }
+
+    #[test]
+    fn decode_lint_stdout_flags_invalid_utf8() {
+        let (text, lossy) = decode_lint_stdout(b"/tmp/a.gd:3:max-line-length:warning: ok\n");
+        assert!(!lossy);
+        assert_eq!(parse_lint_diagnostics(&text).len(), 1);
+
+        let (text, lossy) =
+            decode_lint_stdout(b"/tmp/caf\xe9.gd:3:max-line-length:warning: too long\n");
+        assert!(lossy);
+        let diagnostics = parse_lint_diagnostics(&text);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["file"], "/tmp/caf\u{fffd}.gd");
+    }
+
+    #[test]
+    fn parse_lint_diagnostics_ignores_ansi_colors() {
+        let stdout = "\u{1b}[1m/tmp/a.gd\u{1b}[0m:\u{1b}[33m12\u{1b}[0m:class-name:\u{1b}[31merror\u{1b}[0m: bad class name\n";
+        let diagnostics = parse_lint_diagnostics(&strip_ansi(stdout));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["file"], "/tmp/a.gd");
+        assert_eq!(diagnostics[0]["line"], 12);
+        assert_eq!(diagnostics[0]["severity"], "error");
+    }
+
+    #[test]
+    fn github_annotation_uses_workflow_command_format() {
+        let diagnostics = parse_lint_diagnostics(
+            "res/player.gd:42:max-line-length:warning: line is 120% too long, shorten it\n/tmp/a.gd:3:unused-variable:info: unused\n",
+        );
+        assert_eq!(
+            render_github_annotations(&diagnostics),
+            "::warning file=res/player.gd,line=42,title=max-line-length::line is 120%25 too long, shorten it\n::notice file=/tmp/a.gd,line=3,title=unused-variable::unused"
+        );
+    }
+
+    #[test]
+    fn severity_overrides_rewrite_severity_and_counts() {
+        let mut diagnostics = parse_lint_diagnostics(
+            "/tmp/a.gd:1:class-name:error: bad class name\n/tmp/a.gd:2:max-line-length:warning: too long\n",
+        );
+        assert_eq!(count_severity(&diagnostics, "error"), 1);
+
+        let overrides = get_severity_overrides(&map_from_json(json!({
+            "severity_overrides": {"class-name": "warning"}
+        })))
+        .expect("valid overrides");
+        apply_severity_overrides(&mut diagnostics, &overrides);
+
+        assert_eq!(diagnostics[0]["severity"], "warning");
+        assert_eq!(count_severity(&diagnostics, "error"), 0);
+        assert_eq!(count_severity(&diagnostics, "warning"), 2);
+
+        let invalid = map_from_json(json!({"severity_overrides": {"class-name": "fatal"}}));
+        assert!(get_severity_overrides(&invalid).is_err());
+    }
+
+    #[test]
+    fn fix_hints_are_attached_for_known_rules_only() {
+        let mut diagnostics = parse_lint_diagnostics(
+            "a.gd:3:max-line-length:warning: Line is too long\n\
+             a.gd:7:trailing-whitespace:warning: Trailing whitespace\n\
+             a.gd:9:some-new-rule:error: Something else\n",
+        );
+        attach_fix_hints(&mut diagnostics);
+
+        assert!(
+            diagnostics[0]["fix_hint"]
+                .as_str()
+                .is_some_and(|hint| hint.starts_with("Wrap the line"))
+        );
+        assert_eq!(
+            diagnostics[1]["fix_hint"],
+            "Delete the spaces or tabs at the end of the line."
+        );
+        assert!(diagnostics[2].get("fix_hint").is_none());
     }
 }
@@ -0,0 +1,189 @@
+use crate::config::CONFIG_FILE_NAME;
+use crate::formatter_manager::FormatterManager;
+use crate::targets::{get_optional_i64, get_optional_string};
+use crate::tools::ToolError;
+use crate::tools::lint::{parse_rule_list, validate_disable_rules};
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_LINT_PROFILE: &str = "lint";
+
+pub struct LintProfileToolResult {
+    pub path: PathBuf,
+    pub profile: String,
+    pub disable_rules: Option<String>,
+    pub max_line_length: Option<i64>,
+}
+
+fn normalize_disable_rules(disable_rules: &str) -> String {
+    disable_rules
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Writes the lint settings into `[profiles.<profile>]` of `path`, keeping any
+/// other tables already in the file. The file is this server's own config,
+/// read back through `profile`; the formatter binary never sees it. Returns
+/// the written TOML.
+pub fn write_lint_profile(
+    path: &Path,
+    profile: &str,
+    disable_rules: Option<&str>,
+    max_line_length: Option<i64>,
+) -> Result<String, String> {
+    let mut config = if path.is_file() {
+        fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?
+            .parse::<toml::Table>()
+            .map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))?
+    } else {
+        toml::Table::new()
+    };
+
+    let profiles = config
+        .entry("profiles")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| format!("`profiles` in {} must be a table", path.display()))?;
+    let section = profiles
+        .entry(profile)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| format!("Profile '{profile}' in {} must be a table", path.display()))?;
+    if let Some(disable) = disable_rules {
+        section.insert(
+            "disable_rules".to_owned(),
+            toml::Value::String(disable.to_owned()),
+        );
+    }
+    if let Some(value) = max_line_length {
+        section.insert("max_line_length".to_owned(), toml::Value::Integer(value));
+    }
+
+    let text = toml::to_string(&config)
+        .map_err(|e| format!("Failed to serialize config {}: {}", path.display(), e))?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::write(path, &text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(text)
+}
+
+pub fn lint_profile_structured_content(result: &LintProfileToolResult) -> Value {
+    let mut structured = json!({
+        "ok": true,
+        "path": result.path.to_string_lossy(),
+        "profile": result.profile
+    });
+    if let Some(map) = structured.as_object_mut() {
+        if let Some(disable) = &result.disable_rules {
+            map.insert("disable_rules".to_owned(), json!(disable));
+        }
+        if let Some(value) = result.max_line_length {
+            map.insert("max_line_length".to_owned(), json!(value));
+        }
+    }
+    structured
+}
+
+pub fn render_lint_profile_summary(result: &LintProfileToolResult) -> String {
+    format!(
+        "Lint profile '{}' written to {}.",
+        result.profile,
+        result.path.display()
+    )
+}
+
+pub fn call_gdscript_write_lint_profile(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<LintProfileToolResult, ToolError> {
+    let Some(path) = get_optional_string(arguments, "path")? else {
+        return Err(
+            format!("`path` is required (e.g. a {CONFIG_FILE_NAME} in the project root)").into(),
+        );
+    };
+    let profile = get_optional_string(arguments, "profile_name")?
+        .unwrap_or_else(|| DEFAULT_LINT_PROFILE.to_owned());
+    let disable_rules = get_optional_string(arguments, "disable_rules")?
+        .map(|rules| normalize_disable_rules(&rules));
+    let max_line_length = get_optional_i64(arguments, "max_line_length")?;
+    if let Some(value) = max_line_length
+        && value < 1
+    {
+        return Err("`max_line_length` must be at least 1".to_owned().into());
+    }
+    if disable_rules.is_none() && max_line_length.is_none() {
+        return Err(
+            "At least one of `disable_rules` or `max_line_length` is required"
+                .to_owned()
+                .into(),
+        );
+    }
+
+    if let Some(disable) = disable_rules.as_deref() {
+        let binary = manager.ensure_binary()?;
+        let rule_list = manager.cached_output(&binary, &["lint", "--list-rules"])?;
+        validate_disable_rules(disable, &parse_rule_list(&rule_list), true)?;
+    }
+
+    let path = PathBuf::from(path);
+    write_lint_profile(&path, &profile, disable_rules.as_deref(), max_line_length)?;
+    Ok(LintProfileToolResult {
+        path,
+        profile,
+        disable_rules,
+        max_line_length,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_lint_profile_merges_into_existing_config() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let path = temp.path().join("nested").join(CONFIG_FILE_NAME);
+        fs::create_dir_all(path.parent().expect("parent")).expect("create parent");
+        fs::write(&path, "[profiles.ci]\ncheck = true\n").expect("write config");
+
+        let text = write_lint_profile(
+            &path,
+            DEFAULT_LINT_PROFILE,
+            Some(&normalize_disable_rules(" class-name, ,max-line-length ")),
+            Some(120),
+        )
+        .expect("write profile");
+
+        assert_eq!(fs::read_to_string(&path).expect("read config"), text);
+        let written = text.parse::<toml::Table>().expect("valid toml");
+        assert_eq!(
+            written["profiles"]["ci"]["check"],
+            toml::Value::Boolean(true)
+        );
+        assert_eq!(
+            written["profiles"]["lint"]["disable_rules"],
+            toml::Value::String("class-name,max-line-length".to_owned())
+        );
+        assert_eq!(
+            written["profiles"]["lint"]["max_line_length"],
+            toml::Value::Integer(120)
+        );
+    }
+
+    #[test]
+    fn write_lint_profile_creates_parent_dirs() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let path = temp.path().join("a").join("b").join(CONFIG_FILE_NAME);
+
+        let text = write_lint_profile(&path, "strict", None, Some(80)).expect("write profile");
+        assert_eq!(text, "[profiles.strict]\nmax_line_length = 80\n");
+        assert!(path.is_file());
+    }
+}
@@ -1,2 +1,413 @@
+use crate::formatter_manager::{BinaryError, FormatterManager, UNKNOWN_VERSION};
+use crate::targets::get_optional_string;
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub mod buffers;
+pub mod changed;
+pub mod compare;
+pub mod config_keys;
+pub mod explain;
 pub mod format;
+pub mod format_cache;
+pub mod help;
 pub mod lint;
+pub mod lint_profile;
+pub mod preview;
+pub mod prewarm;
+pub mod update;
+pub mod version;
+
+pub struct ToolError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<String> for ToolError {
+    fn from(message: String) -> Self {
+        Self {
+            code: "tool_error",
+            message,
+        }
+    }
+}
+
+impl From<BinaryError> for ToolError {
+    fn from(err: BinaryError) -> Self {
+        Self {
+            code: err.code(),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+pub fn resolve_binary(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<PathBuf, ToolError> {
+    let Some(path) = get_optional_string(arguments, "formatter_path")? else {
+        return Ok(manager.ensure_binary()?);
+    };
+    let path = PathBuf::from(path);
+    if !path.is_file() || !is_executable(&path) {
+        return Err(ToolError {
+            code: "formatter_path_invalid",
+            message: format!(
+                "`formatter_path` is not an executable file: {}",
+                path.display()
+            ),
+        });
+    }
+    Ok(path)
+}
+
+/// Version of the binary a call actually used: the cached `VERSION` tag when
+/// it is the installed formatter, otherwise its (memoized) `--version` output.
+pub fn binary_version(manager: &FormatterManager, binary: &Path) -> String {
+    let installed = manager.installed_formatter();
+    if installed.path.as_deref() == Some(binary) {
+        return installed.version;
+    }
+    manager
+        .cached_output(binary, &["--version"])
+        .ok()
+        .map(|text| text.trim().to_owned())
+        .filter(|text| !text.is_empty())
+        .unwrap_or_else(|| UNKNOWN_VERSION.to_owned())
+}
+
+/// Compact record of a call's effective inputs and outcome that agents can
+/// log to reproduce the call later.
+pub fn call_summary(
+    file_count: usize,
+    flags: Value,
+    ok_count: usize,
+    failed_count: usize,
+    formatter_version: &str,
+) -> Value {
+    json!({
+        "file_count": file_count,
+        "flags": flags,
+        "ok_count": ok_count,
+        "failed_count": failed_count,
+        "formatter_version": formatter_version
+    })
+}
+
+#[cfg(unix)]
+const LOW_PRIORITY_NICENESS: &str = "10";
+#[cfg(windows)]
+const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+
+/// Builds the command for a formatter run, optionally at lowered scheduling
+/// priority so large jobs don't starve the editor.
+#[cfg(unix)]
+pub fn formatter_command(binary: &Path, low_priority: bool) -> Command {
+    if !low_priority {
+        return Command::new(binary);
+    }
+    let mut command = Command::new("nice");
+    command.arg("-n").arg(LOW_PRIORITY_NICENESS).arg(binary);
+    command
+}
+
+#[cfg(windows)]
+pub fn formatter_command(binary: &Path, low_priority: bool) -> Command {
+    use std::os::windows::process::CommandExt;
+    let mut command = Command::new(binary);
+    if low_priority {
+        command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+    }
+    command
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn formatter_command(binary: &Path, _low_priority: bool) -> Command {
+    Command::new(binary)
+}
+
+/// The signal that terminated a formatter process, by number and by the
+/// platform's name for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerminationSignal {
+    pub number: i32,
+    pub name: String,
+}
+
+#[cfg(unix)]
+pub fn termination_signal(status: &ExitStatus) -> Option<TerminationSignal> {
+    use std::os::unix::process::ExitStatusExt;
+    let number = status.signal()?;
+    Some(TerminationSignal {
+        number,
+        name: signal_name(number),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn termination_signal(_status: &ExitStatus) -> Option<TerminationSignal> {
+    None
+}
+
+/// Names from the target's own signal table, since numbers such as SIGBUS
+/// differ between Linux and macOS/BSD.
+#[cfg(unix)]
+fn signal_name(number: i32) -> String {
+    let name = match number {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGSYS => "SIGSYS",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        _ => return format!("SIG{number}"),
+    };
+    name.to_owned()
+}
+
+pub struct PhaseTimer {
+    start: Instant,
+    last: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimer {
+    pub fn start() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last: now,
+            phases: Vec::new(),
+        }
+    }
+
+    pub fn mark(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        self.phases.push((phase, now - self.last));
+        self.last = now;
+    }
+
+    pub fn to_json(&self) -> Value {
+        let millis = |d: Duration| json!((d.as_secs_f64() * 1_000_000.0).round() / 1_000.0);
+        let mut timings = self
+            .phases
+            .iter()
+            .map(|(phase, duration)| (format!("{phase}_ms"), millis(*duration)))
+            .collect::<Map<String, Value>>();
+        timings.insert("total_ms".to_owned(), millis(self.last - self.start));
+        Value::Object(timings)
+    }
+}
+
+const SPAWN_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+fn is_transient_spawn_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::PermissionDenied
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::ExecutableFileBusy
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+    )
+}
+
+pub fn output_retrying_once(mut run: impl FnMut() -> io::Result<Output>) -> io::Result<Output> {
+    match run() {
+        Err(err) if is_transient_spawn_error(&err) => {
+            eprintln!("Warning: transient failure spawning formatter, retrying once: {err}");
+            thread::sleep(SPAWN_RETRY_DELAY);
+            run()
+        }
+        result => result,
+    }
+}
+
+pub fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+#[cfg(all(test, unix))]
+pub mod test_support {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+
+    pub fn write_fake_formatter(dir: &Path, body: &str) -> PathBuf {
+        let path = dir.join("fake-gdscript-formatter");
+        fs::write(&path, format!("#!/bin/sh\n{body}")).expect("write fake formatter");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .expect("make fake formatter executable");
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_error_keeps_binary_error_code() {
+        let err = ToolError::from(BinaryError::EnvPathMissing("missing".to_owned()));
+        assert_eq!(err.code, "env_path_missing");
+        assert_eq!(err.message, "missing");
+
+        let err = ToolError::from("`check` must be a boolean".to_owned());
+        assert_eq!(err.code, "tool_error");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signal_name_uses_the_target_signal_numbers() {
+        assert_eq!(signal_name(libc::SIGBUS), "SIGBUS");
+        assert_eq!(signal_name(libc::SIGSEGV), "SIGSEGV");
+        assert_eq!(signal_name(libc::SIGSYS), "SIGSYS");
+        assert_eq!(signal_name(64), "SIG64");
+    }
+
+    #[test]
+    fn output_retrying_once_recovers_from_transient_spawn_failure() {
+        let mut attempts = 0;
+        let output = output_retrying_once(|| {
+            attempts += 1;
+            if attempts == 1 {
+                return Err(io::Error::from(io::ErrorKind::ExecutableFileBusy));
+            }
+            Ok(Output {
+                status: Default::default(),
+                stdout: b"ok".to_vec(),
+                stderr: Vec::new(),
+            })
+        })
+        .expect("second attempt succeeds");
+        assert_eq!(attempts, 2);
+        assert_eq!(output.stdout, b"ok");
+
+        let mut attempts = 0;
+        let err = output_retrying_once(|| {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        })
+        .expect_err("not retried");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(attempts, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn formatter_command_lowers_priority_when_requested() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = test_support::write_fake_formatter(temp.path(), "nice\n");
+        let niceness = |low_priority| {
+            let output = formatter_command(&binary, low_priority)
+                .output()
+                .expect("run fake formatter");
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .parse::<i32>()
+                .expect("niceness")
+        };
+
+        let normal = niceness(false);
+        assert!(niceness(true) > normal || normal == 19);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_binary_prefers_per_call_formatter_path() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = test_support::write_fake_formatter(temp.path(), "exit 0\n");
+        let manager = FormatterManager::new().expect("create manager");
+
+        let mut arguments = Map::new();
+        arguments.insert("formatter_path".to_owned(), json!(binary.to_string_lossy()));
+        let resolved = resolve_binary(&manager, &arguments);
+        assert!(resolved.is_ok_and(|path| path == binary));
+
+        let plain = temp.path().join("not-executable");
+        fs::write(&plain, "").expect("write plain file");
+        arguments.insert("formatter_path".to_owned(), json!(plain.to_string_lossy()));
+        let err = resolve_binary(&manager, &arguments).err();
+        assert_eq!(err.map(|err| err.code), Some("formatter_path_invalid"));
+    }
+
+    #[test]
+    fn phase_timer_reports_phases_summing_to_total() {
+        let mut timer = PhaseTimer::start();
+        for phase in ["resolve_targets", "resolve_binary", "execute"] {
+            thread::sleep(Duration::from_millis(5));
+            timer.mark(phase);
+        }
+        let timings = timer.to_json();
+
+        let phase_sum = ["resolve_targets_ms", "resolve_binary_ms", "execute_ms"]
+            .iter()
+            .map(|key| timings[key].as_f64().expect("phase timing present"))
+            .sum::<f64>();
+        let total = timings["total_ms"].as_f64().expect("total present");
+        assert!(phase_sum >= 15.0);
+        assert!((total - phase_sum).abs() < 0.01);
+    }
+
+    #[test]
+    fn strip_ansi_removes_escape_sequences() {
+        assert_eq!(
+            strip_ansi(
+                "\u{1b}[1;31merror\u{1b}[0m: \u{1b}]8;;file:///a.gd\u{7}a.gd\u{1b}]8;;\u{7}"
+            ),
+            "error: a.gd"
+        );
+        assert_eq!(strip_ansi("plain text"), "plain text");
+    }
+}
@@ -0,0 +1,258 @@
+use crate::config::apply_profile;
+use crate::formatter_manager::{FormatterManager, PendingFile};
+use crate::targets::{
+    enforce_max_files, get_optional_string, resolve_max_files, resolve_target_files,
+};
+use crate::tools::explain::unified_diff;
+use crate::tools::format::{FormatOptions, format_copy_passes};
+use crate::tools::format_cache::{bytes_hash, file_hash};
+use crate::tools::{ToolError, resolve_binary};
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct PreviewFile {
+    pub file: String,
+    pub diff: Option<String>,
+    pub reason: Option<String>,
+}
+
+pub struct PreviewToolResult {
+    pub token: Option<String>,
+    pub expires_in_secs: u64,
+    pub files: Vec<PreviewFile>,
+}
+
+pub struct ApplyToolResult {
+    pub applied_files: Vec<String>,
+}
+
+fn preview_path(options: &FormatOptions, file: &str) -> PathBuf {
+    match &options.working_dir {
+        Some(dir) => dir.join(file),
+        None => PathBuf::from(file),
+    }
+}
+
+/// Formats temp copies of `files` and returns the pending writes for files
+/// that would change, alongside per-file diffs for the caller.
+pub fn preview_files(
+    binary: &Path,
+    options: &FormatOptions,
+    files: &[String],
+) -> (Vec<PendingFile>, Vec<PreviewFile>) {
+    let mut pending = Vec::new();
+    let mut results = Vec::new();
+    for file in files {
+        let path = preview_path(options, file);
+        match format_copy_passes(binary, options, &path, 1) {
+            Ok((original, mut passes)) => {
+                let formatted = passes.remove(0);
+                let diff = unified_diff(
+                    &String::from_utf8_lossy(&original),
+                    &String::from_utf8_lossy(&formatted),
+                    "original",
+                    "formatted",
+                );
+                if formatted != original {
+                    pending.push(PendingFile {
+                        path,
+                        original_hash: bytes_hash(&original),
+                        formatted,
+                    });
+                }
+                results.push(PreviewFile {
+                    file: file.clone(),
+                    diff: Some(diff),
+                    reason: None,
+                });
+            }
+            Err(reason) => results.push(PreviewFile {
+                file: file.clone(),
+                diff: None,
+                reason: Some(reason),
+            }),
+        }
+    }
+    (pending, results)
+}
+
+/// Writes a stored preview, refusing the whole apply if any file no longer
+/// matches the content the preview was computed from.
+pub fn apply_preview(manager: &FormatterManager, token: &str) -> Result<Vec<String>, ToolError> {
+    let Some(files) = manager.take_preview(token) else {
+        return Err(ToolError {
+            code: "preview_not_found",
+            message: format!("Unknown or expired preview token: {token}"),
+        });
+    };
+
+    let stale = files
+        .iter()
+        .filter(|file| file_hash(&file.path).ok().as_deref() != Some(file.original_hash.as_str()))
+        .map(|file| file.path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    if !stale.is_empty() {
+        return Err(ToolError {
+            code: "preview_stale",
+            message: format!(
+                "Files changed since the preview, nothing was written: {}",
+                stale.join(", ")
+            ),
+        });
+    }
+
+    files
+        .iter()
+        .map(|file| {
+            fs::write(&file.path, &file.formatted)
+                .map(|()| file.path.to_string_lossy().to_string())
+                .map_err(|e| format!("Failed to write {}: {}", file.path.display(), e).into())
+        })
+        .collect()
+}
+
+pub fn preview_structured_content(result: &PreviewToolResult) -> Value {
+    let files = result
+        .files
+        .iter()
+        .map(|f| match (&f.diff, &f.reason) {
+            (Some(diff), _) => {
+                json!({"file": f.file, "ok": true, "changed": !diff.is_empty(), "diff": diff})
+            }
+            (None, reason) => json!({"file": f.file, "ok": false, "reason": reason}),
+        })
+        .collect::<Vec<_>>();
+    let mut structured = json!({
+        "ok": result.files.iter().all(|f| f.reason.is_none()),
+        "files": files
+    });
+    if let Some(token) = &result.token
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("token".to_owned(), json!(token));
+        map.insert("expires_in_secs".to_owned(), json!(result.expires_in_secs));
+    }
+    structured
+}
+
+pub fn render_preview_summary(result: &PreviewToolResult) -> String {
+    let changed = result
+        .files
+        .iter()
+        .filter(|f| f.diff.as_ref().is_some_and(|d| !d.is_empty()))
+        .count();
+    let failed = result.files.iter().filter(|f| f.reason.is_some()).count();
+    match &result.token {
+        Some(token) => format!(
+            "Format preview ready. changed={changed}, failed={failed}. Apply with token {token}."
+        ),
+        None => format!("Format preview found nothing to apply. failed={failed}."),
+    }
+}
+
+pub fn apply_structured_content(result: &ApplyToolResult) -> Value {
+    json!({
+        "ok": true,
+        "applied_files": result.applied_files
+    })
+}
+
+pub fn render_apply_summary(result: &ApplyToolResult) -> String {
+    format!(
+        "Format preview applied. file_count={}.",
+        result.applied_files.len()
+    )
+}
+
+pub fn call_gdscript_format_preview(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<PreviewToolResult, ToolError> {
+    let arguments = &apply_profile(arguments)?;
+    let (files, _) = resolve_target_files(arguments, true)?;
+    enforce_max_files(&files, resolve_max_files(arguments)?)?;
+    let options = FormatOptions::from_arguments(arguments)?;
+    let binary = resolve_binary(manager, arguments)?;
+
+    let (pending, files) = preview_files(&binary, &options, &files);
+    let token = (!pending.is_empty()).then(|| manager.store_preview(pending));
+    Ok(PreviewToolResult {
+        token,
+        expires_in_secs: manager.preview_ttl().as_secs(),
+        files,
+    })
+}
+
+pub fn call_gdscript_format_apply(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<ApplyToolResult, ToolError> {
+    let token =
+        get_optional_string(arguments, "token")?.ok_or_else(|| "`token` is required".to_owned())?;
+    Ok(ApplyToolResult {
+        applied_files: apply_preview(manager, &token)?,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::write_fake_formatter;
+
+    const COLLAPSE_SPACES: &str = "for last; do :; done\nsed 's/  */ /g' \"$last\" > \"$last.tmp\" && mv \"$last.tmp\" \"$last\"\n";
+
+    #[test]
+    fn apply_writes_previewed_content() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = write_fake_formatter(temp.path(), COLLAPSE_SPACES);
+        let file = temp.path().join("player.gd");
+        fs::write(&file, "var  speed  =  1\n").expect("write file");
+        let files = vec![file.to_string_lossy().to_string()];
+        let manager = FormatterManager::new().expect("create manager");
+
+        let (pending, results) = preview_files(&binary, &FormatOptions::default(), &files);
+        assert_eq!(pending.len(), 1);
+        assert!(results[0].diff.as_deref().is_some_and(|d| !d.is_empty()));
+        assert_eq!(
+            fs::read_to_string(&file).expect("read file"),
+            "var  speed  =  1\n"
+        );
+
+        let token = manager.store_preview(pending);
+        assert!(apply_preview(&manager, &token).is_ok_and(|applied| applied == files));
+        assert_eq!(
+            fs::read_to_string(&file).expect("read file"),
+            "var speed = 1\n"
+        );
+        let reused = apply_preview(&manager, &token).err().map(|err| err.code);
+        assert_eq!(reused, Some("preview_not_found"));
+    }
+
+    #[test]
+    fn apply_rejects_stale_preview() {
+        let temp = tempfile::tempdir().expect("create temp dir");
+        let binary = write_fake_formatter(temp.path(), COLLAPSE_SPACES);
+        let first = temp.path().join("a.gd");
+        let second = temp.path().join("b.gd");
+        fs::write(&first, "var  a  =  1\n").expect("write a.gd");
+        fs::write(&second, "var  b  =  2\n").expect("write b.gd");
+        let files = [&first, &second]
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        let manager = FormatterManager::new().expect("create manager");
+
+        let (pending, _) = preview_files(&binary, &FormatOptions::default(), &files);
+        let token = manager.store_preview(pending);
+        fs::write(&second, "var  b  =  3\n").expect("edit b.gd after preview");
+
+        let err = apply_preview(&manager, &token).err();
+        assert_eq!(err.as_ref().map(|err| err.code), Some("preview_stale"));
+        assert!(err.is_some_and(|err| err.message.contains(&second.to_string_lossy().to_string())));
+        assert_eq!(
+            fs::read_to_string(&first).expect("read a.gd"),
+            "var  a  =  1\n"
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use crate::formatter_manager::{BinaryStatus, CacheRootSelection, FormatterManager};
+use crate::tools::ToolError;
+use serde_json::{Map, Value, json};
+
+pub fn prewarm_structured_content(status: &BinaryStatus, cache_root: &CacheRootSelection) -> Value {
+    json!({
+        "ok": true,
+        "path": status.path.to_string_lossy(),
+        "version": status.version,
+        "downloaded": status.downloaded,
+        "cache_root": {
+            "path": cache_root.path.to_string_lossy(),
+            "source": cache_root.source,
+            "rejected": cache_root.rejected
+        }
+    })
+}
+
+pub fn render_prewarm_summary(status: &BinaryStatus) -> String {
+    format!(
+        "Formatter ready. version={}, downloaded={}.",
+        status.version.as_deref().unwrap_or("unknown"),
+        status.downloaded
+    )
+}
+
+pub fn call_gdscript_prewarm(
+    manager: &FormatterManager,
+    _arguments: &Map<String, Value>,
+) -> Result<BinaryStatus, ToolError> {
+    Ok(manager.ensure_binary_status()?)
+}
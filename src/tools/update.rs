@@ -0,0 +1,104 @@
+use crate::formatter_manager::{FORMATTER_VERSION_ENV, FormatterManager, UpdateOutcome};
+use crate::targets::{get_optional_bool, get_optional_string};
+use crate::tools::ToolError;
+use serde_json::{Map, Value, json};
+
+/// The `GDSCRIPT_FORMATTER_VERSION` tag when it makes other tools ignore
+/// what this update installed.
+fn overriding_env_pin(outcome: &UpdateOutcome) -> Option<&str> {
+    outcome
+        .env_pin
+        .as_deref()
+        .filter(|tag| Some(*tag) != outcome.status.version.as_deref())
+}
+
+pub fn update_structured_content(outcome: &UpdateOutcome) -> Value {
+    let status = &outcome.status;
+    let mut structured = json!({
+        "ok": true,
+        "tag": status.version,
+        "path": status.path.to_string_lossy(),
+        "downloaded": status.downloaded,
+        "pinned": outcome.pinned
+    });
+    if let Some(tag) = overriding_env_pin(outcome)
+        && let Some(map) = structured.as_object_mut()
+    {
+        map.insert("env_pin".to_owned(), json!(tag));
+    }
+    structured
+}
+
+pub fn render_update_summary(outcome: &UpdateOutcome) -> String {
+    let status = &outcome.status;
+    let mut summary = format!(
+        "Formatter re-downloaded. tag={}, path={}, pinned={}.",
+        status.version.as_deref().unwrap_or("unknown"),
+        status.path.display(),
+        outcome.pinned.as_deref().unwrap_or("none")
+    );
+    if let Some(tag) = overriding_env_pin(outcome) {
+        summary.push_str(&format!(
+            " {FORMATTER_VERSION_ENV}={tag} is set, so other tools keep using {tag}."
+        ));
+    }
+    summary
+}
+
+pub fn call_gdscript_update(
+    manager: &FormatterManager,
+    arguments: &Map<String, Value>,
+) -> Result<UpdateOutcome, ToolError> {
+    let tag = get_optional_string(arguments, "tag")?;
+    let pin = get_optional_bool(arguments, "pin")?;
+    if pin == Some(true) && tag.is_none() {
+        return Err("`pin: true` requires `tag`".to_owned().into());
+    }
+    Ok(manager.force_update(tag.as_deref(), pin)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter_manager::BinaryStatus;
+    use std::path::PathBuf;
+
+    #[test]
+    fn update_reports_pin_and_env_override() {
+        let outcome = UpdateOutcome {
+            status: BinaryStatus {
+                path: PathBuf::from("/cache/versions/0.18.2/gdscript-formatter"),
+                version: Some("0.18.2".to_owned()),
+                downloaded: true,
+            },
+            pinned: Some("0.18.2".to_owned()),
+            env_pin: Some("0.18.1".to_owned()),
+        };
+        let structured = update_structured_content(&outcome);
+        assert_eq!(structured["pinned"], "0.18.2");
+        assert_eq!(structured["env_pin"], "0.18.1");
+        assert!(render_update_summary(&outcome).contains("GDSCRIPT_FORMATTER_VERSION=0.18.1"));
+
+        let unpinned = UpdateOutcome {
+            pinned: None,
+            env_pin: Some("0.18.2".to_owned()),
+            ..outcome
+        };
+        let structured = update_structured_content(&unpinned);
+        assert_eq!(structured["pinned"], Value::Null);
+        assert!(structured.get("env_pin").is_none());
+    }
+
+    #[test]
+    fn pin_without_tag_is_rejected() {
+        let manager = FormatterManager::new().expect("create manager");
+        let arguments = json!({"pin": true})
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let err = call_gdscript_update(&manager, &arguments)
+            .err()
+            .expect("error");
+        assert_eq!(err.message, "`pin: true` requires `tag`");
+    }
+}
@@ -0,0 +1,113 @@
+use crate::formatter_manager::{FormatterManager, InstalledFormatter};
+use crate::targets::get_optional_string;
+use crate::tools::ToolError;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+
+fn parse_version(text: &str) -> Option<(u64, u64, u64)> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .map(|token| token.trim_start_matches('v'))
+        .find_map(|token| {
+            let core = token.split(['-', '+']).next()?;
+            let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+            let major = parts.next()??;
+            let minor = parts.next()??;
+            let patch = parts.next().unwrap_or(Some(0))?;
+            parts.next().is_none().then_some((major, minor, patch))
+        })
+}
+
+fn check_min_version(reported: &str, minimum: &str) -> Result<(), ToolError> {
+    let required = parse_version(minimum).ok_or_else(|| {
+        format!("`min_formatter_version` must be a semver version like 0.18.0 (got {minimum})")
+    })?;
+    let actual = parse_version(reported).ok_or_else(|| {
+        format!(
+            "Could not parse formatter version from `--version` output: {}",
+            reported.trim()
+        )
+    })?;
+    if actual < required {
+        return Err(ToolError {
+            code: "formatter_too_old",
+            message: format!(
+                "Formatter version {}.{}.{} is older than the required `min_formatter_version` {minimum}",
+                actual.0, actual.1, actual.2
+            ),
+        });
+    }
+    Ok(())
+}
+
+pub fn enforce_min_formatter_version(
+    manager: &FormatterManager,
+    binary: &Path,
+    arguments: &Map<String, Value>,
+) -> Result<(), ToolError> {
+    let Some(minimum) = get_optional_string(arguments, "min_formatter_version")? else {
+        return Ok(());
+    };
+    let reported = manager.cached_output(binary, &["--version"])?;
+    check_min_version(&reported, &minimum)
+}
+
+pub fn version_structured_content(installed: &InstalledFormatter) -> Value {
+    json!({
+        "ok": true,
+        "server_version": env!("CARGO_PKG_VERSION"),
+        "formatter_version": installed.version,
+        "formatter_path": installed
+            .path
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string())
+    })
+}
+
+pub fn render_version_summary(installed: &InstalledFormatter) -> String {
+    format!(
+        "Server version={}, formatter version={}.",
+        env!("CARGO_PKG_VERSION"),
+        installed.version
+    )
+}
+
+pub fn call_gdscript_version(
+    manager: &FormatterManager,
+    _arguments: &Map<String, Value>,
+) -> Result<InstalledFormatter, ToolError> {
+    Ok(manager.installed_formatter())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_min_version_compares_reported_version() {
+        assert_eq!(
+            parse_version("gdscript-formatter 0.18.2\n"),
+            Some((0, 18, 2))
+        );
+        assert!(check_min_version("gdscript-formatter 0.18.2", "0.18.0").is_ok());
+        assert!(check_min_version("gdscript-formatter v0.18.2", "0.18.2").is_ok());
+
+        let err = check_min_version("gdscript-formatter 0.17.9", "0.18").expect_err("too old");
+        assert_eq!(err.code, "formatter_too_old");
+        assert_eq!(
+            err.message,
+            "Formatter version 0.17.9 is older than the required `min_formatter_version` 0.18"
+        );
+        assert!(check_min_version("gdscript-formatter 0.18.2", "latest").is_err());
+    }
+
+    #[test]
+    fn version_content_reports_null_path_without_formatter() {
+        let missing = InstalledFormatter {
+            path: None,
+            version: "unknown".to_owned(),
+        };
+        let structured = version_structured_content(&missing);
+        assert_eq!(structured["formatter_path"], Value::Null);
+        assert_eq!(structured["formatter_version"], "unknown");
+    }
+}